@@ -0,0 +1,119 @@
+#[macro_use]
+extern crate criterion;
+extern crate sexe_expression;
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use criterion::Criterion;
+use sexe_expression::builder::*;
+use sexe_expression::*;
+
+/// Evaluates every point separately, the way sweeps did before constants were hoisted.
+fn sweep_without_hoisting(func: &ExpressionNode, resolution: u32) -> Vec<(f64, f64)> {
+    let positions = sample_positions(-10.0, 10.0, resolution, Spacing::Linear).unwrap();
+    let mut vars_map = HashMap::new();
+    positions
+        .into_iter()
+        .filter_map(|x| {
+            vars_map.insert("x".to_string(), x);
+            func.evaluate(&vars_map).ok().map(|y| (x, y))
+        })
+        .collect()
+}
+
+fn constant_hoisting(c: &mut Criterion) {
+    let func = sin(var("x")) + ln(constant(123_456.0)) * constant(PI).pow(constant(2.0));
+
+    c.bench_function("sweep without hoisting", |b| {
+        b.iter(|| sweep_without_hoisting(&func, 1000))
+    });
+    c.bench_function("sweep with hoisting", |b| {
+        b.iter(|| evaluate_function_over_domain(-10.0, 10.0, 1000, &func))
+    });
+}
+
+fn domain_sweep(c: &mut Criterion) {
+    let func = sin(var("x")) * cos(var("x"));
+
+    c.bench_function("sweep sin(x) * cos(x) looking x up by name", |b| {
+        b.iter(|| sweep_without_hoisting(&func, 10_000))
+    });
+    c.bench_function("sweep sin(x) * cos(x)", |b| {
+        b.iter(|| evaluate_function_over_domain(-10.0, 10.0, 10_000, &func))
+    });
+}
+
+fn integer_powers(c: &mut Criterion) {
+    let x = || var("x");
+    let func = x().pow(constant(4.0)) + x().pow(constant(3.0)) + x().pow(constant(2.0));
+
+    c.bench_function("sweep x^4 + x^3 + x^2", |b| {
+        b.iter(|| evaluate_function_over_domain(-10.0, 10.0, 10_000, &func))
+    });
+}
+
+fn variable_lookup(c: &mut Criterion) {
+    let func = var("x") * var("y") + sin(var("x")) / var("y");
+    let mut vars_map = HashMap::new();
+    vars_map.insert("x".to_string(), 0.5);
+    vars_map.insert("y".to_string(), 2.0);
+
+    c.bench_function("evaluate with variables looked up by name", |b| {
+        b.iter(|| func.evaluate(&vars_map))
+    });
+    let compiled = func.compile();
+    let values = compiled.bind(&vars_map).unwrap();
+    c.bench_function("evaluate with variables looked up by symbol", |b| {
+        b.iter(|| compiled.evaluate(&values))
+    });
+
+    // A sweep looks the variables up once, then only sets the value of the symbol of `x` at every
+    // point, rather than hashing its name for every occurrence at every point.
+    let positions = sample_positions(-10.0, 10.0, 10_000, Spacing::Linear).unwrap();
+    c.bench_function("sweep with variables looked up by name", |b| {
+        b.iter(|| {
+            let mut vars_map = vars_map.clone();
+            positions
+                .iter()
+                .map(|&x| {
+                    vars_map.insert("x".to_string(), x);
+                    func.evaluate(&vars_map)
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+    c.bench_function("sweep with variables looked up by symbol", |b| {
+        b.iter(|| evaluate_function_over_domain_with(-10.0, 10.0, 10_000, &func, "x", &vars_map))
+    });
+}
+
+fn arena_evaluation(c: &mut Criterion) {
+    // sin(x * 1) / 1 + sin(x * 2) / 2 + ... + sin(x * 8) / 8, which has 55 nodes
+    let func = (1..=8)
+        .map(|i| {
+            let i = f64::from(i);
+            sin(var("x") * constant(i)) / constant(i)
+        })
+        .fold(None, |acc, term| match acc {
+            None => Some(term),
+            Some(acc) => Some(acc + term),
+        })
+        .unwrap();
+    let mut vars_map = HashMap::new();
+    vars_map.insert("x".to_string(), 0.5);
+
+    c.bench_function("evaluate a boxed tree", |b| b.iter(|| func.evaluate(&vars_map)));
+    let arena = ExpressionArena::from_tree(&func);
+    c.bench_function("evaluate an arena", |b| b.iter(|| arena.evaluate(&[0.5])));
+}
+
+criterion_group!(
+    benches,
+    constant_hoisting,
+    domain_sweep,
+    integer_powers,
+    variable_lookup,
+    arena_evaluation
+);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+#[macro_use]
+extern crate criterion;
+extern crate sexe_expression;
+
+use criterion::Criterion;
+use sexe_expression::builder::*;
+use sexe_expression::*;
+
+fn polynomial_sweep(c: &mut Criterion) {
+    // 3x^3 - 2x^2 + x - 5
+    let func = constant(3.0) * var("x").pow(constant(3.0)) - constant(2.0) * var("x").pow(constant(2.0))
+        + var("x")
+        - constant(5.0);
+    let compiled = func.compile();
+    let xs = sample_positions(-10.0, 10.0, 10_000, Spacing::Linear).unwrap();
+    let mut out = vec![0.0; xs.len()];
+
+    c.bench_function("sweep a polynomial one point at a time", |b| {
+        b.iter(|| {
+            for (x, out) in xs.iter().zip(out.iter_mut()) {
+                *out = compiled.evaluate(&[*x]).unwrap();
+            }
+        })
+    });
+    c.bench_function("sweep a polynomial in batches", |b| {
+        b.iter(|| compiled.eval_simd(&xs, &mut out).unwrap())
+    });
+}
+
+criterion_group!(benches, polynomial_sweep);
+criterion_main!(benches);
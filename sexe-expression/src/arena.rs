@@ -0,0 +1,452 @@
+//! An alternative representation of expression trees, with every node stored in a single vector
+//! and referring to its children by index rather than by pointer.
+
+use super::*;
+
+/// The position of a node in an `ExpressionArena`.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A node of an `ExpressionArena`. These mirror the variants of `ExpressionNode`, except that
+/// variables are interned in the arena's `SymbolTable`, and the children of N-ary operators and
+/// function calls are a range of the arena's child lists, see `ExpressionArena::children`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ArenaNode {
+    Binary {
+        operator: BinaryOperator,
+        left: NodeId,
+        right: NodeId,
+    },
+    Unary {
+        operator: UnaryOperator,
+        child: NodeId,
+    },
+    Nary {
+        operator: NaryOperator,
+        first_child: usize,
+        child_count: usize,
+    },
+    /// A sum or product with bounds and a body, i.e. one that can be evaluated. The nodes of its
+    /// body are the ones right after the sum itself, up to and including `body_end`, so that they
+    /// can be evaluated once per index. `operator` is the `NaryOperator` it was built from, whose
+    /// index variable is interned as `index`.
+    Sum {
+        operator: NaryOperator,
+        index: Symbol,
+        lower: NodeId,
+        upper: NodeId,
+        body: NodeId,
+        body_end: NodeId,
+    },
+    FunctionCall {
+        name: String,
+        first_child: usize,
+        child_count: usize,
+    },
+    Variable(Symbol),
+    Constant(f64),
+}
+
+/// An expression tree stored as a flat vector of nodes. Every node comes after its children,
+/// except for the bodies of sums, which come right after their sum, so evaluating the nodes in
+/// order never has to chase a pointer nor recurse.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExpressionArena {
+    nodes: Vec<ArenaNode>,
+    child_lists: Vec<NodeId>,
+    symbols: SymbolTable,
+    root: NodeId,
+}
+
+impl ExpressionArena {
+    /// Copies `tree` into an arena.
+    pub fn from_tree(tree: &ExpressionNode) -> ExpressionArena {
+        let mut arena = ExpressionArena {
+            nodes: Vec::new(),
+            child_lists: Vec::new(),
+            symbols: SymbolTable::new(),
+            root: NodeId(0),
+        };
+        arena.root = arena.add(tree);
+        arena
+    }
+
+    fn push(&mut self, node: ArenaNode) -> NodeId {
+        self.nodes.push(node);
+        NodeId(self.nodes.len() as u32 - 1)
+    }
+
+    fn add_children(&mut self, children: &[ExpressionNode]) -> (usize, usize) {
+        let ids: Vec<NodeId> = children.iter().map(|child| self.add(child)).collect();
+        let first_child = self.child_lists.len();
+        self.child_lists.extend(ids);
+        (first_child, children.len())
+    }
+
+    fn add(&mut self, tree: &ExpressionNode) -> NodeId {
+        match tree {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                let left = self.add(left_node);
+                let right = self.add(right_node);
+                self.push(ArenaNode::Binary {
+                    operator: operator.clone(),
+                    left,
+                    right,
+                })
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                let child = self.add(child_node);
+                self.push(ArenaNode::Unary {
+                    operator: operator.clone(),
+                    child,
+                })
+            }
+            ExpressionNode::NaryExprNode {
+                operator: operator @ (NaryOperator::Sum { index_key } | NaryOperator::Product { index_key }),
+                child_nodes,
+            } if child_nodes.len() == 3 => {
+                let lower = self.add(&child_nodes[0]);
+                let upper = self.add(&child_nodes[1]);
+                let index = self.symbols.intern(index_key);
+                // The body isn't known yet, it is filled in once it has been added.
+                let sum = self.push(ArenaNode::Constant(0.0));
+                let body = self.add(&child_nodes[2]);
+                let body_end = NodeId(self.nodes.len() as u32 - 1);
+                self.nodes[sum.index()] = ArenaNode::Sum {
+                    operator: operator.clone(),
+                    index,
+                    lower,
+                    upper,
+                    body,
+                    body_end,
+                };
+                sum
+            }
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => {
+                let (first_child, child_count) = self.add_children(child_nodes);
+                self.push(ArenaNode::Nary {
+                    operator: operator.clone(),
+                    first_child,
+                    child_count,
+                })
+            }
+            ExpressionNode::FunctionCallNode { name, args } => {
+                let (first_child, child_count) = self.add_children(args);
+                self.push(ArenaNode::FunctionCall {
+                    name: name.clone(),
+                    first_child,
+                    child_count,
+                })
+            }
+            ExpressionNode::VariableExprNode { variable_key } => {
+                let symbol = self.symbols.intern(variable_key);
+                self.push(ArenaNode::Variable(symbol))
+            }
+            ExpressionNode::ConstantExprNode { value } => self.push(ArenaNode::Constant(*value)),
+        }
+    }
+
+    /// Copies the arena back into a boxed tree.
+    pub fn to_tree(&self) -> ExpressionNode {
+        self.tree_at(self.root)
+    }
+
+    fn tree_at(&self, id: NodeId) -> ExpressionNode {
+        match &self.nodes[id.index()] {
+            ArenaNode::Binary {
+                operator,
+                left,
+                right,
+            } => ExpressionNode::BinaryExprNode {
+                operator: operator.clone(),
+                left_node: Box::new(self.tree_at(*left)),
+                right_node: Box::new(self.tree_at(*right)),
+            },
+            ArenaNode::Unary { operator, child } => ExpressionNode::UnaryExprNode {
+                operator: operator.clone(),
+                child_node: Box::new(self.tree_at(*child)),
+            },
+            ArenaNode::Nary { operator, .. } => ExpressionNode::NaryExprNode {
+                operator: operator.clone(),
+                child_nodes: self.trees_at(self.children(id)),
+            },
+            ArenaNode::Sum {
+                operator,
+                lower,
+                upper,
+                body,
+                ..
+            } => ExpressionNode::NaryExprNode {
+                operator: operator.clone(),
+                child_nodes: self.trees_at(&[*lower, *upper, *body]),
+            },
+            ArenaNode::FunctionCall { name, .. } => ExpressionNode::FunctionCallNode {
+                name: name.clone(),
+                args: self.trees_at(self.children(id)),
+            },
+            ArenaNode::Variable(symbol) => ExpressionNode::VariableExprNode {
+                variable_key: self.symbols.name(*symbol).to_string(),
+            },
+            ArenaNode::Constant(value) => ExpressionNode::ConstantExprNode { value: *value },
+        }
+    }
+
+    fn trees_at(&self, ids: &[NodeId]) -> Vec<ExpressionNode> {
+        ids.iter().map(|id| self.tree_at(*id)).collect()
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    pub fn node(&self, id: NodeId) -> &ArenaNode {
+        &self.nodes[id.index()]
+    }
+
+    /// The number of nodes in the arena.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The children of an N-ary operator or function call, or nothing for other nodes.
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        match &self.nodes[id.index()] {
+            ArenaNode::Nary {
+                first_child,
+                child_count,
+                ..
+            }
+            | ArenaNode::FunctionCall {
+                first_child,
+                child_count,
+                ..
+            } => &self.child_lists[*first_child..*first_child + *child_count],
+            _ => &[],
+        }
+    }
+
+    /// The symbols of the variables, which index the slice of values the arena is evaluated
+    /// against.
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// Evaluates the expression with the value of each variable at the index of its symbol in
+    /// `values`, like `CompiledExpression::evaluate`. This uses the default settings of
+    /// `EvaluationContext`.
+    pub fn evaluate(&self, values: &[f64]) -> Result<f64, EvaluationError> {
+        self.evaluate_ctx(values, &EvaluationContext::new())
+    }
+
+    /// Like `evaluate`, but with the settings and functions of `ctx`. Its variables are ignored.
+    pub fn evaluate_ctx(&self, values: &[f64], ctx: &EvaluationContext) -> Result<f64, EvaluationError> {
+        if values.len() < self.symbols.len() {
+            return Err(EvaluationError::VariableNotFoundError);
+        }
+        let mut results = vec![0.0; self.nodes.len()];
+        let mut indices = Vec::new();
+        self.run(0, self.nodes.len(), values, &mut results, &mut indices, ctx)?;
+        Ok(results[self.root.index()])
+    }
+
+    /// Evaluates the nodes `start..end` in order, storing the value of each in `results`. The
+    /// indices of the sums being evaluated are in `indices`, innermost last, and shadow `values`.
+    fn run(
+        &self,
+        start: usize,
+        end: usize,
+        values: &[f64],
+        results: &mut [f64],
+        indices: &mut Vec<(Symbol, f64)>,
+        ctx: &EvaluationContext,
+    ) -> Result<(), EvaluationError> {
+        let mut i = start;
+        while i < end {
+            let (value, any_operand_is_nan) = match &self.nodes[i] {
+                ArenaNode::Binary {
+                    operator,
+                    left,
+                    right,
+                } => {
+                    let (left_value, right_value) = (results[left.index()], results[right.index()]);
+                    let value = match (operator, &self.nodes[right.index()]) {
+                        (BinaryOperator::Exponentiation, ArenaNode::Constant(exponent)) => {
+                            ConstantPower::new(*exponent).apply(left_value)
+                        }
+                        _ => operator.apply(left_value, right_value),
+                    };
+                    (value, left_value.is_nan() || right_value.is_nan())
+                }
+                ArenaNode::Unary { operator, child } => {
+                    let child_value = results[child.index()];
+                    (operator.apply(child_value, ctx.angle_mode)?, child_value.is_nan())
+                }
+                ArenaNode::Nary { operator, .. } => {
+                    let child_values: Vec<f64> = self
+                        .children(NodeId(i as u32))
+                        .iter()
+                        .map(|id| results[id.index()])
+                        .collect();
+                    (
+                        operator.apply(&child_values)?,
+                        child_values.iter().any(|value| value.is_nan()),
+                    )
+                }
+                ArenaNode::Sum {
+                    operator,
+                    index,
+                    lower,
+                    upper,
+                    body,
+                    body_end,
+                } => {
+                    let mut total = operator.empty_value();
+                    match series_indices(results[lower.index()], results[upper.index()])? {
+                        Some(ks) => {
+                            for k in ks {
+                                indices.push((*index, k as f64));
+                                self.run(i + 1, body_end.index() + 1, values, results, indices, ctx)?;
+                                indices.pop();
+                                total = operator.accumulate(total, results[body.index()]);
+                            }
+                        }
+                        None => total = f64::NAN,
+                    }
+                    // This mirrors `evaluate_series`, which doesn't check the sum itself for NaN.
+                    results[i] = total;
+                    i = body_end.index() + 1;
+                    continue;
+                }
+                ArenaNode::FunctionCall { name, .. } => {
+                    let arg_values: Vec<f64> = self
+                        .children(NodeId(i as u32))
+                        .iter()
+                        .map(|id| results[id.index()])
+                        .collect();
+                    (
+                        ctx.call_function(name, &arg_values)?,
+                        arg_values.iter().any(|value| value.is_nan()),
+                    )
+                }
+                ArenaNode::Variable(symbol) => {
+                    results[i] = match indices.iter().rev().find(|(index, _)| index == symbol) {
+                        Some((_, value)) => *value,
+                        None => values[symbol.index()],
+                    };
+                    i += 1;
+                    continue;
+                }
+                ArenaNode::Constant(value) => (*value, true),
+            };
+            if ctx.strict && value.is_nan() && !any_operand_is_nan {
+                return Err(EvaluationError::NotANumberError);
+            }
+            results[i] = value;
+            i += 1;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a ExpressionNode> for ExpressionArena {
+    fn from(tree: &'a ExpressionNode) -> ExpressionArena {
+        ExpressionArena::from_tree(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{call, constant, exp, log, product, sin, sum, var};
+
+    fn expressions() -> Vec<ExpressionNode> {
+        vec![
+            sin(var("x")) * exp(var("x") / constant(3.0)) + var("x").pow(var("y")),
+            log(constant(2.0), var("x")) - call("double", vec![var("y"), var("x")]),
+            sum(
+                "j",
+                constant(1.0),
+                var("y"),
+                sum("k", var("j"), constant(3.0), var("j") * var("k")),
+            ) + var("x"),
+            product("k", var("x"), constant(4.0), var("k") - var("y")),
+            // sum(k, 1, 3)
+            ExpressionNode::nary(
+                NaryOperator::Sum {
+                    index_key: "k".to_string(),
+                },
+                vec![constant(1.0), constant(3.0)],
+            ),
+        ]
+    }
+
+    #[test]
+    fn arenas_round_trip() {
+        for expression in expressions() {
+            let arena = ExpressionArena::from_tree(&expression);
+            assert_eq!(arena.to_tree(), expression);
+        }
+    }
+
+    #[test]
+    fn nodes_refer_to_their_children() {
+        let arena = ExpressionArena::from(&(sin(var("x")) + var("x")));
+        assert_eq!(arena.len(), 4);
+        assert_eq!(arena.root(), NodeId(3));
+        assert_eq!(
+            arena.node(NodeId(3)),
+            &ArenaNode::Binary {
+                operator: BinaryOperator::Addition,
+                left: NodeId(1),
+                right: NodeId(2),
+            }
+        );
+        assert_eq!(arena.node(NodeId(0)), &ArenaNode::Variable(Symbol(0)));
+        assert_eq!(arena.node(NodeId(2)), &ArenaNode::Variable(Symbol(0)));
+    }
+
+    #[test]
+    fn arenas_evaluate_like_trees() {
+        let mut ctx = EvaluationContext::new();
+        ctx.register_function("double", 2, |args| Ok(2.0 * args[0] + args[1]));
+        for expression in expressions() {
+            let arena = ExpressionArena::from_tree(&expression);
+            for &x in &[-2.0, 0.0, 0.5, 3.0] {
+                ctx.variables.insert("x".to_string(), x);
+                ctx.variables.insert("y".to_string(), 2.0);
+                let values: Vec<f64> = arena
+                    .symbols()
+                    .iter()
+                    .map(|(_, name)| ctx.variables.get(name).cloned().unwrap_or(0.0))
+                    .collect();
+
+                let expected = expression.evaluate_ctx(&ctx);
+                let actual = arena.evaluate_ctx(&values, &ctx);
+                match (expected, actual) {
+                    (Ok(expected), Ok(actual)) => assert_eq!(expected.to_bits(), actual.to_bits()),
+                    (expected, actual) => assert_eq!(expected, actual),
+                }
+            }
+        }
+    }
+}
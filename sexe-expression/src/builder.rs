@@ -0,0 +1,155 @@
+//! Functions and operators for building expression trees in Rust, e.g.
+//!
+//! ```
+//! use sexe_expression::builder::*;
+//! use std::collections::HashMap;
+//!
+//! // sin(x) * 2 + 3
+//! let expression = sin(var("x")) * constant(2.0) + constant(3.0);
+//! assert_eq!(expression.to_string(), "sin(x) * 2 + 3");
+//!
+//! let mut vars = HashMap::new();
+//! vars.insert("x".to_string(), 0.0);
+//! assert_eq!(expression.evaluate(&vars), Ok(3.0));
+//! ```
+//!
+//! Exponentiation is the `pow` method, since Rust has no operator for it:
+//!
+//! ```
+//! use sexe_expression::builder::*;
+//!
+//! let expression = (var("x") + constant(1.0)).pow(constant(2.0)) / log(constant(2.0), var("x"));
+//! assert_eq!(expression.to_string(), "(x + 1) ^ 2 / log(x, 2)");
+//! ```
+
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+
+pub use super::ExpressionNode;
+use super::*;
+
+/// The variable `name`, see `ExpressionNode::var`.
+pub fn var(name: &str) -> ExpressionNode {
+    ExpressionNode::var(name)
+}
+
+/// The number `value`, see `ExpressionNode::constant`.
+pub fn constant(value: f64) -> ExpressionNode {
+    ExpressionNode::constant(value)
+}
+
+macro_rules! unary_functions {
+    ($($name:ident => $operator:ident,)*) => {
+        $(
+            #[doc = concat!("`", stringify!($name), "(x)`.")]
+            pub fn $name(x: ExpressionNode) -> ExpressionNode {
+                ExpressionNode::unary(UnaryOperator::$operator, x)
+            }
+        )*
+    };
+}
+
+unary_functions! {
+    sin => Sin,
+    asin => Asin,
+    cos => Cos,
+    acos => Acos,
+    tan => Tan,
+    ctan => Ctan,
+    abs => Abs,
+    exp => Exp,
+    log2 => Log2,
+    log10 => Log10,
+    ln => Ln,
+    ceil => Ceil,
+    floor => Floor,
+    fract => Fract,
+    erf => Erf,
+    gamma => Gamma,
+    isnan => IsNan,
+    isinf => IsInf,
+    isfinite => IsFinite,
+}
+
+/// The logarithm of `x` to the base `base`.
+pub fn log(base: ExpressionNode, x: ExpressionNode) -> ExpressionNode {
+    ExpressionNode::nary(NaryOperator::Log, vec![x, base])
+}
+
+/// The sum of `body` over every integer `index` from `lower` to `upper`.
+pub fn sum(index: &str, lower: ExpressionNode, upper: ExpressionNode, body: ExpressionNode) -> ExpressionNode {
+    let operator = NaryOperator::Sum {
+        index_key: index.to_string(),
+    };
+    ExpressionNode::nary(operator, vec![lower, upper, body])
+}
+
+/// The product of `body` over every integer `index` from `lower` to `upper`.
+pub fn product(index: &str, lower: ExpressionNode, upper: ExpressionNode, body: ExpressionNode) -> ExpressionNode {
+    let operator = NaryOperator::Product {
+        index_key: index.to_string(),
+    };
+    ExpressionNode::nary(operator, vec![lower, upper, body])
+}
+
+/// A call to the function registered as `name` in the `EvaluationContext`.
+pub fn call(name: &str, args: Vec<ExpressionNode>) -> ExpressionNode {
+    ExpressionNode::FunctionCallNode {
+        name: name.to_string(),
+        args,
+    }
+}
+
+impl ExpressionNode {
+    /// `self` raised to `exponent`.
+    pub fn pow(self, exponent: ExpressionNode) -> ExpressionNode {
+        ExpressionNode::binary(BinaryOperator::Exponentiation, self, exponent)
+    }
+}
+
+impl Add for ExpressionNode {
+    type Output = ExpressionNode;
+
+    fn add(self, other: ExpressionNode) -> ExpressionNode {
+        ExpressionNode::binary(BinaryOperator::Addition, self, other)
+    }
+}
+
+impl Sub for ExpressionNode {
+    type Output = ExpressionNode;
+
+    fn sub(self, other: ExpressionNode) -> ExpressionNode {
+        ExpressionNode::binary(BinaryOperator::Subtraction, self, other)
+    }
+}
+
+impl Mul for ExpressionNode {
+    type Output = ExpressionNode;
+
+    fn mul(self, other: ExpressionNode) -> ExpressionNode {
+        ExpressionNode::binary(BinaryOperator::Multiplication, self, other)
+    }
+}
+
+impl Div for ExpressionNode {
+    type Output = ExpressionNode;
+
+    fn div(self, other: ExpressionNode) -> ExpressionNode {
+        ExpressionNode::binary(BinaryOperator::Division, self, other)
+    }
+}
+
+impl Rem for ExpressionNode {
+    type Output = ExpressionNode;
+
+    fn rem(self, other: ExpressionNode) -> ExpressionNode {
+        ExpressionNode::binary(BinaryOperator::Modulo, self, other)
+    }
+}
+
+impl Neg for ExpressionNode {
+    type Output = ExpressionNode;
+
+    fn neg(self) -> ExpressionNode {
+        ExpressionNode::unary(UnaryOperator::Negation, self)
+    }
+}
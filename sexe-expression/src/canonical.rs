@@ -0,0 +1,213 @@
+//! A canonical form of expression trees, under which expressions that only differ in the order of
+//! their sums and products are equal.
+
+use super::*;
+use std::cmp::Ordering;
+
+impl ExpressionNode {
+    /// A copy of the expression with the operands of additions and multiplications sorted into a
+    /// deterministic order, so that e.g. `x + 1` and `1 + x` have the same canonical form. Chains
+    /// of the same operator are flattened before sorting, so `(a * b) * c` and `c * (b * a)` are
+    /// both `a * b * c`, built up from the left.
+    ///
+    /// Subtractions are rewritten as additions of negations, `a - b` as `a + (-b)`, so that they
+    /// take part in the sorting, and double negations are removed. Both are exact in floating
+    /// point, but the reordering of sums and products may change how their results are rounded.
+    ///
+    /// Constants sort first, by value, then variables, then everything else, each by their
+    /// S-expression.
+    pub fn canonicalize(&self) -> ExpressionNode {
+        match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                let left = left_node.canonicalize();
+                let right = right_node.canonicalize();
+                match operator {
+                    BinaryOperator::Addition => sorted_chain(BinaryOperator::Addition, left, right),
+                    BinaryOperator::Subtraction => {
+                        sorted_chain(BinaryOperator::Addition, left, negated(right))
+                    }
+                    BinaryOperator::Multiplication => {
+                        sorted_chain(BinaryOperator::Multiplication, left, right)
+                    }
+                    _ => ExpressionNode::BinaryExprNode {
+                        operator: operator.clone(),
+                        left_node: Box::new(left),
+                        right_node: Box::new(right),
+                    },
+                }
+            }
+            ExpressionNode::UnaryExprNode {
+                operator: UnaryOperator::Negation,
+                child_node,
+            } => negated(child_node.canonicalize()),
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => ExpressionNode::UnaryExprNode {
+                operator: operator.clone(),
+                child_node: Box::new(child_node.canonicalize()),
+            },
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => ExpressionNode::NaryExprNode {
+                operator: operator.clone(),
+                child_nodes: child_nodes.iter().map(|node| node.canonicalize()).collect(),
+            },
+            ExpressionNode::FunctionCallNode { name, args } => ExpressionNode::FunctionCallNode {
+                name: name.clone(),
+                args: args.iter().map(|node| node.canonicalize()).collect(),
+            },
+            ExpressionNode::VariableExprNode { .. } | ExpressionNode::ConstantExprNode { .. } => {
+                self.clone()
+            }
+        }
+    }
+
+    /// Whether `self` and `other` have the same canonical form, see `canonicalize`.
+    pub fn equivalent(&self, other: &ExpressionNode) -> bool {
+        self.canonicalize() == other.canonicalize()
+    }
+}
+
+/// `-node`, where `node` is canonical, without a double negation.
+fn negated(node: ExpressionNode) -> ExpressionNode {
+    match node {
+        ExpressionNode::UnaryExprNode {
+            operator: UnaryOperator::Negation,
+            child_node,
+        } => *child_node,
+        node => -node,
+    }
+}
+
+/// `left operator right`, where both are canonical, as a sorted chain of `operator`.
+fn sorted_chain(
+    operator: BinaryOperator,
+    left: ExpressionNode,
+    right: ExpressionNode,
+) -> ExpressionNode {
+    let mut operands = Vec::new();
+    collect_operands(&operator, left, &mut operands);
+    collect_operands(&operator, right, &mut operands);
+    operands.sort_by(canonical_order);
+    operands
+        .into_iter()
+        .reduce(|chain, operand| ExpressionNode::BinaryExprNode {
+            operator: operator.clone(),
+            left_node: Box::new(chain),
+            right_node: Box::new(operand),
+        })
+        .unwrap()
+}
+
+/// Adds the operands of the chain of `operator` at the top of `node` to `operands`.
+fn collect_operands(
+    operator: &BinaryOperator,
+    node: ExpressionNode,
+    operands: &mut Vec<ExpressionNode>,
+) {
+    match node {
+        ExpressionNode::BinaryExprNode {
+            operator: ref node_operator,
+            left_node,
+            right_node,
+        } if node_operator == operator => {
+            collect_operands(operator, *left_node, operands);
+            collect_operands(operator, *right_node, operands);
+        }
+        node => operands.push(node),
+    }
+}
+
+fn canonical_order(a: &ExpressionNode, b: &ExpressionNode) -> Ordering {
+    fn rank(node: &ExpressionNode) -> u8 {
+        match node {
+            ExpressionNode::ConstantExprNode { .. } => 0,
+            ExpressionNode::VariableExprNode { .. } => 1,
+            _ => 2,
+        }
+    }
+    match (a, b) {
+        (
+            ExpressionNode::ConstantExprNode { value: a },
+            ExpressionNode::ConstantExprNode { value: b },
+        ) => a.total_cmp(b),
+        _ => rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.to_sexpr().cmp(&b.to_sexpr())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::*;
+
+    #[test]
+    fn commutative_operands_are_sorted() {
+        assert!((var("x") + constant(1.0)).equivalent(&(constant(1.0) + var("x"))));
+        assert!(((var("a") * var("b")) * var("c")).equivalent(&(var("c") * (var("b") * var("a")))));
+        assert!((var("x") - var("y")).equivalent(&(-var("y") + var("x"))));
+        assert!((var("x") - (-var("y"))).equivalent(&(var("y") + var("x"))));
+        assert!(sin(var("x") * constant(2.0)).equivalent(&sin(constant(2.0) * var("x"))));
+
+        assert_eq!(
+            (var("y") + sin(var("x")) + constant(2.0) + var("x")).canonicalize(),
+            constant(2.0) + var("x") + var("y") + sin(var("x"))
+        );
+    }
+
+    #[test]
+    fn other_operands_keep_their_order() {
+        assert!(!(var("x") - var("y")).equivalent(&(var("y") - var("x"))));
+        assert!(!(var("x") / var("y")).equivalent(&(var("y") / var("x"))));
+        assert!(!var("x").pow(var("y")).equivalent(&var("y").pow(var("x"))));
+        assert!(!log(var("x"), var("y")).equivalent(&log(var("y"), var("x"))));
+        // Addition and multiplication don't mix.
+        assert!(!(var("x") * (var("y") + var("z"))).equivalent(&(var("x") * var("y") + var("z"))));
+    }
+
+    #[test]
+    fn canonical_forms_are_stable() {
+        let expression = (var("b") - constant(3.0)) * sin(var("a") + var("b") * constant(2.0));
+        let canonical = expression.canonicalize();
+        assert_eq!(canonical.canonicalize(), canonical);
+    }
+
+    #[test]
+    fn canonical_forms_evaluate_the_same() {
+        let expressions = vec![
+            var("x") - constant(2.0) * var("y") + exp(var("x")) - (constant(1.0) - var("y")),
+            (var("y") * var("x")) * (constant(3.0) - var("x")) / (var("x") + constant(5.0)),
+            sum(
+                "k",
+                constant(1.0),
+                constant(4.0),
+                var("k") * var("x") - var("y"),
+            ),
+            -(-var("x") * cos(var("y") + var("x"))),
+        ];
+        for expression in &expressions {
+            let canonical = expression.canonicalize();
+            for i in 0..20 {
+                let mut vars = HashMap::new();
+                vars.insert("x".to_string(), -2.0 + 0.25 * i as f64);
+                vars.insert("y".to_string(), 1.5 - 0.1 * i as f64);
+                let expected = expression.evaluate(&vars).unwrap();
+                let actual = canonical.evaluate(&vars).unwrap();
+                assert!(
+                    (actual - expected).abs() <= 1e-12 * expected.abs().max(1.0),
+                    "{} != {} for {}",
+                    actual,
+                    expected,
+                    canonical
+                );
+            }
+        }
+    }
+}
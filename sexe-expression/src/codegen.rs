@@ -0,0 +1,241 @@
+//! Generation of Rust source code from expression trees, for compiling an expression into a
+//! program instead of evaluating the tree at run time.
+
+use std::f64::consts::{E, PI, TAU};
+
+use super::*;
+
+/// How tightly the outermost operator of a generated Rust expression binds, from a block, which
+/// always needs parentheses, to a variable, literal or method call, which never does.
+const BLOCK: u8 = 0;
+const ADDITIVE: u8 = 1;
+const MULTIPLICATIVE: u8 = 2;
+const PREFIX: u8 = 3;
+const ATOMIC: u8 = 4;
+
+impl ExpressionNode {
+    /// Renders the expression as a standalone Rust function named `fn_name`, which takes the
+    /// variables `vars` as `f64` parameters in that order and returns the value of the expression,
+    /// e.g. `pub fn f(x: f64) -> f64 { x.sin() * 2.0_f64 }`.
+    ///
+    /// The function computes the same value as `evaluate` with the angles in radians. Where
+    /// `evaluate` fails, the function returns NaN or an infinity instead, e.g. for
+    /// `ln(0)`. Variables that aren't in `vars` and custom functions are referred to by name, so
+    /// they must be in scope where the function is compiled, and `erf` and `gamma` are called from
+    /// `sexe_expression::special`.
+    pub fn to_rust_fn(&self, fn_name: &str, vars: &[&str]) -> String {
+        let params: Vec<String> = vars.iter().map(|var| format!("{}: f64", var)).collect();
+        format!(
+            "pub fn {}({}) -> f64 {{\n    {}\n}}\n",
+            fn_name,
+            params.join(", "),
+            self.rust_expr().0
+        )
+    }
+
+    /// The Rust expression computing this subtree, and how tightly its outermost operator binds.
+    fn rust_expr(&self) -> (String, u8) {
+        match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                let precedence = match operator {
+                    BinaryOperator::Addition | BinaryOperator::Subtraction => ADDITIVE,
+                    BinaryOperator::Multiplication | BinaryOperator::Division => MULTIPLICATIVE,
+                    BinaryOperator::Modulo | BinaryOperator::Exponentiation => ATOMIC,
+                };
+                let left = left_node.rust_operand(precedence);
+                match operator {
+                    BinaryOperator::Addition
+                    | BinaryOperator::Subtraction
+                    | BinaryOperator::Multiplication
+                    | BinaryOperator::Division => {
+                        // Operands on the right are parenthesized at the same precedence too,
+                        // since not even addition is associative in floating point.
+                        let right = right_node.rust_operand(precedence + 1);
+                        (format!("{} {} {}", left, operator.symbol(), right), precedence)
+                    }
+                    BinaryOperator::Modulo => {
+                        (format!("{}.rem_euclid({})", left, right_node.rust_expr().0), ATOMIC)
+                    }
+                    // The same choice as `ConstantPower` makes for a constant exponent.
+                    BinaryOperator::Exponentiation => match ConstantPower::of(operator, right_node) {
+                        Some(ConstantPower::Integer(exponent)) => {
+                            (format!("{}.powi({})", left, exponent), ATOMIC)
+                        }
+                        Some(ConstantPower::SquareRoot) => (
+                            format!(
+                                "{{ let __base = {}; \
+                                 if __base > 0.0 {{ __base.sqrt() }} \
+                                 else {{ __base.powf(0.5) }} }}",
+                                left
+                            ),
+                            BLOCK,
+                        ),
+                        _ => (format!("{}.powf({})", left, right_node.rust_expr().0), ATOMIC),
+                    },
+                }
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                // Negations of negations are parenthesized too, since `--x` reads like a decrement.
+                let child = child_node.rust_operand(ATOMIC);
+                let method = match operator {
+                    UnaryOperator::Negation => return (format!("-{}", child), PREFIX),
+                    UnaryOperator::Ctan => {
+                        return (format!("1.0_f64 / {}.tan()", child), MULTIPLICATIVE)
+                    }
+                    UnaryOperator::Erf | UnaryOperator::Gamma => {
+                        let name = operator.name();
+                        let call = format!("::sexe_expression::special::{}({})", name, child_node.rust_expr().0);
+                        return (call, ATOMIC);
+                    }
+                    UnaryOperator::IsNan | UnaryOperator::IsInf | UnaryOperator::IsFinite => {
+                        let method = match operator {
+                            UnaryOperator::IsNan => "is_nan",
+                            UnaryOperator::IsInf => "is_infinite",
+                            _ => "is_finite",
+                        };
+                        return (format!("f64::from(u8::from({}.{}()))", child, method), ATOMIC);
+                    }
+                    UnaryOperator::Sin => "sin",
+                    UnaryOperator::Asin => "asin",
+                    UnaryOperator::Cos => "cos",
+                    UnaryOperator::Acos => "acos",
+                    UnaryOperator::Tan => "tan",
+                    UnaryOperator::Asinh => "asinh",
+                    UnaryOperator::Acosh => "acosh",
+                    UnaryOperator::Atanh => "atanh",
+                    UnaryOperator::Abs => "abs",
+                    UnaryOperator::Exp => "exp",
+                    UnaryOperator::Log2 => "log2",
+                    UnaryOperator::Log10 => "log10",
+                    UnaryOperator::Ln => "ln",
+                    UnaryOperator::Ceil => "ceil",
+                    UnaryOperator::Floor => "floor",
+                    UnaryOperator::Fract => "fract",
+                };
+                (format!("{}.{}()", child, method), ATOMIC)
+            }
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => match (operator, child_nodes.as_slice()) {
+                (NaryOperator::Log, [x, base]) => {
+                    (format!("{}.log({})", x.rust_operand(ATOMIC), base.rust_expr().0), ATOMIC)
+                }
+                (NaryOperator::Sum { index_key }, [lower, upper, body])
+                | (NaryOperator::Product { index_key }, [lower, upper, body]) => {
+                    (rust_series(operator, index_key, lower, upper, body), BLOCK)
+                }
+                // `evaluate` fails for the wrong number of operands.
+                _ => (String::from("f64::NAN"), ATOMIC),
+            },
+            ExpressionNode::FunctionCallNode { name, args } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.rust_expr().0).collect();
+                (format!("{}({})", name, args.join(", ")), ATOMIC)
+            }
+            ExpressionNode::VariableExprNode { variable_key } => (variable_key.clone(), ATOMIC),
+            ExpressionNode::ConstantExprNode { value } => rust_constant(*value),
+        }
+    }
+
+    /// The Rust expression computing this subtree, in parentheses unless it binds at least as
+    /// tightly as `precedence`.
+    fn rust_operand(&self, precedence: u8) -> String {
+        match self.rust_expr() {
+            (expr, own_precedence) if own_precedence >= precedence => expr,
+            (expr, _) => format!("({})", expr),
+        }
+    }
+}
+
+/// A sum or product as a block that loops over the index like `evaluate_series` does, and is NaN
+/// where that fails for too many terms. The names the block declares start with underscores, so
+/// that they don't shadow the variables of the expression, other than the index.
+fn rust_series(
+    operator: &NaryOperator,
+    index_key: &str,
+    lower: &ExpressionNode,
+    upper: &ExpressionNode,
+    body: &ExpressionNode,
+) -> String {
+    let (empty_value, accumulate) = match operator {
+        NaryOperator::Product { .. } => ("1.0_f64", "*="),
+        _ => ("0.0_f64", "+="),
+    };
+    format!(
+        "{{ let __lower = {}.round(); let __upper = {}.round(); \
+         if __lower.abs() <= {:?} && __upper.abs() <= {:?} && __upper - __lower < {:?} {{ \
+         let mut __total = {}; let mut {} = __lower; \
+         while {} <= __upper {{ __total {} {}; {} += 1.0; }} __total \
+         }} else {{ f64::NAN }} }}",
+        lower.rust_operand(ATOMIC),
+        upper.rust_operand(ATOMIC),
+        MAX_SERIES_INDEX,
+        MAX_SERIES_INDEX,
+        MAX_SERIES_TERMS as f64,
+        empty_value,
+        index_key,
+        index_key,
+        accumulate,
+        body.rust_expr().0,
+        index_key
+    )
+}
+
+/// A constant as a literal typed as `f64`, so that methods can be called on it, or as the named
+/// constant it is exactly equal to.
+fn rust_constant(value: f64) -> (String, u8) {
+    let named = if value == E {
+        "::std::f64::consts::E"
+    } else if value == PI {
+        "::std::f64::consts::PI"
+    } else if value == TAU {
+        "::std::f64::consts::TAU"
+    } else if value.is_nan() {
+        "f64::NAN"
+    } else if value == f64::INFINITY {
+        "f64::INFINITY"
+    } else if value == f64::NEG_INFINITY {
+        "f64::NEG_INFINITY"
+    } else {
+        let precedence = if value.is_sign_negative() { PREFIX } else { ATOMIC };
+        return (format!("{:?}_f64", value), precedence);
+    };
+    (named.to_string(), ATOMIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::*;
+
+    #[test]
+    fn functions_are_rendered() {
+        let expression = sin(var("x")) * constant(2.0) - constant(-1.5) % var("y");
+        assert_eq!(
+            expression.to_rust_fn("f", &["x", "y"]),
+            "pub fn f(x: f64, y: f64) -> f64 {\n    x.sin() * 2.0_f64 - (-1.5_f64).rem_euclid(y)\n}\n"
+        );
+
+        let expression = -(var("x") + constant(PI)).pow(constant(2.0)) / var("x").pow(constant(0.5));
+        assert_eq!(
+            expression.to_rust_fn("g", &["x"]),
+            "pub fn g(x: f64) -> f64 {\n    \
+             -(x + ::std::f64::consts::PI).powi(2) / \
+             ({ let __base = x; if __base > 0.0 { __base.sqrt() } else { __base.powf(0.5) } })\n}\n"
+        );
+
+        let expression = log(constant(2.0), gamma(var("t"))) + call("double", vec![var("t")]);
+        assert_eq!(
+            expression.to_rust_fn("h", &["t"]),
+            "pub fn h(t: f64) -> f64 {\n    \
+             ::sexe_expression::special::gamma(t).log(2.0_f64) + double(t)\n}\n"
+        );
+    }
+}
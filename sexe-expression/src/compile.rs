@@ -0,0 +1,351 @@
+//! Expressions compiled to a flat program that reads its variables from a slice, for evaluating
+//! the same expression many times, e.g. for every point of a plot.
+
+use super::*;
+
+/// A single step of a compiled program. The program runs in postfix order on a stack of values:
+/// every instruction pushes one value, after popping the values of its operands.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Instruction {
+    Constant(f64),
+    Variable(Symbol),
+    Unary(UnaryOperator),
+    Binary(BinaryOperator),
+    /// Raises the value on top of the stack to a constant exponent.
+    Power(ConstantPower),
+    /// An N-ary operator with the given number of operands.
+    Nary(NaryOperator, usize),
+    /// A call to a custom function with the given number of arguments.
+    Call(String, usize),
+    /// Pops the bounds of a sum or product, then runs the next `body_len` instructions once for
+    /// every index, with `index` bound to it, and combines their values as `operator` does.
+    Sum {
+        operator: NaryOperator,
+        index: Symbol,
+        body_len: usize,
+    },
+}
+
+/// An expression compiled from an `ExpressionNode`, see `ExpressionNode::compile`. Its variables
+/// are identified by the symbols of `symbols()`, and their values are passed as a slice indexed by
+/// those symbols, rather than as a map from their names.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompiledExpression {
+    pub(crate) program: Vec<Instruction>,
+    pub(crate) symbols: SymbolTable,
+    /// The symbols that must be given a value. The others are only ever the index of a sum.
+    pub(crate) free: Vec<Symbol>,
+}
+
+impl ExpressionNode {
+    /// Compiles the expression tree rooted at `self`. It evaluates to exactly the same results as
+    /// the tree.
+    pub fn compile(&self) -> CompiledExpression {
+        let mut symbols = SymbolTable::new();
+        let mut program = Vec::new();
+        self.emit(&mut symbols, &mut program);
+        let free = self
+            .variables()
+            .iter()
+            .filter_map(|name| symbols.get(name))
+            .collect();
+        CompiledExpression {
+            program,
+            symbols,
+            free,
+        }
+    }
+
+    fn emit(&self, symbols: &mut SymbolTable, program: &mut Vec<Instruction>) {
+        match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                left_node.emit(symbols, program);
+                match ConstantPower::of(operator, right_node) {
+                    Some(power) => program.push(Instruction::Power(power)),
+                    None => {
+                        right_node.emit(symbols, program);
+                        program.push(Instruction::Binary(operator.clone()));
+                    }
+                }
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                child_node.emit(symbols, program);
+                program.push(Instruction::Unary(operator.clone()));
+            }
+            ExpressionNode::NaryExprNode {
+                operator: operator @ (NaryOperator::Sum { index_key } | NaryOperator::Product { index_key }),
+                child_nodes,
+            } if child_nodes.len() == 3 => {
+                child_nodes[0].emit(symbols, program);
+                child_nodes[1].emit(symbols, program);
+                let index = symbols.intern(index_key);
+                let start = program.len();
+                program.push(Instruction::Sum {
+                    operator: operator.clone(),
+                    index,
+                    body_len: 0,
+                });
+                child_nodes[2].emit(symbols, program);
+                let len = program.len() - start - 1;
+                if let Instruction::Sum { body_len, .. } = &mut program[start] {
+                    *body_len = len;
+                }
+            }
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => {
+                for node in child_nodes.iter() {
+                    node.emit(symbols, program);
+                }
+                program.push(Instruction::Nary(operator.clone(), child_nodes.len()));
+            }
+            ExpressionNode::FunctionCallNode { name, args } => {
+                for node in args {
+                    node.emit(symbols, program);
+                }
+                program.push(Instruction::Call(name.clone(), args.len()));
+            }
+            ExpressionNode::VariableExprNode { variable_key } => {
+                program.push(Instruction::Variable(symbols.intern(variable_key)));
+            }
+            ExpressionNode::ConstantExprNode { value } => {
+                program.push(Instruction::Constant(*value));
+            }
+        }
+    }
+}
+
+impl CompiledExpression {
+    /// The symbols of the variables of the expression, which index the slice of values it is
+    /// evaluated against.
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// Looks up the value of every symbol in `vars`, building the slice to evaluate against.
+    /// Variables that are only the index of a sum needn't be bound.
+    pub fn bind<T: Float>(&self, vars: &HashMap<String, T>) -> Result<Vec<T>, EvaluationError> {
+        let mut values = vec![T::from_f64(0.0); self.symbols.len()];
+        for (symbol, name) in self.symbols.iter() {
+            match vars.get(name) {
+                Some(value) => values[symbol.index()] = *value,
+                None if self.free.contains(&symbol) => {
+                    return Err(EvaluationError::VariableNotFoundError)
+                }
+                None => (),
+            }
+        }
+        Ok(values)
+    }
+
+    /// Evaluates the expression with the value of each variable at the index of its symbol in
+    /// `values`. This uses the default settings of `EvaluationContext`.
+    pub fn evaluate(&self, values: &[f64]) -> Result<f64, EvaluationError> {
+        self.evaluate_ctx(values, &EvaluationContext::new())
+    }
+
+    /// Like `evaluate`, but with the settings and functions of `ctx`. Its variables are ignored.
+    pub fn evaluate_ctx(&self, values: &[f64], ctx: &EvaluationContext) -> Result<f64, EvaluationError> {
+        self.evaluate_with(values, ctx)
+    }
+
+    /// Evaluates against the variables of `vars`, like `ExpressionNode::evaluate`. Binding the
+    /// names to symbols costs as much as evaluating, so use `bind` and `evaluate` to evaluate
+    /// repeatedly.
+    pub fn evaluate_map(&self, vars: &HashMap<String, f64>) -> Result<f64, EvaluationError> {
+        self.evaluate(&self.bind(vars)?)
+    }
+
+    pub(crate) fn evaluate_with<T: Float>(
+        &self,
+        values: &[T],
+        ctx: &EvaluationContext,
+    ) -> Result<T, EvaluationError> {
+        self.evaluate_on(values, &mut Vec::new(), ctx)
+    }
+
+    /// Like `evaluate_with`, but works on `stack` rather than allocating a stack of its own, so
+    /// that evaluating repeatedly, e.g. for every point of a sweep, allocates nothing.
+    pub(crate) fn evaluate_on<T: Float>(
+        &self,
+        values: &[T],
+        stack: &mut Vec<T>,
+        ctx: &EvaluationContext,
+    ) -> Result<T, EvaluationError> {
+        if values.len() < self.symbols.len() {
+            return Err(EvaluationError::VariableNotFoundError);
+        }
+        stack.clear();
+        run(&self.program, values, stack, &mut Vec::new(), ctx)
+    }
+}
+
+/// Runs `program`, which must push exactly one value onto `stack` and which leaves the values
+/// already on it alone. The indices of the sums being evaluated are in `indices`, innermost last,
+/// and shadow `values`.
+fn run<T: Float>(
+    program: &[Instruction],
+    values: &[T],
+    stack: &mut Vec<T>,
+    indices: &mut Vec<(Symbol, T)>,
+    ctx: &EvaluationContext,
+) -> Result<T, EvaluationError> {
+    let mut pc = 0;
+    while pc < program.len() {
+        let instruction = &program[pc];
+        pc += 1;
+        let (value, any_operand_is_nan) = match instruction {
+            Instruction::Constant(value) => {
+                stack.push(T::from_f64(*value));
+                continue;
+            }
+            Instruction::Variable(symbol) => {
+                let value = match indices.iter().rev().find(|(index, _)| index == symbol) {
+                    Some((_, value)) => *value,
+                    None => values[symbol.index()],
+                };
+                stack.push(value);
+                continue;
+            }
+            Instruction::Unary(operator) => {
+                let child_value = stack.pop().unwrap();
+                (operator.apply(child_value, ctx.angle_mode)?, child_value.is_nan())
+            }
+            Instruction::Binary(operator) => {
+                let right_value = stack.pop().unwrap();
+                let left_value = stack.pop().unwrap();
+                (
+                    operator.apply(left_value, right_value),
+                    left_value.is_nan() || right_value.is_nan(),
+                )
+            }
+            Instruction::Power(power) => {
+                let base = stack.pop().unwrap();
+                (power.apply(base), base.is_nan())
+            }
+            Instruction::Nary(operator, count) => {
+                let first = stack.len() - count;
+                let value = operator.apply(&stack[first..])?;
+                let any_operand_is_nan = stack[first..].iter().any(|value| value.is_nan());
+                stack.truncate(first);
+                (value, any_operand_is_nan)
+            }
+            Instruction::Call(name, count) => {
+                // Custom functions deal in `f64`, whatever we are evaluating in.
+                let arg_values: Vec<f64> = stack
+                    .split_off(stack.len() - count)
+                    .into_iter()
+                    .map(Float::to_f64)
+                    .collect();
+                (
+                    T::from_f64(ctx.call_function(name, &arg_values)?),
+                    arg_values.iter().any(|value| value.is_nan()),
+                )
+            }
+            Instruction::Sum {
+                operator,
+                index,
+                body_len,
+            } => {
+                let body = &program[pc..pc + body_len];
+                pc += body_len;
+                let upper = stack.pop().unwrap().to_f64();
+                let lower = stack.pop().unwrap().to_f64();
+                // This mirrors `evaluate_series`, which doesn't check the sum itself for NaN.
+                let ks = match series_indices(lower, upper)? {
+                    Some(ks) => ks,
+                    None => {
+                        stack.push(T::from_f64(f64::NAN));
+                        continue;
+                    }
+                };
+                let mut total = operator.empty_value();
+                for k in ks {
+                    indices.push((*index, T::from_f64(k as f64)));
+                    total = operator.accumulate(total, run(body, values, stack, indices, ctx)?);
+                    indices.pop();
+                }
+                stack.push(total);
+                continue;
+            }
+        };
+        if ctx.strict && value.is_nan() && !any_operand_is_nan {
+            return Err(EvaluationError::NotANumberError);
+        }
+        stack.push(value);
+    }
+    Ok(stack.pop().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{constant, exp, ln, log, product, sin, sum, var};
+
+    #[test]
+    fn symbols_are_interned_once() {
+        let expression = var("x") * var("y") + var("x");
+        let compiled = expression.compile();
+        let symbols = compiled.symbols();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols.get("x"), Some(Symbol(0)));
+        assert_eq!(symbols.get("y"), Some(Symbol(1)));
+        assert_eq!(symbols.name(Symbol(1)), "y");
+        assert_eq!(symbols, &SymbolTable::from_expression(&expression));
+        assert_eq!(compiled.evaluate(&[2.0, 3.0]), Ok(8.0));
+        assert_eq!(compiled.evaluate(&[2.0]), Err(EvaluationError::VariableNotFoundError));
+    }
+
+    #[test]
+    fn compiled_expressions_agree_with_trees() {
+        let expressions = vec![
+            sin(var("x")) * exp(var("x") / constant(3.0)) + var("x").pow(var("y")),
+            log(constant(2.0), var("x")) - ln(var("x")),
+            // The outer `k` isn't the index.
+            sum("k", constant(1.0), var("y"), var("k") * var("x")) + var("k"),
+            sum(
+                "j",
+                constant(1.0),
+                constant(3.0),
+                sum("k", var("j"), constant(3.0), var("j") * var("k")),
+            ),
+            product("k", constant(1.0), var("y"), var("x") + var("k")),
+            // log(x)
+            ExpressionNode::nary(NaryOperator::Log, vec![var("x")]),
+        ];
+
+        for expression in &expressions {
+            let compiled = expression.compile();
+            for &x in &[-2.0, -0.5, 0.0, 0.25, 1.0, 3.0] {
+                let mut vars_map = HashMap::new();
+                vars_map.insert("x".to_string(), x);
+                vars_map.insert("y".to_string(), 4.0);
+                vars_map.insert("k".to_string(), 100.0);
+
+                let expected = expression.evaluate(&vars_map);
+                let actual = compiled.evaluate_map(&vars_map);
+                match (expected, actual) {
+                    (Ok(expected), Ok(actual)) => assert_eq!(expected.to_bits(), actual.to_bits()),
+                    (expected, actual) => assert_eq!(expected, actual),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sum_indices_need_not_be_bound() {
+        let compiled = sum("k", constant(1.0), var("n"), var("k")).compile();
+        let mut vars_map = HashMap::new();
+        assert_eq!(compiled.bind(&vars_map), Err(EvaluationError::VariableNotFoundError));
+        vars_map.insert("n".to_string(), 4.0);
+        assert_eq!(compiled.evaluate_map(&vars_map), Ok(10.0));
+    }
+}
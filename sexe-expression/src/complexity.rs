@@ -0,0 +1,149 @@
+//! Measures of how large an expression tree is and how much evaluating it costs.
+
+use super::*;
+
+impl ExpressionNode {
+    /// The number of nodes in the expression tree rooted at `self`.
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        let mut remaining = vec![self];
+        while let Some(node) = remaining.pop() {
+            count += 1;
+            remaining.extend(node.children());
+        }
+        count
+    }
+
+    /// The number of nodes on the longest path from `self` down to a leaf, so a lone constant
+    /// has a depth of 1.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut remaining = vec![(self, 1)];
+        while let Some((node, node_depth)) = remaining.pop() {
+            depth = depth.max(node_depth);
+            remaining.extend(node.children().into_iter().map(|child| (child, node_depth + 1)));
+        }
+        depth
+    }
+
+    /// A rough estimate of how expensive evaluating the expression is, in units of an addition.
+    /// Variables and constants are free, while transcendental functions cost several additions
+    /// and special functions many. The body of a sum is counted once, since the number of terms
+    /// may depend on variables.
+    pub fn operation_cost(&self) -> u64 {
+        let mut cost = 0;
+        let mut remaining = vec![self];
+        while let Some(node) = remaining.pop() {
+            cost += node.own_cost();
+            remaining.extend(node.children());
+        }
+        cost
+    }
+
+    /// The cost of the outermost operator alone.
+    fn own_cost(&self) -> u64 {
+        match self {
+            ExpressionNode::BinaryExprNode { operator, .. } => match operator {
+                BinaryOperator::Addition
+                | BinaryOperator::Subtraction
+                | BinaryOperator::Multiplication => 1,
+                BinaryOperator::Division | BinaryOperator::Modulo => 2,
+                BinaryOperator::Exponentiation => 8,
+            },
+            ExpressionNode::UnaryExprNode { operator, .. } => match operator {
+                UnaryOperator::Negation
+                | UnaryOperator::Abs
+                | UnaryOperator::Ceil
+                | UnaryOperator::Floor
+                | UnaryOperator::Fract
+                | UnaryOperator::IsNan
+                | UnaryOperator::IsInf
+                | UnaryOperator::IsFinite => 1,
+                UnaryOperator::Sin
+                | UnaryOperator::Asin
+                | UnaryOperator::Cos
+                | UnaryOperator::Acos
+                | UnaryOperator::Tan
+                | UnaryOperator::Ctan
+                | UnaryOperator::Asinh
+                | UnaryOperator::Acosh
+                | UnaryOperator::Atanh
+                | UnaryOperator::Exp
+                | UnaryOperator::Log2
+                | UnaryOperator::Log10
+                | UnaryOperator::Ln => 8,
+                UnaryOperator::Erf | UnaryOperator::Gamma => 32,
+            },
+            ExpressionNode::NaryExprNode { operator, .. } => match operator {
+                NaryOperator::Log => 16,
+                NaryOperator::Sum { .. } | NaryOperator::Product { .. } => 1,
+            },
+            // Custom functions could do anything, so they are assumed to be as expensive as the
+            // special functions.
+            ExpressionNode::FunctionCallNode { .. } => 32,
+            ExpressionNode::VariableExprNode { .. } | ExpressionNode::ConstantExprNode { .. } => 0,
+        }
+    }
+
+    /// The immediate subtrees of this node, from left to right.
+    pub(crate) fn children(&self) -> Vec<&ExpressionNode> {
+        match self {
+            ExpressionNode::BinaryExprNode {
+                left_node,
+                right_node,
+                ..
+            } => vec![left_node, right_node],
+            ExpressionNode::UnaryExprNode { child_node, .. } => vec![child_node],
+            ExpressionNode::NaryExprNode { child_nodes, .. } => child_nodes.iter().collect(),
+            ExpressionNode::FunctionCallNode { args, .. } => args.iter().collect(),
+            ExpressionNode::VariableExprNode { .. } | ExpressionNode::ConstantExprNode { .. } => {
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::*;
+
+    #[test]
+    fn small_trees_are_measured() {
+        let leaf = constant(1.0);
+        assert_eq!(leaf.node_count(), 1);
+        assert_eq!(leaf.depth(), 1);
+        assert_eq!(leaf.operation_cost(), 0);
+
+        let expression = sin(var("x")) * constant(3.0) + log(constant(2.0), var("x"));
+        assert_eq!(expression.node_count(), 8);
+        assert_eq!(expression.depth(), 4);
+        // + 1, * 1, sin 8, log 16
+        assert_eq!(expression.operation_cost(), 26);
+
+        let expression = -(var("x") / constant(2.0)).pow(gamma(var("x")));
+        assert_eq!(expression.node_count(), 7);
+        assert_eq!(expression.depth(), 4);
+        // - 1, ^ 8, / 2, gamma 32
+        assert_eq!(expression.operation_cost(), 43);
+    }
+
+    #[test]
+    fn deep_trees_are_measured_without_overflowing() {
+        // -(-(-(... x)))
+        let mut expression = var("x");
+        for _ in 0..100_000 {
+            expression = -expression;
+        }
+
+        assert_eq!(expression.node_count(), 100_001);
+        assert_eq!(expression.depth(), 100_001);
+        assert_eq!(expression.operation_cost(), 100_000);
+
+        // Dropping the tree is recursive too, so tear it down a level at a time.
+        let mut remaining = expression;
+        while let ExpressionNode::UnaryExprNode { child_node, .. } = remaining {
+            remaining = *child_node;
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Configuration of how expressions are evaluated.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::EvaluationError;
+
+/// The signature of functions that can be registered with
+/// `EvaluationContext::register_function`. They receive the values of their arguments.
+pub type CustomFunction = dyn Fn(&[f64]) -> Result<f64, EvaluationError> + Send + Sync;
+
+#[derive(Clone)]
+struct RegisteredFunction {
+    arity: usize,
+    function: Arc<CustomFunction>,
+}
+
+impl fmt::Debug for RegisteredFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RegisteredFunction {{ arity: {} }}", self.arity)
+    }
+}
+
+/// The unit that trigonometric functions take their arguments in, and that inverse trigonometric
+/// functions return their results in.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    Degrees,
+}
+
+/// Everything an expression is evaluated against: the values of its variables, the functions it
+/// may call, and the settings that change what the operators compute. A context is meant to be
+/// built once and reused, e.g. for every point of a plot, updating only the variables that change.
+#[derive(Debug, Default, Clone)]
+pub struct EvaluationContext {
+    pub variables: HashMap<String, f64>,
+    pub angle_mode: AngleMode,
+    /// If set, an operator that produces NaN from operands that aren't NaN, e.g. `asin(2)`, is an
+    /// `EvaluationError::NotANumberError` rather than a NaN value.
+    pub strict: bool,
+    functions: HashMap<String, RegisteredFunction>,
+}
+
+impl EvaluationContext {
+    /// A context with the default settings (radians, not strict) and no variables.
+    pub fn new() -> EvaluationContext {
+        EvaluationContext::default()
+    }
+
+    /// A context with the default settings that binds `variables`.
+    pub fn with_variables(variables: HashMap<String, f64>) -> EvaluationContext {
+        EvaluationContext {
+            variables,
+            ..EvaluationContext::default()
+        }
+    }
+
+    /// Makes `function` callable by `name` from expressions, e.g. `name(x, 2)`, with exactly
+    /// `arity` arguments. A function registered under the same name before is replaced.
+    pub fn register_function<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[f64]) -> Result<f64, EvaluationError> + Send + Sync + 'static,
+    {
+        self.functions.insert(
+            name.to_string(),
+            RegisteredFunction {
+                arity,
+                function: Arc::new(function),
+            },
+        );
+    }
+
+    /// Calls the function registered as `name`.
+    pub(crate) fn call_function(&self, name: &str, args: &[f64]) -> Result<f64, EvaluationError> {
+        match self.functions.get(name) {
+            Some(registered) if registered.arity == args.len() => (registered.function)(args),
+            Some(registered) => Err(EvaluationError::WrongNumberOfArgsError {
+                expected: registered.arity,
+                got: args.len(),
+            }),
+            None => Err(EvaluationError::UnknownFunction {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
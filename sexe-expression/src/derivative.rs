@@ -0,0 +1,373 @@
+//! Numeric and symbolic differentiation.
+
+use super::*;
+use builder::*;
+use std::f64::consts::{LN_10, LN_2, PI};
+
+/// How far apart the two estimates of Richardson extrapolation may be, relative to the larger of
+/// the derivative and one, before the derivative is considered unresolvable. Near a pole or a
+/// jump, the central differences at both step sizes disagree wildly.
+const MAX_RELATIVE_DISAGREEMENT: f64 = 0.1;
+
+/// The derivative of `func` with respect to `var` at `x`, from central differences with steps of
+/// `h` and `h / 2`, combined by Richardson extrapolation. Its error shrinks with `h^4`, until the
+/// rounding errors of the differences take over below about `1e-3`.
+///
+/// If `func` isn't finite at any of the points it is evaluated at, or the differences don't agree,
+/// as happens across a pole or a jump, the derivative is a `DomainError`. Errors from evaluating
+/// `func` are passed on.
+pub fn derivative_at(
+    func: &ExpressionNode,
+    var: &str,
+    x: f64,
+    h: f64,
+) -> Result<f64, EvaluationError> {
+    let mut vars = HashMap::new();
+    let mut f = |x: f64| {
+        vars.insert(var.to_string(), x);
+        match func.evaluate(&vars)? {
+            y if y.is_finite() => Ok(y),
+            _ => Err(EvaluationError::DomainError),
+        }
+    };
+    let mut central_difference = |h: f64| Ok((f(x + h)? - f(x - h)?) / (2.0 * h));
+
+    let coarse = central_difference(h)?;
+    let fine = central_difference(h / 2.0)?;
+    // The leading error term of a central difference grows with `h^2`, so it is four times as
+    // large in `coarse` as in `fine`.
+    let extrapolated = (4.0 * fine - coarse) / 3.0;
+    if (fine - coarse).abs() > MAX_RELATIVE_DISAGREEMENT * extrapolated.abs().max(1.0) {
+        return Err(EvaluationError::DomainError);
+    }
+    Ok(extrapolated)
+}
+
+/// Samples the derivative of `func` with respect to `x` like `evaluate_function_over_domain`
+/// samples `func`, for plotting the two together. The step of the differences is a hundredth of
+/// the distance between samples, and points where the derivative is an error are omitted,
+/// leaving a gap.
+pub fn evaluate_derivative_over_domain(
+    start_x: f64,
+    end_x: f64,
+    resolution: u32,
+    func: &ExpressionNode,
+) -> Vec<(f64, f64)> {
+    // Linear spacing accepts any bounds.
+    let positions = sample_positions(start_x, end_x, resolution, Spacing::Linear).unwrap();
+    let h = ((end_x - start_x) / f64::from(resolution)).abs() / 100.0;
+    positions
+        .into_iter()
+        .filter_map(|x| derivative_at(func, "x", x, h).ok().map(|y| (x, y)))
+        .collect()
+}
+
+impl ExpressionNode {
+    /// The derivative of the expression with respect to `var`, as another expression. Subtrees
+    /// that don't depend on `var` differentiate to zero, and the zeros and ones this produces are
+    /// left out, so that e.g. the derivative of `sin(x)` is just `cos(x)`.
+    ///
+    /// `floor`, `ceil` and the predicates, e.g. `isnan`, differentiate to zero, ignoring their
+    /// jumps. `gamma`, calls to custom
+    /// functions, and sums whose bounds depend on `var` are `NotDifferentiable`.
+    pub fn differentiate(&self, var: &str) -> Result<ExpressionNode, EvaluationError> {
+        if !self.variables().contains(var) {
+            return Ok(constant(0.0));
+        }
+        match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                let (f, g) = (left_node.as_ref().clone(), right_node.as_ref().clone());
+                let (df, dg) = (f.differentiate(var)?, g.differentiate(var)?);
+                Ok(match operator {
+                    BinaryOperator::Addition => plus(df, dg),
+                    BinaryOperator::Subtraction => minus(df, dg),
+                    BinaryOperator::Multiplication => plus(times(df, g), times(f, dg)),
+                    BinaryOperator::Division => {
+                        minus(times(df, g.clone()), times(f, dg)) / g.pow(constant(2.0))
+                    }
+                    // f % g is f - g * floor(f / g), and the floor is flat almost everywhere.
+                    BinaryOperator::Modulo => minus(df, times(dg, floor(f / g))),
+                    BinaryOperator::Exponentiation if !g.variables().contains(var) => {
+                        let exponent = match g {
+                            ExpressionNode::ConstantExprNode { value } => constant(value - 1.0),
+                            _ => g.clone() - constant(1.0),
+                        };
+                        times(times(g, f.pow(exponent)), df)
+                    }
+                    BinaryOperator::Exponentiation => times(
+                        self.clone(),
+                        plus(times(dg, ln(f.clone())), times(g, df) / f),
+                    ),
+                })
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                let u = child_node.as_ref().clone();
+                let du = u.differentiate(var)?;
+                let outer = match operator {
+                    UnaryOperator::Negation => return Ok(-du),
+                    UnaryOperator::Fract => return Ok(du),
+                    UnaryOperator::Ceil
+                    | UnaryOperator::Floor
+                    | UnaryOperator::IsNan
+                    | UnaryOperator::IsInf
+                    | UnaryOperator::IsFinite => return Ok(constant(0.0)),
+                    UnaryOperator::Gamma => return Err(EvaluationError::NotDifferentiable),
+                    UnaryOperator::Abs => u.clone() / abs(u),
+                    UnaryOperator::Sin => cos(u),
+                    UnaryOperator::Cos => -sin(u),
+                    UnaryOperator::Tan => constant(1.0) / cos(u).pow(constant(2.0)),
+                    UnaryOperator::Ctan => -(constant(1.0) / sin(u).pow(constant(2.0))),
+                    UnaryOperator::Asin => {
+                        constant(1.0) / (constant(1.0) - u.pow(constant(2.0))).pow(constant(0.5))
+                    }
+                    UnaryOperator::Acos => {
+                        -(constant(1.0) / (constant(1.0) - u.pow(constant(2.0))).pow(constant(0.5)))
+                    }
+                    UnaryOperator::Asinh => {
+                        constant(1.0) / (u.pow(constant(2.0)) + constant(1.0)).pow(constant(0.5))
+                    }
+                    UnaryOperator::Acosh => {
+                        constant(1.0) / (u.pow(constant(2.0)) - constant(1.0)).pow(constant(0.5))
+                    }
+                    UnaryOperator::Atanh => constant(1.0) / (constant(1.0) - u.pow(constant(2.0))),
+                    UnaryOperator::Exp => exp(u),
+                    UnaryOperator::Ln => constant(1.0) / u,
+                    UnaryOperator::Log2 => constant(1.0) / (u * constant(LN_2)),
+                    UnaryOperator::Log10 => constant(1.0) / (u * constant(LN_10)),
+                    UnaryOperator::Erf => constant(2.0 / PI.sqrt()) * exp(-u.pow(constant(2.0))),
+                };
+                Ok(times(outer, du))
+            }
+            ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Log,
+                child_nodes,
+            } => match child_nodes.as_slice() {
+                [x, base] => (ln(x.clone()) / ln(base.clone())).differentiate(var),
+                _ => Err(NaryOperator::Log.wrong_number_of_args(child_nodes.len())),
+            },
+            ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Sum { index_key },
+                child_nodes,
+            } => match child_nodes.as_slice() {
+                [lower, upper, body] => {
+                    if lower.variables().contains(var) || upper.variables().contains(var) {
+                        return Err(EvaluationError::NotDifferentiable);
+                    }
+                    // The body only depends on `var` if it isn't the index, or the whole sum
+                    // would have been constant.
+                    Ok(sum(
+                        index_key,
+                        lower.clone(),
+                        upper.clone(),
+                        body.differentiate(var)?,
+                    ))
+                }
+                _ => Err(EvaluationError::WrongNumberOfArgsError {
+                    expected: 3,
+                    got: child_nodes.len(),
+                }),
+            },
+            // The product rule over a variable number of factors isn't worth the trouble.
+            ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Product { .. },
+                ..
+            } => Err(EvaluationError::NotDifferentiable),
+            ExpressionNode::FunctionCallNode { .. } => Err(EvaluationError::NotDifferentiable),
+            // A variable that depends on `var` is `var` itself, and constants never do.
+            ExpressionNode::VariableExprNode { .. } => Ok(constant(1.0)),
+            ExpressionNode::ConstantExprNode { .. } => Ok(constant(0.0)),
+        }
+    }
+}
+
+fn is_constant_value(node: &ExpressionNode, value: f64) -> bool {
+    *node == constant(value)
+}
+
+/// `a + b`, leaving out zeros.
+fn plus(a: ExpressionNode, b: ExpressionNode) -> ExpressionNode {
+    if is_constant_value(&a, 0.0) {
+        b
+    } else if is_constant_value(&b, 0.0) {
+        a
+    } else {
+        a + b
+    }
+}
+
+/// `a - b`, leaving out zeros.
+fn minus(a: ExpressionNode, b: ExpressionNode) -> ExpressionNode {
+    if is_constant_value(&b, 0.0) {
+        a
+    } else if is_constant_value(&a, 0.0) {
+        -b
+    } else {
+        a - b
+    }
+}
+
+/// `a * b`, leaving out ones and collapsing to zero if either is zero.
+fn times(a: ExpressionNode, b: ExpressionNode) -> ExpressionNode {
+    if is_constant_value(&a, 0.0) || is_constant_value(&b, 0.0) {
+        constant(0.0)
+    } else if is_constant_value(&a, 1.0) {
+        b
+    } else if is_constant_value(&b, 1.0) {
+        a
+    } else {
+        a * b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derivatives_match_analytic_ones() {
+        let cube = var("x").pow(constant(3.0));
+        for &x in &[-2.0, -0.5, 0.0, 1.0, 3.0] {
+            let derivative = derivative_at(&cube, "x", x, 1e-3).unwrap();
+            assert!(
+                (derivative - 3.0 * x * x).abs() < 1e-9,
+                "{} at {}",
+                derivative,
+                x
+            );
+        }
+
+        let sine = sin(var("t"));
+        for &t in &[-3.0, -1.0, 0.0, 0.5, 2.0] {
+            let derivative = derivative_at(&sine, "t", t, 1e-3).unwrap();
+            assert!(
+                (derivative - f64::cos(t)).abs() < 1e-9,
+                "{} at {}",
+                derivative,
+                t
+            );
+        }
+    }
+
+    #[test]
+    fn derivatives_across_poles_are_errors() {
+        let reciprocal = constant(1.0) / var("x");
+        assert_eq!(
+            derivative_at(&reciprocal, "x", 0.0, 1e-3),
+            Err(EvaluationError::DomainError)
+        );
+        // The pole is between the points either side of `x`.
+        assert_eq!(
+            derivative_at(&reciprocal, "x", 1e-4, 1e-3),
+            Err(EvaluationError::DomainError)
+        );
+        assert_eq!(
+            derivative_at(&ln(var("x")), "x", 0.0, 1e-3),
+            Err(EvaluationError::DomainError)
+        );
+    }
+
+    #[test]
+    fn derivative_sweeps_leave_gaps() {
+        let points = evaluate_derivative_over_domain(-1.0, 1.0, 4, &(constant(1.0) / var("x")));
+        assert_eq!(points.len(), 3);
+        for (&(x, y), &expected_x) in points.iter().zip(&[-1.0, -0.5, 0.5]) {
+            assert_eq!(x, expected_x);
+            assert!((y + 1.0 / (x * x)).abs() < 1e-6, "{} at {}", y, x);
+        }
+
+        let points = evaluate_derivative_over_domain(-1.0, 1.0, 4, &ln(var("x")));
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].0, 0.5);
+        assert!((points[0].1 - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn symbolic_derivatives_match_numeric_ones() {
+        let expressions = vec![
+            var("x").pow(constant(3.0)) - constant(2.0) * var("x"),
+            sin(var("x")) * cos(var("x")),
+            exp(var("x")) / (var("x") + constant(2.0)),
+            var("x").pow(var("x")),
+            ln(var("x")) + log2(var("x")) + log10(var("x")),
+            tan(var("x")) - ctan(var("x")),
+            asin(var("x") / constant(2.0)) + acos(var("x") / constant(3.0)),
+            erf(var("x")) * abs(var("x") - constant(3.0)),
+            log(var("x") + constant(1.0), var("x") * var("x")),
+            var("x") % constant(0.3) + fract(var("x")) + floor(var("x")),
+            sum(
+                "k",
+                constant(1.0),
+                constant(4.0),
+                var("k") * var("x").pow(var("k")),
+            ),
+            -var("x") * var("y"),
+        ];
+        let mut y = HashMap::new();
+        y.insert("y".to_string(), 2.5);
+        for expression in &expressions {
+            let expression = expression.partial_evaluate(&y);
+            let derivative = expression.differentiate("x").unwrap();
+            for &x in &[0.4, 0.7, 1.1] {
+                let mut vars = HashMap::new();
+                vars.insert("x".to_string(), x);
+                let symbolic = derivative.evaluate(&vars).unwrap();
+                let numeric = derivative_at(&expression, "x", x, 1e-3).unwrap();
+                // erf is only approximated, which throws its numeric derivative off by about 1e-6.
+                assert!(
+                    (symbolic - numeric).abs() < 1e-5 * numeric.abs().max(1.0),
+                    "{}: {} != {} at {}",
+                    expression,
+                    symbolic,
+                    numeric,
+                    x
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn symbolic_derivatives_are_simplified() {
+        assert_eq!(sin(var("x")).differentiate("x"), Ok(cos(var("x"))));
+        assert_eq!(
+            var("x").pow(constant(3.0)).differentiate("x"),
+            Ok(constant(3.0) * var("x").pow(constant(2.0)))
+        );
+        assert_eq!(
+            (constant(2.0) * var("x") + var("y")).differentiate("x"),
+            Ok(constant(2.0))
+        );
+        assert_eq!(sin(var("y")).differentiate("x"), Ok(constant(0.0)));
+        assert_eq!(
+            sum("x", constant(1.0), constant(3.0), var("x")).differentiate("x"),
+            Ok(constant(0.0))
+        );
+    }
+
+    #[test]
+    fn some_expressions_are_not_differentiable() {
+        assert_eq!(
+            gamma(var("x")).differentiate("x"),
+            Err(EvaluationError::NotDifferentiable)
+        );
+        assert_eq!(
+            call("f", vec![var("x")]).differentiate("x"),
+            Err(EvaluationError::NotDifferentiable)
+        );
+        assert_eq!(
+            sum("k", constant(1.0), var("x"), var("k")).differentiate("x"),
+            Err(EvaluationError::NotDifferentiable)
+        );
+        // Only the parts that depend on the variable have to be differentiable.
+        assert_eq!(
+            (gamma(var("y")) + var("x")).differentiate("x"),
+            Ok(constant(1.0))
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Printing of expression trees in the syntax the parser reads.
+
+use std::f64::consts::{E, PI, TAU};
+use std::fmt;
+
+use super::*;
+
+impl fmt::Display for ExpressionNode {
+    /// Writes the expression with only the parentheses that the precedence of its operators
+    /// requires, e.g. `(x + 1) * sin(x)`. Constants that are exactly `e` or `pi` are written by
+    /// name.
+    ///
+    /// The alternate form, `{:#}`, is meant for reading rather than parsing: constants that are
+    /// only within rounding error of `e`, `pi` or `tau` are written by name too, e.g. the
+    /// `6.283185307179585` a derivative might produce is written as `tau`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                self.fmt_operand(f, Operand::Left, left_node)?;
+                write!(f, " {} ", operator.symbol())?;
+                self.fmt_operand(f, Operand::Right, right_node)
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                if *operator == UnaryOperator::Negation {
+                    write!(f, "-")?;
+                    return self.fmt_operand(f, Operand::Right, child_node);
+                }
+                write!(f, "{}(", operator.name())?;
+                write_node(f, child_node)?;
+                write!(f, ")")
+            }
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => match operator {
+                NaryOperator::Log => write_call(f, "log", child_nodes),
+                NaryOperator::Sum { index_key } | NaryOperator::Product { index_key } => {
+                    write!(f, "{}({}", operator.name(), index_key)?;
+                    for node in child_nodes {
+                        write!(f, ", ")?;
+                        write_node(f, node)?;
+                    }
+                    write!(f, ")")
+                }
+            },
+            ExpressionNode::FunctionCallNode { name, args } => write_call(f, name, args),
+            ExpressionNode::VariableExprNode { variable_key } => write!(f, "{}", variable_key),
+            ExpressionNode::ConstantExprNode { value } => {
+                let sign = if value.is_sign_negative() { "-" } else { "" };
+                let name = match value.abs() {
+                    x if f.alternate() => classify_constant(x),
+                    x if x == E => Some("e"),
+                    x if x == PI => Some("pi"),
+                    _ => None,
+                };
+                match name {
+                    Some(name) => write!(f, "{}{}", sign, name),
+                    None => write!(f, "{}", value),
+                }
+            }
+        }
+    }
+}
+
+impl ExpressionNode {
+    /// Writes `child`, the `operand` of the operator at the root of `self`, in parentheses if it
+    /// needs them.
+    fn fmt_operand(&self, f: &mut fmt::Formatter, operand: Operand, child: &ExpressionNode) -> fmt::Result {
+        if self.operand_needs_parens(operand, child) {
+            write!(f, "(")?;
+            write_node(f, child)?;
+            write!(f, ")")
+        } else {
+            write_node(f, child)
+        }
+    }
+}
+
+/// Writes `node` in the same form, plain or alternate, as the node being written to `f`.
+fn write_node(f: &mut fmt::Formatter, node: &ExpressionNode) -> fmt::Result {
+    if f.alternate() {
+        write!(f, "{:#}", node)
+    } else {
+        write!(f, "{}", node)
+    }
+}
+
+fn write_call(f: &mut fmt::Formatter, name: &str, args: &[ExpressionNode]) -> fmt::Result {
+    write!(f, "{}(", name)?;
+    for (i, node) in args.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write_node(f, node)?;
+    }
+    write!(f, ")")
+}
+
+/// How close, relative to its size, a number has to be to a named constant to be taken for it.
+/// A few ulps is enough to absorb the rounding of folding e.g. `2 * pi / 2`, while values that
+/// merely look similar, like `3.1416`, keep their digits.
+const NAMED_CONSTANT_TOLERANCE: f64 = 1e-12;
+
+/// The name of the constant `value` is within rounding error of, if any: `pi`, `e` or `tau`.
+/// Negative values have no name, so callers write the sign themselves.
+pub(crate) fn classify_constant(value: f64) -> Option<&'static str> {
+    [(PI, "pi"), (E, "e"), (TAU, "tau")]
+        .iter()
+        .find(|(constant, _)| (value - constant).abs() <= NAMED_CONSTANT_TOLERANCE * constant)
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::*;
+
+    #[test]
+    fn parentheses_follow_precedence() {
+        let expression = (var("x") + constant(1.0)) * constant(2.0);
+        assert_eq!(expression.to_string(), "(x + 1) * 2");
+
+        let expression = var("x") - (var("y") - constant(1.0)) + var("z");
+        assert_eq!(expression.to_string(), "x - (y - 1) + z");
+
+        let expression = -var("x").pow(constant(2.0)) * (var("x") / constant(-2.0));
+        assert_eq!(expression.to_string(), "(-x ^ 2) * (x / (-2))");
+
+        let expression = var("x") % (var("y") * constant(2.0)) + constant(1.0);
+        assert_eq!(expression.to_string(), "x % (y * 2) + 1");
+
+        let expression = var("x").pow(var("y").pow(var("z")));
+        assert_eq!(expression.to_string(), "x ^ y ^ z");
+        let expression = var("x").pow(var("y")).pow(var("z"));
+        assert_eq!(expression.to_string(), "(x ^ y) ^ z");
+    }
+
+    #[test]
+    fn functions_and_constants() {
+        let expression = sin(constant(PI) * var("x")).pow(constant(-E));
+        assert_eq!(expression.to_string(), "sin(pi * x) ^ (-e)");
+
+        let expression = sum("k", constant(1.0), var("n"), log(constant(3.0), var("k")));
+        assert_eq!(expression.to_string(), "sum(k, 1, n, log(k, 3))");
+    }
+
+    #[test]
+    fn constants_close_to_named_ones() {
+        assert_eq!(constant(PI).to_string(), "pi");
+        assert_eq!(format!("{:#}", constant(PI)), "pi");
+
+        // Folding `pi * 11 / 11` doesn't give back exactly `pi`.
+        let almost_pi = PI * 11.0 / 11.0;
+        assert_ne!(almost_pi, PI);
+        assert_eq!(constant(almost_pi).to_string(), almost_pi.to_string());
+        assert_eq!(format!("{:#}", constant(almost_pi)), "pi");
+
+        let expression = sin(constant(2.0 * PI) * var("x")) + constant(-E) * constant(PI + 1e-9);
+        assert_eq!(
+            format!("{:#}", expression),
+            "sin(tau * x) + (-e) * 3.141592654589793"
+        );
+
+        assert_eq!(classify_constant(E), Some("e"));
+        assert_eq!(classify_constant(TAU), Some("tau"));
+        assert_eq!(classify_constant(-PI), None);
+        assert_eq!(classify_constant(0.0), None);
+    }
+}
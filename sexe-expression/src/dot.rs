@@ -0,0 +1,62 @@
+//! Export of expression trees as Graphviz graphs, for seeing how an expression was parsed.
+
+use super::*;
+
+impl ExpressionNode {
+    /// Renders the tree as a Graphviz digraph, with one node per subtree labeled with its operator
+    /// or value, and edges to its children from left to right. Nodes are numbered `n0`, `n1`, ...
+    /// in pre-order, so the output of the same tree is always the same.
+    pub fn to_dot(&self) -> String {
+        let mut nodes = String::new();
+        let mut edges = String::new();
+        let mut next_id = 0;
+        // Each entry is a node and the id of its parent.
+        let mut remaining = vec![(self, None)];
+        while let Some((node, parent)) = remaining.pop() {
+            let id = next_id;
+            next_id += 1;
+            nodes += &format!("    n{} [label=\"{}\"];\n", id, node.dot_label());
+            if let Some(parent) = parent {
+                edges += &format!("    n{} -> n{};\n", parent, id);
+            }
+            // Pushed in reverse, so that the leftmost child is numbered first.
+            remaining.extend(node.children().into_iter().rev().map(|child| (child, Some(id))));
+        }
+        format!("digraph expression {{\n{}{}}}\n", nodes, edges)
+    }
+
+    /// The label of the root of this subtree alone.
+    fn dot_label(&self) -> String {
+        match self {
+            ExpressionNode::BinaryExprNode { operator, .. } => operator.symbol().to_string(),
+            ExpressionNode::UnaryExprNode { operator, .. } => operator.name().to_string(),
+            ExpressionNode::NaryExprNode { operator, .. } => match operator {
+                NaryOperator::Log => "log".to_string(),
+                NaryOperator::Sum { index_key } => format!("sum {}", index_key),
+                NaryOperator::Product { index_key } => format!("prod {}", index_key),
+            },
+            ExpressionNode::FunctionCallNode { name, .. } => name.clone(),
+            ExpressionNode::VariableExprNode { variable_key } => variable_key.clone(),
+            ExpressionNode::ConstantExprNode { value } => format!("{}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use builder::*;
+
+    #[test]
+    fn trees_are_rendered_as_digraphs() {
+        let expression = constant(3.0) + var("x") * constant(2.0);
+        let dot = expression.to_dot();
+
+        assert!(dot.starts_with("digraph expression {\n"));
+        for label in &["n0 [label=\"+\"]", "n1 [label=\"3\"]", "n2 [label=\"*\"]", "n3 [label=\"x\"]", "n4 [label=\"2\"]"] {
+            assert!(dot.contains(label), "{} is missing from {}", label, dot);
+        }
+        assert_eq!(dot.matches(" -> ").count(), 4);
+        assert!(dot.contains("n0 -> n2;\n    n2 -> n3;"));
+        assert_eq!(dot, expression.to_dot());
+    }
+}
@@ -0,0 +1,159 @@
+//! Numeric search for the local minima and maxima of a function.
+
+use super::*;
+
+/// How narrow the bracket around each extremum is made before settling on its midpoint. An
+/// extremum is flat, so its position can't be found much more precisely than the square root of
+/// the precision of `f64` anyway.
+const EXTREMUM_TOLERANCE: f64 = 1e-9;
+
+/// The golden ratio minus one, by which golden-section search shrinks its bracket every step.
+const INVERSE_GOLDEN_RATIO: f64 = 0.618_033_988_749_894_8;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExtremumKind {
+    Min,
+    Max,
+}
+
+/// A local minimum or maximum of a function, at `x`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Extremum {
+    pub x: f64,
+    pub y: f64,
+    pub kind: ExtremumKind,
+}
+
+/// Finds the local minima and maxima of `func` of `x` inside the domain, sorted by `x`.
+///
+/// `func` is sampled like `evaluate_function_over_domain`, and every sample that is higher (or
+/// lower) than both of its neighbors brackets a maximum (or minimum), which is then narrowed down
+/// by golden-section search. A run of equal samples counts as a single sample, so a plateau has at
+/// most one extremum. Samples that aren't finite don't bracket anything, and the ends of the
+/// domain are never extrema.
+pub fn find_extrema(
+    start_x: f64,
+    end_x: f64,
+    resolution: u32,
+    func: &ExpressionNode,
+) -> Vec<Extremum> {
+    // Linear spacing accepts any bounds.
+    let positions = sample_positions(start_x, end_x, resolution, Spacing::Linear).unwrap();
+    let mut samples: Vec<(f64, f64)> = Vec::new();
+    for (&x, y) in positions.iter().zip(func.evaluate_many("x", &positions)) {
+        let y = y.unwrap_or(f64::NAN);
+        // Only the first sample of a plateau is kept.
+        if samples.last().is_none_or(|&(_, last_y)| last_y != y) {
+            samples.push((x, y));
+        }
+    }
+
+    let mut vars = HashMap::new();
+    let mut evaluate = |x: f64| {
+        vars.insert("x".to_string(), x);
+        func.evaluate(&vars).unwrap_or(f64::NAN)
+    };
+
+    let mut extrema = Vec::new();
+    for window in samples.windows(3) {
+        let ((a, y_a), (_, y_mid), (b, y_b)) = (window[0], window[1], window[2]);
+        if !(y_a.is_finite() && y_mid.is_finite() && y_b.is_finite()) {
+            continue;
+        }
+        let kind = if y_mid > y_a && y_mid > y_b {
+            ExtremumKind::Max
+        } else if y_mid < y_a && y_mid < y_b {
+            ExtremumKind::Min
+        } else {
+            continue;
+        };
+        // Golden-section search finds a minimum, so maxima are searched for upside down.
+        let sign = match kind {
+            ExtremumKind::Min => 1.0,
+            ExtremumKind::Max => -1.0,
+        };
+        let x = golden_section_minimum(|x| sign * evaluate(x), a, b);
+        extrema.push(Extremum {
+            x,
+            y: evaluate(x),
+            kind,
+        });
+    }
+    extrema
+}
+
+/// Narrows the bracket from `a` to `b` down to a minimum of `f` inside it, by repeatedly leaving
+/// out the part beyond the larger of two points that divide the bracket in the golden ratio.
+fn golden_section_minimum<F: FnMut(f64) -> f64>(mut f: F, mut a: f64, mut b: f64) -> f64 {
+    let mut c = b - INVERSE_GOLDEN_RATIO * (b - a);
+    let mut d = a + INVERSE_GOLDEN_RATIO * (b - a);
+    let (mut f_c, mut f_d) = (f(c), f(d));
+    while (b - a).abs() > EXTREMUM_TOLERANCE {
+        // NaN compares as larger here, so the search moves away from it.
+        if f_c < f_d || f_d.is_nan() {
+            b = d;
+            d = c;
+            f_d = f_c;
+            c = b - INVERSE_GOLDEN_RATIO * (b - a);
+            f_c = f(c);
+        } else {
+            a = c;
+            c = d;
+            f_c = f_d;
+            d = a + INVERSE_GOLDEN_RATIO * (b - a);
+            f_d = f(d);
+        }
+        // The bracket can't be split any further in floating point.
+        if c == a || d == b {
+            break;
+        }
+    }
+    (a + b) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn extrema_of_sine_are_found() {
+        let extrema = find_extrema(0.0, 4.0 * PI, 1000, &sin(var("x")));
+
+        let expected = [
+            (0.5 * PI, ExtremumKind::Max),
+            (1.5 * PI, ExtremumKind::Min),
+            (2.5 * PI, ExtremumKind::Max),
+            (3.5 * PI, ExtremumKind::Min),
+        ];
+        assert_eq!(extrema.len(), expected.len());
+        for (extremum, &(x, kind)) in extrema.iter().zip(&expected) {
+            assert_eq!(extremum.kind, kind);
+            assert!((extremum.x - x).abs() < 1e-6, "{} != {}", extremum.x, x);
+            assert!((extremum.y.abs() - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn monotonic_functions_have_no_extrema() {
+        assert_eq!(find_extrema(-10.0, 10.0, 1000, &var("x")), Vec::new());
+        assert_eq!(find_extrema(0.0, 10.0, 1000, &ln(var("x"))), Vec::new());
+    }
+
+    #[test]
+    fn plateaus_have_one_extremum() {
+        // floor(x) is a staircase, but only its top and bottom steps are higher or lower than
+        // their neighbors, and those are at the ends.
+        assert_eq!(find_extrema(0.0, 4.0, 100, &floor(var("x"))), Vec::new());
+
+        // |x| + |x - 1| is 1 all the way from 0 to 1. The samples are a power of two apart, so
+        // that they are exactly 1 there.
+        let trough = abs(var("x")) + abs(var("x") - constant(1.0));
+        let extrema = find_extrema(-2.0, 3.0, 80, &trough);
+        assert_eq!(extrema.len(), 1);
+        assert_eq!(extrema[0].kind, ExtremumKind::Min);
+        assert!((extrema[0].y - 1.0).abs() < 1e-9);
+        assert!(extrema[0].x >= -1e-9 && extrema[0].x <= 1.0 + 1e-9);
+    }
+}
@@ -0,0 +1,104 @@
+//! The floating point types that expressions can be evaluated in.
+
+use std::fmt::Debug;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use special;
+
+/// A floating point type that expressions can be evaluated in, see
+/// `ExpressionNode::evaluate_as`. It is implemented for `f32` and `f64`.
+pub trait Float:
+    Copy
+    + Debug
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    /// Converts a constant of the expression tree, which are stored as `f64`.
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+
+    fn powf(self, exponent: Self) -> Self;
+    fn powi(self, exponent: i32) -> Self;
+    fn sqrt(self) -> Self;
+    fn rem_euclid(self, divisor: Self) -> Self;
+    fn sin(self) -> Self;
+    fn asin(self) -> Self;
+    fn cos(self) -> Self;
+    fn acos(self) -> Self;
+    fn tan(self) -> Self;
+    fn asinh(self) -> Self;
+    fn acosh(self) -> Self;
+    fn atanh(self) -> Self;
+    fn abs(self) -> Self;
+    fn exp(self) -> Self;
+    fn log(self, base: Self) -> Self;
+    fn log2(self) -> Self;
+    fn log10(self) -> Self;
+    fn ln(self) -> Self;
+    fn ceil(self) -> Self;
+    fn floor(self) -> Self;
+    fn fract(self) -> Self;
+    fn to_radians(self) -> Self;
+    fn to_degrees(self) -> Self;
+    fn is_nan(self) -> bool;
+    fn is_infinite(self) -> bool;
+    fn is_finite(self) -> bool;
+
+    /// The error function. This is computed in `f64` and converted back.
+    fn erf(self) -> Self {
+        Self::from_f64(special::erf(self.to_f64()))
+    }
+
+    /// The gamma function. This is computed in `f64` and converted back.
+    fn gamma(self) -> Self {
+        Self::from_f64(special::gamma(self.to_f64()))
+    }
+}
+
+macro_rules! impl_float {
+    ($t:ty) => {
+        impl Float for $t {
+            fn from_f64(value: f64) -> Self {
+                value as $t
+            }
+
+            fn to_f64(self) -> f64 {
+                self.into()
+            }
+
+            fn powf(self, exponent: Self) -> Self { <$t>::powf(self, exponent) }
+            fn powi(self, exponent: i32) -> Self { <$t>::powi(self, exponent) }
+            fn sqrt(self) -> Self { <$t>::sqrt(self) }
+            fn rem_euclid(self, divisor: Self) -> Self { <$t>::rem_euclid(self, divisor) }
+            fn sin(self) -> Self { <$t>::sin(self) }
+            fn asin(self) -> Self { <$t>::asin(self) }
+            fn cos(self) -> Self { <$t>::cos(self) }
+            fn acos(self) -> Self { <$t>::acos(self) }
+            fn tan(self) -> Self { <$t>::tan(self) }
+            fn asinh(self) -> Self { <$t>::asinh(self) }
+            fn acosh(self) -> Self { <$t>::acosh(self) }
+            fn atanh(self) -> Self { <$t>::atanh(self) }
+            fn abs(self) -> Self { <$t>::abs(self) }
+            fn exp(self) -> Self { <$t>::exp(self) }
+            fn log(self, base: Self) -> Self { <$t>::log(self, base) }
+            fn log2(self) -> Self { <$t>::log2(self) }
+            fn log10(self) -> Self { <$t>::log10(self) }
+            fn ln(self) -> Self { <$t>::ln(self) }
+            fn ceil(self) -> Self { <$t>::ceil(self) }
+            fn floor(self) -> Self { <$t>::floor(self) }
+            fn fract(self) -> Self { <$t>::fract(self) }
+            fn to_radians(self) -> Self { <$t>::to_radians(self) }
+            fn to_degrees(self) -> Self { <$t>::to_degrees(self) }
+            fn is_nan(self) -> bool { <$t>::is_nan(self) }
+            fn is_infinite(self) -> bool { <$t>::is_infinite(self) }
+            fn is_finite(self) -> bool { <$t>::is_finite(self) }
+        }
+    };
+}
+
+impl_float!(f32);
+impl_float!(f64);
@@ -0,0 +1,179 @@
+//! Numeric definite integration.
+
+use super::*;
+
+/// How `integrate` approximates an integral.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IntegrationMethod {
+    /// The composite trapezoid rule over `intervals` equal intervals, at least one.
+    Trapezoid { intervals: u32 },
+    /// Simpson's rule, splitting the domain until the error of each part is estimated to be below
+    /// its share of `tolerance`.
+    AdaptiveSimpson { tolerance: f64 },
+}
+
+/// How many times adaptive Simpson's rule may halve the domain. Smooth functions converge long
+/// before this, so reaching it means the integral doesn't converge.
+const MAX_SIMPSON_DEPTH: u32 = 50;
+
+/// Integrates `func` of `x` from `start` to `end`.
+///
+/// Singularities are not skipped: if `func` is infinite or NaN at any point it is sampled at, as
+/// `1 / x` is at zero, or adaptive Simpson's rule doesn't converge, the integral is a
+/// `DomainError`. Errors from evaluating `func` are passed on.
+pub fn integrate(
+    start: f64,
+    end: f64,
+    func: &ExpressionNode,
+    method: IntegrationMethod,
+) -> Result<f64, EvaluationError> {
+    let mut vars = HashMap::new();
+    let mut f = |x: f64| {
+        vars.insert("x".to_string(), x);
+        match func.evaluate(&vars)? {
+            y if y.is_finite() => Ok(y),
+            _ => Err(EvaluationError::DomainError),
+        }
+    };
+
+    match method {
+        IntegrationMethod::Trapezoid { intervals } => {
+            let intervals = intervals.max(1);
+            let width = (end - start) / f64::from(intervals);
+            let mut sum = (f(start)? + f(end)?) / 2.0;
+            for i in 1..intervals {
+                sum += f(start + f64::from(i) * width)?;
+            }
+            Ok(sum * width)
+        }
+        IntegrationMethod::AdaptiveSimpson { tolerance } => {
+            let (f_start, f_end) = (f(start)?, f(end)?);
+            let mid = (start + end) / 2.0;
+            let f_mid = f(mid)?;
+            let whole = simpson(start, end, f_start, f_mid, f_end);
+            adaptive_simpson(
+                &mut f,
+                (start, f_start),
+                (mid, f_mid),
+                (end, f_end),
+                whole,
+                tolerance,
+                0,
+            )
+        }
+    }
+}
+
+/// Simpson's rule for the interval from `a` to `b`, given the function at its ends and middle.
+fn simpson(a: f64, b: f64, f_a: f64, f_mid: f64, f_b: f64) -> f64 {
+    (b - a) / 6.0 * (f_a + 4.0 * f_mid + f_b)
+}
+
+/// Refines `whole`, the Simpson estimate between the points `a` and `b`, by comparing it with the
+/// sum of the estimates of both halves.
+fn adaptive_simpson<F: FnMut(f64) -> Result<f64, EvaluationError>>(
+    f: &mut F,
+    (a, f_a): (f64, f64),
+    (mid, f_mid): (f64, f64),
+    (b, f_b): (f64, f64),
+    whole: f64,
+    tolerance: f64,
+    depth: u32,
+) -> Result<f64, EvaluationError> {
+    let (left_mid, right_mid) = ((a + mid) / 2.0, (mid + b) / 2.0);
+    let (f_left_mid, f_right_mid) = (f(left_mid)?, f(right_mid)?);
+    let left = simpson(a, mid, f_a, f_left_mid, f_mid);
+    let right = simpson(mid, b, f_mid, f_right_mid, f_b);
+    let difference = left + right - whole;
+
+    // The error of the halves is about a fifteenth of their difference from the whole, which is
+    // also used to extrapolate a better estimate.
+    if difference.abs() <= 15.0 * tolerance {
+        Ok(left + right + difference / 15.0)
+    } else if depth >= MAX_SIMPSON_DEPTH {
+        Err(EvaluationError::DomainError)
+    } else {
+        let left = adaptive_simpson(
+            f,
+            (a, f_a),
+            (left_mid, f_left_mid),
+            (mid, f_mid),
+            left,
+            tolerance / 2.0,
+            depth + 1,
+        )?;
+        let right = adaptive_simpson(
+            f,
+            (mid, f_mid),
+            (right_mid, f_right_mid),
+            (b, f_b),
+            right,
+            tolerance / 2.0,
+            depth + 1,
+        )?;
+        Ok(left + right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::*;
+    use std::f64::consts::PI;
+
+    const SIMPSON: IntegrationMethod = IntegrationMethod::AdaptiveSimpson { tolerance: 1e-10 };
+    const TRAPEZOID: IntegrationMethod = IntegrationMethod::Trapezoid { intervals: 1000 };
+
+    #[test]
+    fn smooth_functions_are_integrated() {
+        let area = integrate(0.0, PI, &sin(var("x")), SIMPSON).unwrap();
+        assert!((area - 2.0).abs() < 1e-10, "{}", area);
+        let area = integrate(0.0, 1.0, &var("x").pow(constant(2.0)), SIMPSON).unwrap();
+        assert!((area - 1.0 / 3.0).abs() < 1e-10, "{}", area);
+
+        // The trapezoid rule's error shrinks with the square of the interval width.
+        let area = integrate(0.0, PI, &sin(var("x")), TRAPEZOID).unwrap();
+        assert!((area - 2.0).abs() < 1e-5, "{}", area);
+        let area = integrate(0.0, 1.0, &var("x").pow(constant(2.0)), TRAPEZOID).unwrap();
+        assert!((area - 1.0 / 3.0).abs() < 1e-6, "{}", area);
+    }
+
+    #[test]
+    fn reversed_bounds_negate_the_integral() {
+        let area = integrate(1.0, 0.0, &var("x"), SIMPSON).unwrap();
+        assert!((area + 0.5).abs() < 1e-12, "{}", area);
+        let area = integrate(
+            1.0,
+            0.0,
+            &var("x"),
+            IntegrationMethod::Trapezoid { intervals: 0 },
+        );
+        assert_eq!(area, Ok(-0.5));
+    }
+
+    #[test]
+    fn singularities_are_domain_errors() {
+        let reciprocal = constant(1.0) / var("x");
+        assert_eq!(
+            integrate(-1.0, 1.0, &reciprocal, SIMPSON),
+            Err(EvaluationError::DomainError)
+        );
+        assert_eq!(
+            integrate(-1.0, 1.0, &reciprocal, TRAPEZOID),
+            Err(EvaluationError::DomainError)
+        );
+        // Even when the pole falls between the samples, Simpson's rule doesn't converge.
+        assert_eq!(
+            integrate(-1.0, 2.0, &reciprocal, SIMPSON),
+            Err(EvaluationError::DomainError)
+        );
+        assert_eq!(
+            integrate(-1.0, 1.0, &ln(var("x")), SIMPSON),
+            Err(EvaluationError::DomainError)
+        );
+        assert_eq!(
+            integrate(0.0, 1.0, &var("y"), SIMPSON),
+            Err(EvaluationError::VariableNotFoundError)
+        );
+    }
+}
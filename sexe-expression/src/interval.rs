@@ -0,0 +1,390 @@
+//! Interval arithmetic over expression trees.
+
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use super::*;
+
+/// A closed interval `(lower, upper)`.
+type Interval = (f64, f64);
+
+/// The interval that is returned whenever nothing tighter can be guaranteed, e.g. across a pole.
+const ENTIRE: Interval = (f64::NEG_INFINITY, f64::INFINITY);
+
+/// Where the gamma function attains its minimum on the positive reals.
+const GAMMA_MIN_X: f64 = 1.461_632_144_968_362_3;
+
+impl ExpressionNode {
+    /// Bounds the values the expression takes while each variable ranges over the interval
+    /// `(lower, upper)` it is bound to in `vars`. The bounds are guaranteed to contain every point
+    /// evaluation in those ranges (up to floating point rounding), but they may be wider than the
+    /// actual range, e.g. `x - x` is bounded by `(-1, 1)` for `x` in `(0, 1)`.
+    ///
+    /// Across poles, where an operator is undefined on part of its input, and for calls to custom
+    /// functions, nothing tighter than `(-inf, inf)` is returned.
+    pub fn evaluate_interval(
+        &self,
+        vars: &HashMap<String, (f64, f64)>,
+    ) -> Result<(f64, f64), EvaluationError> {
+        match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                let left = left_node.evaluate_interval(vars)?;
+                let right = right_node.evaluate_interval(vars)?;
+                Ok(match operator {
+                    BinaryOperator::Addition => bounds(left.0 + right.0, left.1 + right.1),
+                    BinaryOperator::Subtraction => bounds(left.0 - right.1, left.1 - right.0),
+                    BinaryOperator::Multiplication => corners(left, right, |a, b| a * b),
+                    BinaryOperator::Division => divide(left, right),
+                    BinaryOperator::Modulo => modulo(left, right),
+                    BinaryOperator::Exponentiation => power(left, right),
+                })
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                let x = child_node.evaluate_interval(vars)?;
+                Ok(match operator {
+                    UnaryOperator::Negation => bounds(-x.1, -x.0),
+                    // sin peaks at pi/2 and bottoms out at -pi/2.
+                    UnaryOperator::Sin => periodic(x, f64::sin, FRAC_PI_2, -FRAC_PI_2),
+                    UnaryOperator::Asin => clamped(x, -1.0, 1.0, f64::asin),
+                    UnaryOperator::Cos => periodic(x, f64::cos, 0.0, PI),
+                    UnaryOperator::Acos => clamped(x, -1.0, 1.0, f64::acos),
+                    // tan is increasing between its poles at pi/2 + k * pi, and its reciprocal is
+                    // decreasing between its poles at k * pi.
+                    UnaryOperator::Tan if !contains_phase(x, FRAC_PI_2, PI) => {
+                        monotonic(x, f64::tan)
+                    }
+                    UnaryOperator::Ctan if !contains_phase(x, 0.0, PI) => {
+                        monotonic(x, |x| 1.0 / x.tan())
+                    }
+                    UnaryOperator::Tan | UnaryOperator::Ctan => ENTIRE,
+                    UnaryOperator::Asinh => monotonic(x, f64::asinh),
+                    UnaryOperator::Acosh => clamped(x, 1.0, f64::INFINITY, f64::acosh),
+                    UnaryOperator::Atanh => clamped(x, -1.0, 1.0, f64::atanh),
+                    UnaryOperator::Abs => {
+                        if x.0 >= 0.0 || x.1 <= 0.0 {
+                            monotonic(x, f64::abs)
+                        } else {
+                            (0.0, x.1.max(-x.0))
+                        }
+                    }
+                    UnaryOperator::Exp => monotonic(x, f64::exp),
+                    UnaryOperator::Log2 => clamped(x, 0.0, f64::INFINITY, f64::log2),
+                    UnaryOperator::Log10 => clamped(x, 0.0, f64::INFINITY, f64::log10),
+                    UnaryOperator::Ln => clamped(x, 0.0, f64::INFINITY, f64::ln),
+                    UnaryOperator::Ceil => monotonic(x, f64::ceil),
+                    UnaryOperator::Floor => monotonic(x, f64::floor),
+                    // fract is increasing as long as the integer part doesn't change.
+                    UnaryOperator::Fract if x.0.trunc() == x.1.trunc() => monotonic(x, f64::fract),
+                    UnaryOperator::Fract if x.0 >= 0.0 => (0.0, 1.0),
+                    UnaryOperator::Fract if x.1 <= 0.0 => (-1.0, 0.0),
+                    UnaryOperator::Fract => (-1.0, 1.0),
+                    UnaryOperator::Erf => monotonic(x, special::erf),
+                    // On the positive reals gamma falls until its minimum, then rises.
+                    UnaryOperator::Gamma if x.0 > 0.0 => {
+                        let mut values = vec![special::gamma(x.0), special::gamma(x.1)];
+                        if x.0 < GAMMA_MIN_X && GAMMA_MIN_X < x.1 {
+                            values.push(special::gamma(GAMMA_MIN_X));
+                        }
+                        hull(&values)
+                    }
+                    UnaryOperator::Gamma => ENTIRE,
+                    // Values between finite bounds are finite, but they may still be NaN.
+                    UnaryOperator::IsInf if x.0.is_finite() && x.1.is_finite() => (0.0, 0.0),
+                    UnaryOperator::IsNan | UnaryOperator::IsInf | UnaryOperator::IsFinite => (0.0, 1.0),
+                })
+            }
+            ExpressionNode::NaryExprNode {
+                operator: operator @ (NaryOperator::Sum { index_key } | NaryOperator::Product { index_key }),
+                child_nodes,
+            } => match &child_nodes[..] {
+                [lower, upper, body] => {
+                    let lower = lower.evaluate_interval(vars)?;
+                    let upper = upper.evaluate_interval(vars)?;
+                    let (first, last) = (lower.0.round(), upper.0.round());
+                    // Only a sum or product over a known set of indices is bounded term by term.
+                    if lower.1.round() != first || upper.1.round() != last || !first.is_finite() {
+                        return Ok(ENTIRE);
+                    }
+                    let indices = match series_indices(first, last) {
+                        Ok(Some(indices)) => indices,
+                        _ => return Ok(ENTIRE),
+                    };
+                    let mut body_vars = vars.clone();
+                    let empty = operator.empty_value();
+                    let mut total = (empty, empty);
+                    for index in indices {
+                        let index = index as f64;
+                        body_vars.insert(index_key.clone(), (index, index));
+                        let term = body.evaluate_interval(&body_vars)?;
+                        total = match operator {
+                            NaryOperator::Product { .. } => corners(total, term, |a, b| a * b),
+                            _ => bounds(total.0 + term.0, total.1 + term.1),
+                        };
+                    }
+                    Ok(total)
+                }
+                _ => Err(operator.wrong_number_of_args(child_nodes.len())),
+            },
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => {
+                let child_intervals: Vec<Interval> = child_nodes
+                    .iter()
+                    .map(|node| node.evaluate_interval(vars))
+                    .collect::<Result<_, _>>()?;
+                match operator {
+                    NaryOperator::Log => match &child_intervals[..] {
+                        [x, base] => Ok(divide(
+                            clamped(*x, 0.0, f64::INFINITY, f64::ln),
+                            clamped(*base, 0.0, f64::INFINITY, f64::ln),
+                        )),
+                        _ => Err(operator.wrong_number_of_args(child_intervals.len())),
+                    },
+                    NaryOperator::Sum { .. } | NaryOperator::Product { .. } => unreachable!(),
+                }
+            }
+            ExpressionNode::FunctionCallNode { args, .. } => {
+                for node in args {
+                    node.evaluate_interval(vars)?;
+                }
+                Ok(ENTIRE)
+            }
+            ExpressionNode::VariableExprNode { variable_key } => match vars.get(variable_key) {
+                Some(x) => Ok(*x),
+                None => Err(EvaluationError::VariableNotFoundError),
+            },
+            ExpressionNode::ConstantExprNode { value } => Ok((*value, *value)),
+        }
+    }
+}
+
+/// Builds an interval, widening any bound that came out as NaN (e.g. from `inf - inf`).
+fn bounds(lower: f64, upper: f64) -> Interval {
+    (
+        if lower.is_nan() { f64::NEG_INFINITY } else { lower },
+        if upper.is_nan() { f64::INFINITY } else { upper },
+    )
+}
+
+/// The smallest interval containing all of `values`.
+fn hull(values: &[f64]) -> Interval {
+    if values.iter().any(|value| value.is_nan()) {
+        return ENTIRE;
+    }
+    values.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lower, upper), &value| {
+        (lower.min(value), upper.max(value))
+    })
+}
+
+/// Applies a function that is monotonic (in either direction) over `x`.
+fn monotonic<F: Fn(f64) -> f64>(x: Interval, f: F) -> Interval {
+    hull(&[f(x.0), f(x.1)])
+}
+
+/// Applies a monotonic function that is only defined on `[min, max]` to the part of `x` that lies
+/// there.
+fn clamped<F: Fn(f64) -> f64>(x: Interval, min: f64, max: f64, f: F) -> Interval {
+    if x.1 < min || x.0 > max {
+        return ENTIRE;
+    }
+    monotonic((x.0.max(min), x.1.min(max)), f)
+}
+
+/// Applies a function that is monotonic in each argument separately, so its extremes over the
+/// box `x` by `y` lie at the corners.
+fn corners<F: Fn(f64, f64) -> f64>(x: Interval, y: Interval, f: F) -> Interval {
+    hull(&[f(x.0, y.0), f(x.0, y.1), f(x.1, y.0), f(x.1, y.1)])
+}
+
+fn divide(x: Interval, y: Interval) -> Interval {
+    if y.0 <= 0.0 && 0.0 <= y.1 {
+        ENTIRE
+    } else {
+        corners(x, y, |a, b| a / b)
+    }
+}
+
+fn modulo(x: Interval, y: Interval) -> Interval {
+    if y.0 <= 0.0 && 0.0 <= y.1 {
+        ENTIRE
+    } else if y.0 == y.1 && x.0.div_euclid(y.0) == x.1.div_euclid(y.0) {
+        // Within one period, the remainder grows with `x`.
+        monotonic(x, |x| x.rem_euclid(y.0))
+    } else {
+        (0.0, y.0.abs().max(y.1.abs()))
+    }
+}
+
+fn power(base: Interval, exponent: Interval) -> Interval {
+    let (n, is_integer) = (exponent.0, exponent.0 == exponent.1 && exponent.0.fract() == 0.0);
+    let contains_zero = base.0 <= 0.0 && 0.0 <= base.1;
+    if is_integer && n < 0.0 && contains_zero {
+        ENTIRE
+    } else if is_integer && n > 0.0 && n % 2.0 == 0.0 && contains_zero {
+        // An even power has its minimum at zero.
+        (0.0, base.0.powf(n).max(base.1.powf(n)))
+    } else if is_integer {
+        monotonic(base, |x| x.powf(n))
+    } else if base.0 >= 0.0 {
+        // For a non-negative base, `x^y` is monotonic in `x` and in `y`.
+        corners(base, exponent, f64::powf)
+    } else {
+        // A negative base is only defined for integer exponents.
+        ENTIRE
+    }
+}
+
+/// Bounds a function with period `2 pi` and range `[-1, 1]` that peaks at `peak` and bottoms out
+/// at `trough`.
+fn periodic<F: Fn(f64) -> f64>(x: Interval, f: F, peak: f64, trough: f64) -> Interval {
+    if is_wider_than(x, 2.0 * PI) {
+        return (-1.0, 1.0);
+    }
+    let (lower, upper) = monotonic(x, f);
+    (
+        if contains_phase(x, trough, 2.0 * PI) { -1.0 } else { lower },
+        if contains_phase(x, peak, 2.0 * PI) { 1.0 } else { upper },
+    )
+}
+
+/// Whether `x` spans at least `width`. Intervals with infinite bounds count as wider than anything.
+fn is_wider_than(x: Interval, width: f64) -> bool {
+    let x_width = x.1 - x.0;
+    x_width.is_nan() || x_width >= width
+}
+
+/// Whether `x` contains `phase + k * period` for some integer `k`.
+fn contains_phase(x: Interval, phase: f64, period: f64) -> bool {
+    if is_wider_than(x, period) {
+        return true;
+    }
+    let k = ((x.0 - phase) / period).ceil();
+    phase + k * period <= x.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{constant, cos, log, sin, tan, var};
+
+    fn x_bound_to(interval: Interval) -> HashMap<String, Interval> {
+        let mut vars = HashMap::new();
+        vars.insert("x".to_string(), interval);
+        vars
+    }
+
+    #[test]
+    fn intervals_contain_point_evaluations() {
+        let unary_operators = vec![
+            UnaryOperator::Negation,
+            UnaryOperator::Sin,
+            UnaryOperator::Asin,
+            UnaryOperator::Cos,
+            UnaryOperator::Acos,
+            UnaryOperator::Tan,
+            UnaryOperator::Ctan,
+            UnaryOperator::Asinh,
+            UnaryOperator::Acosh,
+            UnaryOperator::Atanh,
+            UnaryOperator::Abs,
+            UnaryOperator::Exp,
+            UnaryOperator::Log2,
+            UnaryOperator::Log10,
+            UnaryOperator::Ln,
+            UnaryOperator::Ceil,
+            UnaryOperator::Floor,
+            UnaryOperator::Fract,
+            UnaryOperator::Erf,
+            UnaryOperator::Gamma,
+            UnaryOperator::IsNan,
+            UnaryOperator::IsInf,
+            UnaryOperator::IsFinite,
+        ];
+        let mut expressions: Vec<ExpressionNode> = unary_operators
+            .into_iter()
+            .map(|operator| ExpressionNode::unary(operator, var("x")))
+            .collect();
+        for &n in &[2.0, 3.0, -1.0, -2.0, 0.5, 0.0] {
+            expressions.push(var("x").pow(constant(n)));
+        }
+        expressions.push(constant(2.0).pow(var("x")));
+        expressions.push(var("x").pow(var("x")));
+        for &n in &[3.0, -2.5, 100.0] {
+            expressions.push(var("x") % constant(n));
+        }
+        expressions.push(constant(1.0) / (var("x") - constant(3.0001)));
+        expressions.push(sin(var("x")) * cos(var("x")) + var("x") / constant(4.0));
+        expressions.push(log(constant(2.0), var("x")));
+
+        let intervals = [
+            (-3.5, -0.2),
+            (-1.0, 1.0),
+            (0.1, 0.9),
+            (1.2, 1.7),
+            (2.0, 4.0),
+            (3.0, 3.0),
+            (-10.0, 10.0),
+        ];
+        for expression in &expressions {
+            for &interval in &intervals {
+                let (lower, upper) = expression.evaluate_interval(&x_bound_to(interval)).unwrap();
+                for &x in &[interval.0, (interval.0 + interval.1) / 2.0, interval.1] {
+                    let mut vars = HashMap::new();
+                    vars.insert("x".to_string(), x);
+                    // Points outside of the domain have no value to contain.
+                    let y = match expression.evaluate(&vars) {
+                        Err(EvaluationError::DomainError) => continue,
+                        y => y.unwrap(),
+                    };
+                    assert!(
+                        y.is_nan() || (lower <= y && y <= upper),
+                        "{:?} at x = {} is {}, outside of ({}, {})",
+                        expression,
+                        x,
+                        y,
+                        lower,
+                        upper
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn intervals_are_tight_for_simple_expressions() {
+        let sine = sin(var("x"));
+        assert_eq!(sine.evaluate_interval(&x_bound_to((0.0, PI))).unwrap().1, 1.0);
+        assert_eq!(sine.evaluate_interval(&x_bound_to((-1.0, 10.0))).unwrap(), (-1.0, 1.0));
+
+        let square = var("x").pow(constant(2.0));
+        assert_eq!(square.evaluate_interval(&x_bound_to((-2.0, 3.0))).unwrap(), (0.0, 9.0));
+
+        let sum = var("x") + constant(1.0);
+        assert_eq!(sum.evaluate_interval(&x_bound_to((-2.0, 3.0))).unwrap(), (-1.0, 4.0));
+    }
+
+    #[test]
+    fn poles_give_unbounded_intervals() {
+        // The spike of 1 / (x - 3.0001) falls between any reasonable set of samples.
+        let spike = constant(1.0) / (var("x") - constant(3.0001));
+        assert_eq!(spike.evaluate_interval(&x_bound_to((2.0, 4.0))).unwrap(), ENTIRE);
+
+        let tangent = tan(var("x"));
+        assert_eq!(tangent.evaluate_interval(&x_bound_to((1.0, 2.0))).unwrap(), ENTIRE);
+    }
+
+    #[test]
+    fn unbound_variables_are_errors() {
+        assert_eq!(
+            var("y").evaluate_interval(&x_bound_to((0.0, 1.0))),
+            Err(EvaluationError::VariableNotFoundError)
+        );
+    }
+}
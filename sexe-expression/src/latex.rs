@@ -0,0 +1,233 @@
+//! Rendering of expression trees as LaTeX.
+
+use super::*;
+use display::classify_constant;
+
+impl ExpressionNode {
+    /// Renders the expression as LaTeX math, e.g. `\frac{x}{2}` or `\sin\left(x\right)^{2}`.
+    /// Parentheses are only inserted where the precedence of the operators requires them, the same
+    /// as for `Display`. Constants within rounding error of `e`, `pi` or `tau` are rendered as `e`,
+    /// `\pi` and `\tau`; they are told apart from other numbers only by their value.
+    pub fn to_latex(&self) -> String {
+        match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                let left = || self.latex_operand(Operand::Left, left_node);
+                let right = || self.latex_operand(Operand::Right, right_node);
+                match operator {
+                    BinaryOperator::Addition => format!("{} + {}", left(), right()),
+                    BinaryOperator::Subtraction => format!("{} - {}", left(), right()),
+                    BinaryOperator::Multiplication => format!("{} \\cdot {}", left(), right()),
+                    BinaryOperator::Modulo => format!("{} \\bmod {}", left(), right()),
+                    // Fractions are stacked, so their parts never need parentheses.
+                    BinaryOperator::Division => {
+                        format!("\\frac{{{}}}{{{}}}", left_node.to_latex(), right_node.to_latex())
+                    }
+                    BinaryOperator::Exponentiation => {
+                        if let ExpressionNode::ConstantExprNode { value } = **right_node {
+                            if value == 0.5 {
+                                return format!("\\sqrt{{{}}}", left_node.to_latex());
+                            }
+                        }
+                        // A stacked fraction needs parentheses as a base too.
+                        let base_is_fraction = matches!(
+                            **left_node,
+                            ExpressionNode::BinaryExprNode {
+                                operator: BinaryOperator::Division,
+                                ..
+                            }
+                        );
+                        let base = if base_is_fraction {
+                            latex_parens(&left_node.to_latex())
+                        } else {
+                            left()
+                        };
+                        // The exponent is raised, so it never needs parentheses either.
+                        format!("{}^{{{}}}", base, right_node.to_latex())
+                    }
+                }
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                let child = latex_parens(&child_node.to_latex());
+                match operator {
+                    UnaryOperator::Negation => {
+                        format!("-{}", self.latex_operand(Operand::Right, child_node))
+                    }
+                    UnaryOperator::Sin => format!("\\sin{}", child),
+                    UnaryOperator::Asin => format!("\\arcsin{}", child),
+                    UnaryOperator::Cos => format!("\\cos{}", child),
+                    UnaryOperator::Acos => format!("\\arccos{}", child),
+                    UnaryOperator::Tan => format!("\\tan{}", child),
+                    UnaryOperator::Ctan => format!("\\cot{}", child),
+                    UnaryOperator::Asinh => format!("\\operatorname{{arsinh}}{}", child),
+                    UnaryOperator::Acosh => format!("\\operatorname{{arcosh}}{}", child),
+                    UnaryOperator::Atanh => format!("\\operatorname{{artanh}}{}", child),
+                    UnaryOperator::Abs => format!("\\left|{}\\right|", child_node.to_latex()),
+                    UnaryOperator::Exp => format!("\\exp{}", child),
+                    UnaryOperator::Log2 => format!("\\log_{{2}}{}", child),
+                    UnaryOperator::Log10 => format!("\\log_{{10}}{}", child),
+                    UnaryOperator::Ln => format!("\\ln{}", child),
+                    UnaryOperator::Ceil => format!("\\lceil {} \\rceil", child_node.to_latex()),
+                    UnaryOperator::Floor => format!("\\lfloor {} \\rfloor", child_node.to_latex()),
+                    UnaryOperator::Fract => format!("\\operatorname{{fract}}{}", child),
+                    UnaryOperator::Erf => format!("\\operatorname{{erf}}{}", child),
+                    UnaryOperator::Gamma => format!("\\Gamma{}", child),
+                    UnaryOperator::IsNan => format!("\\operatorname{{isnan}}{}", child),
+                    UnaryOperator::IsInf => format!("\\operatorname{{isinf}}{}", child),
+                    UnaryOperator::IsFinite => format!("\\operatorname{{isfinite}}{}", child),
+                }
+            }
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => match operator {
+                NaryOperator::Log => match &child_nodes[..] {
+                    [x, base] => format!(
+                        "\\log_{{{}}}{}",
+                        base.to_latex(),
+                        latex_parens(&x.to_latex())
+                    ),
+                    _ => format!("\\log{}", latex_parens(&latex_args(child_nodes))),
+                },
+                NaryOperator::Sum { index_key } | NaryOperator::Product { index_key } => match &child_nodes[..] {
+                    [lower, upper, body] => {
+                        let body = if body.precedence() < Precedence::Product {
+                            latex_parens(&body.to_latex())
+                        } else {
+                            body.to_latex()
+                        };
+                        format!(
+                            "\\{}_{{{}={}}}^{{{}}} {}",
+                            operator.name(),
+                            index_key,
+                            lower.to_latex(),
+                            upper.to_latex(),
+                            body
+                        )
+                    }
+                    _ => format!(
+                        "\\operatorname{{{}}}{}",
+                        operator.name(),
+                        latex_parens(&latex_args(child_nodes))
+                    ),
+                },
+            },
+            ExpressionNode::FunctionCallNode { name, args } => {
+                format!("\\operatorname{{{}}}{}", name, latex_parens(&latex_args(args)))
+            }
+            ExpressionNode::VariableExprNode { variable_key } => variable_key.clone(),
+            ExpressionNode::ConstantExprNode { value } => {
+                let sign = if value.is_sign_negative() { "-" } else { "" };
+                match classify_constant(value.abs()) {
+                    Some("pi") => format!("{}\\pi", sign),
+                    Some("tau") => format!("{}\\tau", sign),
+                    Some(name) => format!("{}{}", sign, name),
+                    None => format!("{}", value),
+                }
+            }
+        }
+    }
+
+    /// Renders `child`, the `operand` of the operator at the root of `self`, in parentheses if it
+    /// needs them.
+    fn latex_operand(&self, operand: Operand, child: &ExpressionNode) -> String {
+        if self.operand_needs_parens(operand, child) {
+            latex_parens(&child.to_latex())
+        } else {
+            child.to_latex()
+        }
+    }
+}
+
+/// Wraps `latex` in parentheses that grow with their content.
+fn latex_parens(latex: &str) -> String {
+    format!("\\left({}\\right)", latex)
+}
+
+fn latex_args(nodes: &[ExpressionNode]) -> String {
+    nodes.iter().map(|node| node.to_latex()).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{E, PI};
+
+    use builder::*;
+
+    #[test]
+    fn division_renders_as_a_fraction() {
+        let expression = var("x") / constant(2.0);
+        assert_eq!(expression.to_latex(), "\\frac{x}{2}");
+
+        let expression = (var("x") + constant(1.0)) / (var("x") - constant(1.0));
+        assert_eq!(expression.to_latex(), "\\frac{x + 1}{x - 1}");
+    }
+
+    #[test]
+    fn powers_and_functions() {
+        let expression = sin(var("x")).pow(constant(2.0));
+        assert_eq!(expression.to_latex(), "\\sin\\left(x\\right)^{2}");
+
+        let expression = sin(var("x").pow(constant(2.0)));
+        assert_eq!(expression.to_latex(), "\\sin\\left(x^{2}\\right)");
+
+        let expression = var("x").pow(constant(0.5));
+        assert_eq!(expression.to_latex(), "\\sqrt{x}");
+
+        // x^(y + 1) needs no parentheses, since the exponent is raised.
+        let expression = var("x").pow(var("y") + constant(1.0));
+        assert_eq!(expression.to_latex(), "x^{y + 1}");
+
+        let expression = abs(var("x"));
+        assert_eq!(expression.to_latex(), "\\left|x\\right|");
+    }
+
+    #[test]
+    fn parentheses_follow_precedence() {
+        let expression = (var("x") + constant(1.0)) * constant(2.0);
+        assert_eq!(expression.to_latex(), "\\left(x + 1\\right) \\cdot 2");
+
+        let expression = var("x") - (var("y") - constant(1.0));
+        assert_eq!(expression.to_latex(), "x - \\left(y - 1\\right)");
+
+        let expression = -(var("x") + constant(1.0));
+        assert_eq!(expression.to_latex(), "-\\left(x + 1\\right)");
+
+        let expression = (var("x") / constant(2.0)).pow(constant(2.0));
+        assert_eq!(expression.to_latex(), "\\left(\\frac{x}{2}\\right)^{2}");
+
+        // The same parentheses as Display: x + -2 is written x + (-2).
+        let expression = var("x") + constant(-2.0);
+        assert_eq!(expression.to_latex(), "x + \\left(-2\\right)");
+        assert_eq!(expression.to_string(), "x + (-2)");
+    }
+
+    #[test]
+    fn logarithm_with_base() {
+        let expression = log(constant(3.0), constant(9.0));
+        assert_eq!(expression.to_latex(), "\\log_{3}\\left(9\\right)");
+    }
+
+    #[test]
+    fn known_constants_are_named() {
+        let expression = constant(2.0) * constant(PI) * var("x");
+        assert_eq!(expression.to_latex(), "2 \\cdot \\pi \\cdot x");
+
+        let expression = constant(E).pow(constant(-1.0));
+        assert_eq!(expression.to_latex(), "e^{-1}");
+
+        assert_eq!(constant(-PI).to_latex(), "-\\pi");
+        assert_eq!(constant(1.5).to_latex(), "1.5");
+
+        // Folded arithmetic is only within rounding error of the constants.
+        assert_eq!(constant(PI * 11.0 / 11.0).to_latex(), "\\pi");
+        assert_eq!(constant(-2.0 * PI).to_latex(), "-\\tau");
+        assert_eq!(constant(PI + 1e-9).to_latex(), "3.141592654589793");
+    }
+}
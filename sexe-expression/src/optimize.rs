@@ -0,0 +1,512 @@
+//! Rewrites of expression trees that make them cheaper to evaluate repeatedly, without changing
+//! what they evaluate to.
+
+use super::*;
+
+/// The prefix of the names `cse` binds shared subtrees to, `$0`, `$1` and so on. The parser never
+/// produces it, so they can't clash with the variables of the expression.
+const SHARED_PREFIX: &str = "$";
+
+fn shared_name(index: usize) -> String {
+    format!("{}{}", SHARED_PREFIX, index)
+}
+
+/// Evaluates the shared subtrees and the rest of an expression, as returned by
+/// `ExpressionNode::cse`, with the variables bound to `vars`. This is the value of the expression
+/// they were factored out of, though if it fails to evaluate in several places, the error may be
+/// a different one of them.
+pub fn evaluate_cse(
+    shared: &[ExpressionNode],
+    root: &ExpressionNode,
+    vars: &HashMap<String, f64>,
+) -> Result<f64, EvaluationError> {
+    evaluate_cse_ctx(shared, root, &EvaluationContext::with_variables(vars.clone()))
+}
+
+/// Like `evaluate_cse`, but evaluates against the variables and settings of `ctx`.
+pub fn evaluate_cse_ctx(
+    shared: &[ExpressionNode],
+    root: &ExpressionNode,
+    ctx: &EvaluationContext,
+) -> Result<f64, EvaluationError> {
+    let mut ctx = ctx.clone();
+    for (index, subtree) in shared.iter().enumerate() {
+        let value = subtree.evaluate_ctx(&ctx)?;
+        ctx.variables.insert(shared_name(index), value);
+    }
+    root.evaluate_ctx(&ctx)
+}
+
+/// A node of an expression tree, apart from its children, which are given by the numbers
+/// `Numbering` gave them. Nodes are equal if and only if they have the same shape.
+#[derive(PartialEq, Eq, Hash)]
+enum Shape {
+    Binary(BinaryOperator, Vec<usize>),
+    Unary(UnaryOperator, Vec<usize>),
+    Nary(NaryOperator, Vec<usize>),
+    Call(String, Vec<usize>),
+    Variable(String),
+    /// The bits of the value, so that e.g. `0` and `-0` are told apart.
+    Constant(u64),
+}
+
+/// Numbers the subtrees of an expression, giving equal ones the same number. Since the children
+/// of a subtree are numbered before it, comparing two subtrees only compares their shapes, rather
+/// than every node below them.
+#[derive(Default)]
+struct Numbering {
+    numbers: HashMap<Shape, usize>,
+}
+
+impl Numbering {
+    fn number(&mut self, shape: Shape) -> usize {
+        let next = self.numbers.len();
+        *self.numbers.entry(shape).or_insert(next)
+    }
+}
+
+/// The state of `ExpressionNode::share`.
+struct Sharing {
+    numbering: Numbering,
+    /// How often each subtree that may be shared occurs, by number.
+    counts: Vec<usize>,
+    /// The index in `shared` of each subtree that is shared, by number.
+    shared_as: HashMap<usize, usize>,
+    shared: Vec<ExpressionNode>,
+}
+
+impl ExpressionNode {
+    /// Replaces every maximal subtree that doesn't depend on any variable with a constant, so that
+    /// e.g. the `ln(123456) * pi^2` of `sin(x) + ln(123456) * pi^2` is computed once rather than
+    /// for every point of a sweep. The constants are computed against `ctx`, and if one of them
+    /// fails to evaluate (e.g. `asin(2)` in a strict context), that error is returned.
+    ///
+    /// Calls to custom functions are never folded, since they may not return the same value every
+    /// time.
+    pub fn hoist_constants(&self, ctx: &EvaluationContext) -> Result<ExpressionNode, EvaluationError> {
+        self.hoist(ctx).map(|(node, _)| node)
+    }
+
+    /// Binds the variables in `vars` to their values and folds whatever becomes constant, leaving
+    /// the other variables in place. E.g. `a*sin(b*x)` with `a = 2` and `b = 3` becomes
+    /// `2*sin(3*x)`, which is cheaper to sweep over `x` than the original with all three bound.
+    ///
+    /// If a subtree that becomes constant fails to evaluate, nothing is folded, and the error is
+    /// left for the evaluation of the result to report.
+    pub fn partial_evaluate(&self, vars: &HashMap<String, f64>) -> ExpressionNode {
+        let substituted = vars.iter().fold(self.clone(), |tree, (name, &value)| {
+            substitute_var(&tree, name, &ExpressionNode::ConstantExprNode { value })
+        });
+        substituted
+            .hoist_constants(&EvaluationContext::new())
+            .unwrap_or(substituted)
+    }
+
+    /// Common subexpression elimination: factors out the subtrees that occur more than once, e.g.
+    /// the `sin(x)` of `sin(x)^2 + sin(x)*cos(x)`, so that each is evaluated once per point of a
+    /// sweep rather than once per occurrence. Returns the shared subtrees and the rest of the
+    /// expression, in which they are replaced by the variables `$0`, `$1` and so on. Each shared
+    /// subtree only refers to the ones before it, so `evaluate_cse` evaluates them in order.
+    ///
+    /// Subtrees are equal if they have the same structure, with constants compared bit for bit.
+    /// Variables and constants aren't shared, since they cost no more to evaluate than a shared
+    /// subtree does to look up, and neither are calls to custom functions, which may not return
+    /// the same value every time, nor the parts of sums, which depend on the index.
+    pub fn cse(self) -> (Vec<ExpressionNode>, ExpressionNode) {
+        let mut numbering = Numbering::default();
+        let mut counts = Vec::new();
+        self.count_subtrees(&mut numbering, &mut counts, true);
+        let mut sharing = Sharing {
+            numbering,
+            counts,
+            shared_as: HashMap::new(),
+            shared: Vec::new(),
+        };
+        let (root, _) = self.share(&mut sharing);
+        (sharing.shared, root)
+    }
+
+    /// Numbers the subtrees of `self`, and if `counted`, counts how often each one that may be
+    /// shared occurs, by number. Returns the number of `self`.
+    fn count_subtrees(
+        &self,
+        numbering: &mut Numbering,
+        counts: &mut Vec<usize>,
+        counted: bool,
+    ) -> usize {
+        let is_series = matches!(
+            self,
+            ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Sum { .. } | NaryOperator::Product { .. },
+                ..
+            }
+        );
+        let children = self
+            .children()
+            .into_iter()
+            .map(|child| child.count_subtrees(numbering, counts, counted && !is_series))
+            .collect();
+        let number = numbering.number(self.shape(children));
+        let shareable = match self {
+            ExpressionNode::BinaryExprNode { .. }
+            | ExpressionNode::UnaryExprNode { .. }
+            | ExpressionNode::NaryExprNode { .. } => true,
+            ExpressionNode::FunctionCallNode { .. }
+            | ExpressionNode::VariableExprNode { .. }
+            | ExpressionNode::ConstantExprNode { .. } => false,
+        };
+        if counted && shareable {
+            if counts.len() <= number {
+                counts.resize(number + 1, 0);
+            }
+            counts[number] += 1;
+        }
+        number
+    }
+
+    /// What `self` is, apart from its children, which are given by their numbers.
+    fn shape(&self, children: Vec<usize>) -> Shape {
+        match self {
+            ExpressionNode::BinaryExprNode { operator, .. } => {
+                Shape::Binary(operator.clone(), children)
+            }
+            ExpressionNode::UnaryExprNode { operator, .. } => {
+                Shape::Unary(operator.clone(), children)
+            }
+            ExpressionNode::NaryExprNode { operator, .. } => {
+                Shape::Nary(operator.clone(), children)
+            }
+            ExpressionNode::FunctionCallNode { name, .. } => Shape::Call(name.clone(), children),
+            ExpressionNode::VariableExprNode { variable_key } => {
+                Shape::Variable(variable_key.clone())
+            }
+            ExpressionNode::ConstantExprNode { value } => Shape::Constant(value.to_bits()),
+        }
+    }
+
+    /// Replaces the subtrees of `self` that occur more than once with the variables they are
+    /// bound to, adding them to `sharing.shared` the first time they are seen. Returns the new
+    /// tree and the number of `self` as it was.
+    fn share(self, sharing: &mut Sharing) -> (ExpressionNode, usize) {
+        let mut share_all = |nodes: Vec<ExpressionNode>| -> (Vec<ExpressionNode>, Vec<usize>) {
+            nodes.into_iter().map(|node| node.share(sharing)).unzip()
+        };
+        let (node, children) = match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                let (mut nodes, children) = share_all(vec![*left_node, *right_node]);
+                let right_node = Box::new(nodes.pop().unwrap());
+                let left_node = Box::new(nodes.pop().unwrap());
+                let node = ExpressionNode::BinaryExprNode {
+                    operator,
+                    left_node,
+                    right_node,
+                };
+                (node, children)
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                let (mut nodes, children) = share_all(vec![*child_node]);
+                let node = ExpressionNode::UnaryExprNode {
+                    operator,
+                    child_node: Box::new(nodes.pop().unwrap()),
+                };
+                (node, children)
+            }
+            ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Log,
+                child_nodes,
+            } => {
+                let (child_nodes, children) = share_all(child_nodes);
+                let node = ExpressionNode::NaryExprNode {
+                    operator: NaryOperator::Log,
+                    child_nodes,
+                };
+                (node, children)
+            }
+            ExpressionNode::FunctionCallNode { name, args } => {
+                let (args, children) = share_all(args);
+                (ExpressionNode::FunctionCallNode { name, args }, children)
+            }
+            // Sums, variables and constants are left as they are.
+            node => {
+                let numbering = &mut sharing.numbering;
+                let children = node
+                    .children()
+                    .into_iter()
+                    .map(|child| child.count_subtrees(numbering, &mut Vec::new(), false))
+                    .collect();
+                (node, children)
+            }
+        };
+        let number = sharing.numbering.number(node.shape(children));
+        let index = match sharing.shared_as.get(&number) {
+            Some(&index) => index,
+            None if sharing.counts.get(number).is_some_and(|&count| count > 1) => {
+                sharing.shared.push(node);
+                sharing.shared_as.insert(number, sharing.shared.len() - 1);
+                sharing.shared.len() - 1
+            }
+            None => return (node, number),
+        };
+        let variable = ExpressionNode::VariableExprNode {
+            variable_key: shared_name(index),
+        };
+        (variable, number)
+    }
+
+    /// Hoists constants out of the subtrees, returning the new tree and whether it is a constant.
+    fn hoist(&self, ctx: &EvaluationContext) -> Result<(ExpressionNode, bool), EvaluationError> {
+        let (node, children_are_constant) = match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                let (left_node, left_is_constant) = left_node.hoist(ctx)?;
+                let (right_node, right_is_constant) = right_node.hoist(ctx)?;
+                let node = ExpressionNode::BinaryExprNode {
+                    operator: operator.clone(),
+                    left_node: Box::new(left_node),
+                    right_node: Box::new(right_node),
+                };
+                (node, left_is_constant && right_is_constant)
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                let (child_node, child_is_constant) = child_node.hoist(ctx)?;
+                let node = ExpressionNode::UnaryExprNode {
+                    operator: operator.clone(),
+                    child_node: Box::new(child_node),
+                };
+                (node, child_is_constant)
+            }
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => {
+                let mut all_constant = true;
+                let mut hoisted = Vec::with_capacity(child_nodes.len());
+                for child in child_nodes.iter() {
+                    let (child, is_constant) = child.hoist(ctx)?;
+                    all_constant &= is_constant;
+                    hoisted.push(child);
+                }
+                let node = ExpressionNode::NaryExprNode {
+                    operator: operator.clone(),
+                    child_nodes: hoisted,
+                };
+                (node, all_constant)
+            }
+            ExpressionNode::FunctionCallNode { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.hoist(ctx).map(|(arg, _)| arg))
+                    .collect::<Result<_, _>>()?;
+                let node = ExpressionNode::FunctionCallNode {
+                    name: name.clone(),
+                    args,
+                };
+                return Ok((node, false));
+            }
+            ExpressionNode::VariableExprNode { .. } => return Ok((self.clone(), false)),
+            ExpressionNode::ConstantExprNode { .. } => return Ok((self.clone(), true)),
+        };
+
+        if children_are_constant {
+            // The children are constants by now, so this only applies a single operator.
+            let value = node.evaluate_ctx(ctx)?;
+            Ok((ExpressionNode::ConstantExprNode { value }, true))
+        } else {
+            Ok((node, false))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{asin, call, constant, cos, ln, sin, sum, var};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn expression() -> ExpressionNode {
+        sin(var("x")) + ln(constant(123_456.0)) * constant(std::f64::consts::PI).pow(constant(2.0))
+    }
+
+    #[test]
+    fn constant_subtrees_are_folded() {
+        let hoisted = expression().hoist_constants(&EvaluationContext::new()).unwrap();
+        let folded = 123_456f64.ln() * std::f64::consts::PI.powf(2.0);
+        assert_eq!(hoisted, sin(var("x")) + constant(folded));
+    }
+
+    #[test]
+    fn hoisting_preserves_results() {
+        let original = expression();
+        let hoisted = original.hoist_constants(&EvaluationContext::new()).unwrap();
+        assert_eq!(
+            evaluate_function_over_domain(-5.0, 5.0, 100, &original),
+            evaluate_function_over_domain(-5.0, 5.0, 100, &hoisted)
+        );
+    }
+
+    #[test]
+    fn errors_in_constant_subtrees_surface() {
+        let expression = var("x") + asin(constant(2.0));
+        let mut ctx = EvaluationContext::new();
+        ctx.strict = true;
+        assert_eq!(
+            expression.hoist_constants(&ctx),
+            Err(EvaluationError::NotANumberError)
+        );
+    }
+
+    #[test]
+    fn function_calls_are_not_folded() {
+        let random = call("random", vec![constant(1.0) + constant(2.0)]);
+        assert_eq!(
+            random.hoist_constants(&EvaluationContext::new()).unwrap(),
+            call("random", vec![constant(3.0)])
+        );
+    }
+
+    #[test]
+    fn partial_evaluation_matches_full_evaluation() {
+        let original = var("a") * sin(var("b") * var("x"));
+        let mut bound = HashMap::new();
+        bound.insert("a".to_string(), 2.0);
+        bound.insert("b".to_string(), 3.0);
+        let specialized = original.partial_evaluate(&bound);
+        assert_eq!(specialized, constant(2.0) * sin(constant(3.0) * var("x")));
+
+        for i in 0..=100 {
+            let x = -5.0 + i as f64 * 0.1;
+            let mut all = bound.clone();
+            all.insert("x".to_string(), x);
+            let mut only_x = HashMap::new();
+            only_x.insert("x".to_string(), x);
+            assert_eq!(specialized.evaluate(&only_x), original.evaluate(&all));
+        }
+    }
+
+    #[test]
+    fn partial_evaluation_leaves_unbound_variables_and_errors() {
+        let expression = ln(var("a")) + var("y");
+        let mut bound = HashMap::new();
+        bound.insert("a".to_string(), 1.0);
+        assert_eq!(expression.partial_evaluate(&bound), constant(0.0) + var("y"));
+        assert_eq!(expression.partial_evaluate(&HashMap::new()), expression);
+
+        bound.insert("a".to_string(), -1.0);
+        let mut y = HashMap::new();
+        y.insert("y".to_string(), 1.0);
+        assert_eq!(
+            expression.partial_evaluate(&bound).evaluate(&y),
+            Err(EvaluationError::DomainError)
+        );
+    }
+
+    /// The values of `expression`, evaluated naively and after `cse`, for `x` across `[-3, 3]`.
+    fn evaluate_both_ways(
+        expression: &ExpressionNode,
+    ) -> Vec<(Result<f64, EvaluationError>, Result<f64, EvaluationError>)> {
+        let (shared, root) = expression.clone().cse();
+        (0..=60)
+            .map(|i| {
+                let mut vars = HashMap::new();
+                vars.insert("x".to_string(), -3.0 + i as f64 * 0.1);
+                (expression.evaluate(&vars), evaluate_cse(&shared, &root, &vars))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn repeated_subtrees_are_shared() {
+        let sin_x = || sin(var("x"));
+        let expression = sin_x().pow(constant(2.0)) + sin_x() * cos(var("x"));
+        let (shared, root) = expression.clone().cse();
+        assert_eq!(shared, vec![sin_x()]);
+        assert_eq!(root, var("$0").pow(constant(2.0)) + var("$0") * cos(var("x")));
+
+        for (naive, shared) in evaluate_both_ways(&expression) {
+            assert_eq!(naive, shared);
+        }
+    }
+
+    #[test]
+    fn shared_subtrees_are_evaluated_once() {
+        // `tick` counts how often the subtree around it is evaluated.
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let mut ctx = EvaluationContext::new();
+        let counter = Arc::clone(&ticks);
+        ctx.register_function("tick", 1, move |args| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(args[0])
+        });
+        ctx.variables.insert("x".to_string(), 0.5);
+
+        let sin_x = || sin(call("tick", vec![var("x")]));
+        let expression = sin_x().pow(constant(2.0)) + sin_x() * cos(var("x"));
+        let naive = expression.evaluate_ctx(&ctx);
+        assert_eq!(ticks.swap(0, Ordering::Relaxed), 2);
+
+        let (shared, root) = expression.cse();
+        assert_eq!(evaluate_cse_ctx(&shared, &root, &ctx), naive);
+        assert_eq!(ticks.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn repetition_at_every_depth_is_shared() {
+        // x + 1, then (x + 1) * (x + 1), and so on, doubling the size of the tree 16 times.
+        let mut expression = var("x") + constant(1.0);
+        for _ in 0..16 {
+            expression = expression.clone() * expression;
+        }
+        let (shared, root) = expression.cse();
+        assert_eq!(shared.len(), 16);
+        assert_eq!(root, var("$15") * var("$15"));
+    }
+
+    #[test]
+    fn shared_subtrees_refer_to_earlier_ones() {
+        let cube = || (var("x") + constant(1.0)).pow(constant(3.0));
+        let square = || cube() * cube();
+        let expression = square() + ln(square());
+        let (shared, root) = expression.clone().cse();
+        assert_eq!(
+            shared,
+            vec![
+                var("x") + constant(1.0),
+                var("$0").pow(constant(3.0)),
+                var("$1") * var("$1"),
+            ]
+        );
+        assert_eq!(root, var("$2") + ln(var("$2")));
+        // ln of a negative number fails either way.
+        for (naive, shared) in evaluate_both_ways(&expression) {
+            assert_eq!(naive, shared);
+        }
+    }
+
+    #[test]
+    fn sum_bodies_and_calls_are_not_shared() {
+        // sum(k, 1, 3, sin(k*x) + sin(k*x)) + sum(k, 1, 3, sin(k*x) + sin(k*x)) + f(x) + f(x)
+        let series = || {
+            let term = || sin(var("k") * var("x"));
+            sum("k", constant(1.0), constant(3.0), term() + term())
+        };
+        let f = || call("f", vec![var("x")]);
+        let expression = series() + series() + f() + f();
+        let (shared, root) = expression.cse();
+        assert_eq!(shared, vec![series()]);
+        assert_eq!(root, var("$0") + var("$0") + f() + f());
+    }
+}
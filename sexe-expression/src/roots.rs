@@ -0,0 +1,197 @@
+//! Numeric root finding, e.g. for marking where a plotted function crosses zero.
+
+use super::*;
+
+/// How narrow `find_roots` makes the bracket around each root before settling on its midpoint.
+pub const DEFAULT_ROOT_TOLERANCE: f64 = 1e-12;
+
+/// Finds the values of `x` in the domain where `func` crosses zero, see `find_roots_with`.
+pub fn find_roots(start_x: f64, end_x: f64, resolution: u32, func: &ExpressionNode) -> Vec<f64> {
+    find_roots_with(
+        start_x,
+        end_x,
+        resolution,
+        func,
+        "x",
+        DEFAULT_ROOT_TOLERANCE,
+    )
+}
+
+/// Samples `func` over the domain like `evaluate_function_over_domain_var` and finds the values of
+/// `var_name` where it crosses zero, see `roots_between_samples`.
+pub fn find_roots_with(
+    start_x: f64,
+    end_x: f64,
+    resolution: u32,
+    func: &ExpressionNode,
+    var_name: &str,
+    tolerance: f64,
+) -> Vec<f64> {
+    // Linear spacing accepts any bounds.
+    let positions = sample_positions(start_x, end_x, resolution, Spacing::Linear).unwrap();
+    // Points that fail to evaluate are kept as NaN, so that no bracket spans them.
+    let samples: Vec<(f64, f64)> = positions
+        .iter()
+        .zip(func.evaluate_many(var_name, &positions))
+        .map(|(&x, y)| (x, y.unwrap_or(f64::NAN)))
+        .collect();
+    roots_between_samples(&samples, func, var_name, tolerance)
+}
+
+/// Finds the values of `var_name` where `func` crosses zero by looking for sign changes between
+/// consecutive `samples`, then narrowing each one down by bisection until the bracket is no wider
+/// than `tolerance`. Samples that are exactly zero are roots themselves, unless a neighboring
+/// sample is zero too: a function that stays at zero doesn't have isolated roots to mark. Pairs of
+/// samples that aren't both finite are skipped. The roots are sorted.
+///
+/// A pole, as in `1 / x`, changes sign too. It is told apart from a root because the function
+/// grows rather than shrinks towards it, so a bracket is only a root if the function ends up
+/// no farther from zero than at its samples.
+pub fn roots_between_samples(
+    samples: &[(f64, f64)],
+    func: &ExpressionNode,
+    var_name: &str,
+    tolerance: f64,
+) -> Vec<f64> {
+    let mut vars = HashMap::new();
+    let mut evaluate = |x: f64| {
+        vars.insert(var_name.to_string(), x);
+        func.evaluate(&vars).ok().filter(|y| y.is_finite())
+    };
+
+    let is_zero = |i: usize| samples.get(i).is_some_and(|&(_, y)| y == 0.0);
+    let mut roots: Vec<f64> = (0..samples.len())
+        .filter(|&i| is_zero(i) && !(i > 0 && is_zero(i - 1)) && !is_zero(i + 1))
+        .map(|i| samples[i].0)
+        .collect();
+    for pair in samples.windows(2) {
+        let ((mut a, mut y_a), (mut b, y_b)) = (pair[0], pair[1]);
+        if !(y_a.is_finite() && y_b.is_finite()) || y_a * y_b >= 0.0 {
+            continue;
+        }
+        let farthest_from_zero = y_a.abs().max(y_b.abs());
+        while (b - a).abs() > tolerance {
+            let mid = (a + b) / 2.0;
+            // The bracket can't be split any further in floating point.
+            if mid == a || mid == b {
+                break;
+            }
+            match evaluate(mid) {
+                Some(0.0) => {
+                    a = mid;
+                    b = mid;
+                }
+                Some(y) if y * y_a < 0.0 => b = mid,
+                Some(y) => {
+                    a = mid;
+                    y_a = y;
+                }
+                // The function is undefined somewhere inside the bracket, so it doesn't cross zero
+                // there as far as we can tell.
+                None => break,
+            }
+        }
+        let root = (a + b) / 2.0;
+        if evaluate(root).is_some_and(|y| y.abs() <= farthest_from_zero) {
+            roots.push(root);
+        }
+    }
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    roots
+}
+
+/// Finds the points in the domain where the graphs of `f` and `g` of `x` cross, as the roots of
+/// the tree of `f - g`. Where the two are equal over a stretch of samples, as identical
+/// expressions are everywhere, they don't cross at isolated points, so nothing is found there.
+pub fn find_intersections(
+    start_x: f64,
+    end_x: f64,
+    resolution: u32,
+    f: &ExpressionNode,
+    g: &ExpressionNode,
+) -> Vec<(f64, f64)> {
+    let difference = ExpressionNode::BinaryExprNode {
+        operator: BinaryOperator::Subtraction,
+        left_node: Box::new(f.clone()),
+        right_node: Box::new(g.clone()),
+    };
+    let mut vars = HashMap::new();
+    find_roots(start_x, end_x, resolution, &difference)
+        .into_iter()
+        .filter_map(|x| {
+            vars.insert("x".to_string(), x);
+            f.evaluate(&vars).ok().map(|y| (x, y))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn roots_of_sine_are_multiples_of_pi() {
+        let roots = find_roots(-10.0, 10.0, 1000, &sin(var("x")));
+
+        assert_eq!(roots.len(), 7);
+        for (root, k) in roots.iter().zip(-3..=3) {
+            let expected = f64::from(k) * PI;
+            assert!((root - expected).abs() < 1e-9, "{} != {}", root, expected);
+        }
+    }
+
+    #[test]
+    fn functions_without_roots_have_none() {
+        let func = var("x").pow(constant(2.0)) + constant(1.0);
+        assert_eq!(find_roots(-10.0, 10.0, 1000, &func), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn sampled_zeros_are_roots() {
+        let samples = vec![(-1.0, -1.0), (0.0, 0.0), (1.0, 1.0)];
+        assert_eq!(
+            roots_between_samples(&samples, &var("x"), "x", 1e-9),
+            vec![0.0]
+        );
+    }
+
+    #[test]
+    fn zero_stretches_have_no_roots() {
+        let samples = vec![(-1.0, 1.0), (0.0, 0.0), (1.0, 0.0), (2.0, 1.0), (3.0, 0.0)];
+        assert_eq!(
+            roots_between_samples(&samples, &var("x"), "x", 1e-9),
+            vec![3.0]
+        );
+    }
+
+    #[test]
+    fn intersections_are_found() {
+        let intersections = find_intersections(0.0, 2.0 * PI, 1000, &sin(var("x")), &cos(var("x")));
+        assert_eq!(intersections.len(), 2);
+        for (&(x, y), &expected_x) in intersections.iter().zip(&[0.25 * PI, 1.25 * PI]) {
+            assert!((x - expected_x).abs() < 1e-9, "{} != {}", x, expected_x);
+            assert!((y - expected_x.sin()).abs() < 1e-9);
+        }
+
+        let parabola = var("x").pow(constant(2.0));
+        assert_eq!(
+            find_intersections(-1.0, 1.0, 100, &parabola, &parabola),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn poles_and_gaps_are_not_roots() {
+        // 1/x changes sign between -1/3 and 1/3, but only by way of its pole.
+        let func = constant(1.0) / var("x");
+        assert_eq!(find_roots(-1.0, 1.0, 3, &func), Vec::<f64>::new());
+
+        // The samples left of zero are domain errors, which leaves a gap before the root.
+        let func = ln(var("x")) - constant(0.5);
+        let roots = find_roots(-2.0, 3.0, 10, &func);
+        assert_eq!(roots.len(), 1);
+        assert!((roots[0] - 0.5f64.exp()).abs() < 1e-9);
+    }
+}
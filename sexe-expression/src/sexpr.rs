@@ -0,0 +1,301 @@
+//! Conversion of expression trees to and from S-expressions, e.g. `(+ (* 3 x) (sin x))`.
+//!
+//! The heads of the lists are:
+//!
+//! * `+`, `-`, `*`, `/`, `%` and `^` for the binary operators, with two operands,
+//! * `neg` for negation, and the name of the function for the other unary operators, e.g. `sin`,
+//!   `asin`, `ctan`, `abs`, `log2`, `fract` or `gamma`, with one operand,
+//! * `log` for the logarithm, as `(log x base)`,
+//! * `sum` for sums, as `(sum k lower upper body)`, and `prod` for products likewise,
+//! * `call` for the functions of an `EvaluationContext`, as `(call name args...)`.
+//!
+//! Any other atom is a number if it parses as an `f64`, which includes `inf` and `NaN`, and a
+//! variable otherwise.
+
+use std::iter::Peekable;
+use std::vec;
+
+use super::*;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseError {
+    /// The input ended in the middle of an expression.
+    UnexpectedEnd,
+    /// A `)` appeared where an expression was expected, or anything appeared after a complete
+    /// expression.
+    UnexpectedToken { token: String },
+    /// A list started with a head that isn't one of the operators.
+    UnknownOperator { name: String },
+    /// An operator was given the wrong number of operands.
+    WrongNumberOfArgs { operator: String },
+    /// The head of a list, the index of a sum or product or the name of a call wasn't an atom.
+    ExpectedName,
+}
+
+impl ExpressionNode {
+    /// Writes the expression as an S-expression, with the operator names described in the
+    /// module documentation.
+    pub fn to_sexpr(&self) -> String {
+        match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => format!("({} {} {})", operator.symbol(), left_node.to_sexpr(), right_node.to_sexpr()),
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => format!("({} {})", unary_head(operator), child_node.to_sexpr()),
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => match operator {
+                NaryOperator::Log => sexpr_list("log", child_nodes),
+                NaryOperator::Sum { index_key } => sexpr_list(&format!("sum {}", index_key), child_nodes),
+                NaryOperator::Product { index_key } => sexpr_list(&format!("prod {}", index_key), child_nodes),
+            },
+            ExpressionNode::FunctionCallNode { name, args } => {
+                sexpr_list(&format!("call {}", name), args)
+            }
+            ExpressionNode::VariableExprNode { variable_key } => variable_key.clone(),
+            ExpressionNode::ConstantExprNode { value } => format!("{}", value),
+        }
+    }
+
+    /// Reads an expression written as an S-expression, such as the ones of `to_sexpr`.
+    pub fn from_sexpr(input: &str) -> Result<ExpressionNode, ParseError> {
+        let mut tokens = tokenize(input).into_iter().peekable();
+        let expression = read_expression(&mut tokens)?;
+        match tokens.next() {
+            None => Ok(expression),
+            Some(token) => Err(ParseError::UnexpectedToken {
+                token: token.to_string(),
+            }),
+        }
+    }
+}
+
+fn unary_head(operator: &UnaryOperator) -> &'static str {
+    match operator {
+        UnaryOperator::Negation => "neg",
+        _ => operator.name(),
+    }
+}
+
+fn sexpr_list(head: &str, nodes: &[ExpressionNode]) -> String {
+    let mut list = format!("({}", head);
+    for node in nodes {
+        list += " ";
+        list += &node.to_sexpr();
+    }
+    list + ")"
+}
+
+/// Splits the input into parentheses and the atoms between them.
+fn tokenize(input: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut atom_start = None;
+    for (i, c) in input.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(start) = atom_start.take() {
+                tokens.push(&input[start..i]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&input[i..i + 1]);
+            }
+        } else if atom_start.is_none() {
+            atom_start = Some(i);
+        }
+    }
+    if let Some(start) = atom_start {
+        tokens.push(&input[start..]);
+    }
+    tokens
+}
+
+type Tokens<'a> = Peekable<vec::IntoIter<&'a str>>;
+
+fn read_expression(tokens: &mut Tokens) -> Result<ExpressionNode, ParseError> {
+    match tokens.next() {
+        None => Err(ParseError::UnexpectedEnd),
+        Some("(") => read_list(tokens),
+        Some(")") => Err(ParseError::UnexpectedToken {
+            token: ")".to_string(),
+        }),
+        Some(atom) => Ok(match atom.parse::<f64>() {
+            Ok(value) => ExpressionNode::ConstantExprNode { value },
+            Err(_) => ExpressionNode::VariableExprNode {
+                variable_key: atom.to_string(),
+            },
+        }),
+    }
+}
+
+/// Reads the rest of a list whose `(` has been read.
+fn read_list(tokens: &mut Tokens) -> Result<ExpressionNode, ParseError> {
+    let head = read_name(tokens)?;
+    let name = match head.as_str() {
+        "sum" | "prod" | "call" => Some(read_name(tokens)?),
+        _ => None,
+    };
+
+    let mut operands = Vec::new();
+    while tokens.peek() != Some(&")") {
+        operands.push(read_expression(tokens)?);
+    }
+    tokens.next();
+
+    let operator = match head.as_str() {
+        "+" => BinaryOperator::Addition,
+        "-" => BinaryOperator::Subtraction,
+        "*" => BinaryOperator::Multiplication,
+        "/" => BinaryOperator::Division,
+        "%" => BinaryOperator::Modulo,
+        "^" => BinaryOperator::Exponentiation,
+        "log" if operands.len() == 2 => {
+            return Ok(ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Log,
+                child_nodes: operands,
+            })
+        }
+        "sum" if operands.len() == 3 => {
+            return Ok(ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Sum {
+                    index_key: name.unwrap(),
+                },
+                child_nodes: operands,
+            })
+        }
+        "prod" if operands.len() == 3 => {
+            return Ok(ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Product {
+                    index_key: name.unwrap(),
+                },
+                child_nodes: operands,
+            })
+        }
+        "call" => {
+            return Ok(ExpressionNode::FunctionCallNode {
+                name: name.unwrap(),
+                args: operands,
+            })
+        }
+        "log" | "sum" | "prod" => return Err(ParseError::WrongNumberOfArgs { operator: head }),
+        _ => {
+            let operator = UnaryOperator::ALL
+                .iter()
+                .find(|operator| unary_head(operator) == head)
+                .ok_or_else(|| ParseError::UnknownOperator { name: head.clone() })?;
+            if operands.len() != 1 {
+                return Err(ParseError::WrongNumberOfArgs { operator: head });
+            }
+            return Ok(ExpressionNode::UnaryExprNode {
+                operator: operator.clone(),
+                child_node: Box::new(operands.pop().unwrap()),
+            });
+        }
+    };
+    if operands.len() != 2 {
+        return Err(ParseError::WrongNumberOfArgs { operator: head });
+    }
+    let right_node = Box::new(operands.pop().unwrap());
+    let left_node = Box::new(operands.pop().unwrap());
+    Ok(ExpressionNode::BinaryExprNode {
+        operator,
+        left_node,
+        right_node,
+    })
+}
+
+/// Reads an atom that names something, rather than an expression.
+fn read_name(tokens: &mut Tokens) -> Result<String, ParseError> {
+    match tokens.next() {
+        None => Err(ParseError::UnexpectedEnd),
+        Some("(") | Some(")") => Err(ParseError::ExpectedName),
+        Some(name) => Ok(name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{call, constant, log, log10, product, sin, sum, var};
+
+    fn assert_round_trip(expression: ExpressionNode, sexpr: &str) {
+        assert_eq!(expression.to_sexpr(), sexpr);
+        assert_eq!(ExpressionNode::from_sexpr(sexpr), Ok(expression));
+    }
+
+    #[test]
+    fn binary_operators_round_trip() {
+        assert_round_trip(constant(3.0) * var("x") + sin(var("x")), "(+ (* 3 x) (sin x))");
+        assert_round_trip(
+            var("x") / constant(-0.5) - var("y").pow(constant(2.0)),
+            "(- (/ x -0.5) (^ y 2))",
+        );
+    }
+
+    #[test]
+    fn unary_operators_round_trip() {
+        for operator in UnaryOperator::ALL.iter() {
+            let expression = ExpressionNode::unary(operator.clone(), var("x"));
+            let sexpr = expression.to_sexpr();
+            assert_eq!(ExpressionNode::from_sexpr(&sexpr), Ok(expression));
+        }
+        assert_round_trip(-var("x"), "(neg x)");
+        assert_round_trip(log10(constant(100.0)), "(log10 100)");
+    }
+
+    #[test]
+    fn nary_operators_and_calls_round_trip() {
+        assert_round_trip(log(constant(2.0), var("x")), "(log x 2)");
+        assert_round_trip(
+            sum("k", constant(1.0), var("n"), var("k") * var("k")),
+            "(sum k 1 n (* k k))",
+        );
+        assert_round_trip(product("k", constant(1.0), var("n"), var("k")), "(prod k 1 n k)");
+        assert_round_trip(
+            call("clamp", vec![var("x"), constant(0.0), constant(1.0)]),
+            "(call clamp x 0 1)",
+        );
+    }
+
+    #[test]
+    fn whitespace_is_insignificant() {
+        assert_eq!(
+            ExpressionNode::from_sexpr("  (+\n\tx(neg 1))  "),
+            Ok(var("x") + -constant(1.0))
+        );
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        assert_eq!(
+            ExpressionNode::from_sexpr("(frobnicate x)"),
+            Err(ParseError::UnknownOperator {
+                name: "frobnicate".to_string()
+            })
+        );
+        assert_eq!(
+            ExpressionNode::from_sexpr("(+ x)"),
+            Err(ParseError::WrongNumberOfArgs {
+                operator: "+".to_string()
+            })
+        );
+        assert_eq!(
+            ExpressionNode::from_sexpr("(sin x y)"),
+            Err(ParseError::WrongNumberOfArgs {
+                operator: "sin".to_string()
+            })
+        );
+        assert_eq!(ExpressionNode::from_sexpr("(+ x 1"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(ExpressionNode::from_sexpr(""), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            ExpressionNode::from_sexpr("x)"),
+            Err(ParseError::UnexpectedToken {
+                token: ")".to_string()
+            })
+        );
+        assert_eq!(ExpressionNode::from_sexpr("((sin x) 1)"), Err(ParseError::ExpectedName));
+    }
+}
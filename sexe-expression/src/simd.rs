@@ -0,0 +1,213 @@
+//! Evaluation of compiled expressions at several values of their variable at once, for sweeps
+//! over many points.
+
+use super::*;
+use compile::Instruction;
+
+/// How many values are evaluated together. Each instruction is applied to all of them in a loop
+/// without branches for the arithmetic operators, which the compiler turns into vector
+/// instructions.
+const LANES: usize = 4;
+
+type Lanes = [f64; LANES];
+
+impl CompiledExpression {
+    /// Evaluates the expression at every value of `xs` of its only variable, writing the results
+    /// to `out`, `LANES` values at a time. Points at which `evaluate` would fail, e.g. `ln(0)`,
+    /// are NaN. The results are exactly those of `evaluate`, since every lane computes the same
+    /// operations in the same order; the operators without vector instructions, such as `sin`,
+    /// are applied one lane at a time. Expressions with sums or custom functions, and the values
+    /// left over after the last full group, are evaluated one at a time.
+    ///
+    /// Fails with `VariableNotFoundError` if the expression has more than one variable, other
+    /// than the indices of sums.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `xs` and `out` differ in length.
+    pub fn eval_simd(&self, xs: &[f64], out: &mut [f64]) -> Result<(), EvaluationError> {
+        assert_eq!(xs.len(), out.len(), "every value needs room for its result");
+        let sweep = match self.free[..] {
+            [] => None,
+            [symbol] => Some(symbol),
+            _ => return Err(EvaluationError::VariableNotFoundError),
+        };
+
+        let vectorizable = !self
+            .program
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::Sum { .. } | Instruction::Call(..)));
+        let vectorized = if vectorizable { xs.len() - xs.len() % LANES } else { 0 };
+        let mut stack = Vec::new();
+        for (xs, out) in xs[..vectorized]
+            .chunks_exact(LANES)
+            .zip(out[..vectorized].chunks_exact_mut(LANES))
+        {
+            let mut lanes = [0.0; LANES];
+            lanes.copy_from_slice(xs);
+            out.copy_from_slice(&run_lanes(&self.program, &lanes, &mut stack));
+        }
+
+        let ctx = EvaluationContext::new();
+        let mut values = vec![0.0; self.symbols.len()];
+        let mut stack = Vec::new();
+        for (&x, out) in xs[vectorized..].iter().zip(&mut out[vectorized..]) {
+            if let Some(symbol) = sweep {
+                values[symbol.index()] = x;
+            }
+            *out = self.evaluate_on(&values, &mut stack, &ctx).unwrap_or(f64::NAN);
+        }
+        Ok(())
+    }
+}
+
+/// Runs `program`, which has no sums or calls, for every lane of `xs`, the values of its only
+/// variable. Lanes in which an operator fails are NaN.
+fn run_lanes(program: &[Instruction], xs: &Lanes, stack: &mut Vec<Lanes>) -> Lanes {
+    stack.clear();
+    let mut failed = [false; LANES];
+    // Applies `operator` to each lane, marking the lanes in which it fails.
+    let mut checked = |lane: usize, result: Result<f64, EvaluationError>| {
+        result.unwrap_or_else(|_| {
+            failed[lane] = true;
+            f64::NAN
+        })
+    };
+    for instruction in program {
+        let mut result = [0.0; LANES];
+        match instruction {
+            Instruction::Constant(value) => result = [*value; LANES],
+            Instruction::Variable(_) => result = *xs,
+            Instruction::Unary(operator) => {
+                let child = stack.pop().unwrap();
+                match operator {
+                    UnaryOperator::Negation => {
+                        for i in 0..LANES {
+                            result[i] = -child[i];
+                        }
+                    }
+                    UnaryOperator::Abs => {
+                        for i in 0..LANES {
+                            result[i] = child[i].abs();
+                        }
+                    }
+                    _ => {
+                        for i in 0..LANES {
+                            result[i] = checked(i, operator.apply(child[i], AngleMode::Radians));
+                        }
+                    }
+                }
+            }
+            Instruction::Binary(operator) => {
+                let right = stack.pop().unwrap();
+                let left = stack.pop().unwrap();
+                match operator {
+                    BinaryOperator::Addition => {
+                        for i in 0..LANES {
+                            result[i] = left[i] + right[i];
+                        }
+                    }
+                    BinaryOperator::Subtraction => {
+                        for i in 0..LANES {
+                            result[i] = left[i] - right[i];
+                        }
+                    }
+                    BinaryOperator::Multiplication => {
+                        for i in 0..LANES {
+                            result[i] = left[i] * right[i];
+                        }
+                    }
+                    BinaryOperator::Division => {
+                        for i in 0..LANES {
+                            result[i] = left[i] / right[i];
+                        }
+                    }
+                    BinaryOperator::Modulo | BinaryOperator::Exponentiation => {
+                        for i in 0..LANES {
+                            result[i] = operator.apply(left[i], right[i]);
+                        }
+                    }
+                }
+            }
+            Instruction::Power(power) => {
+                let base = stack.pop().unwrap();
+                for i in 0..LANES {
+                    result[i] = power.apply(base[i]);
+                }
+            }
+            Instruction::Nary(operator, count) => {
+                let operands = stack.split_off(stack.len() - count);
+                for (i, result) in result.iter_mut().enumerate() {
+                    let values: Vec<f64> = operands.iter().map(|operand| operand[i]).collect();
+                    *result = checked(i, operator.apply(&values));
+                }
+            }
+            Instruction::Sum { .. } | Instruction::Call(..) => {
+                unreachable!("sums and calls are evaluated one value at a time")
+            }
+        }
+        stack.push(result);
+    }
+    let mut result = stack.pop().unwrap();
+    for i in 0..LANES {
+        if failed[i] {
+            result[i] = f64::NAN;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::*;
+
+    /// The values of `expression` at `xs` one at a time, with NaN where evaluating fails.
+    fn scalar(expression: &ExpressionNode, xs: &[f64]) -> Vec<f64> {
+        let compiled = expression.compile();
+        xs.iter()
+            .map(|&x| {
+                let mut vars = HashMap::new();
+                vars.insert("x".to_string(), x);
+                compiled.evaluate_map(&vars).unwrap_or(f64::NAN)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batches_agree_with_scalar_evaluation() {
+        let expressions = vec![
+            constant(3.0) * var("x").pow(constant(3.0)) - constant(2.0) * var("x") + constant(1.0),
+            -abs(var("x")) / (var("x") - constant(0.5)) % constant(2.0),
+            sin(var("x")) * exp(var("x") / constant(3.0)) + var("x").pow(var("x")),
+            ln(var("x")) + log(constant(2.0), var("x")),
+            gamma(var("x")) + erf(var("x")),
+            sum("k", constant(1.0), constant(3.0), var("k") * var("x")),
+            constant(7.0),
+        ];
+        // Every remainder after the full groups of lanes.
+        for len in 0..=2 * LANES + 3 {
+            let xs: Vec<f64> = (0..len).map(|i| i as f64 * 0.75 - 2.0).collect();
+            for expression in &expressions {
+                let mut out = vec![0.0; len];
+                expression.compile().eval_simd(&xs, &mut out).unwrap();
+                let expected = scalar(expression, &xs);
+                let bits = |values: &[f64]| values.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+                assert_eq!(bits(&out), bits(&expected), "{} at {:?}", expression, xs);
+            }
+        }
+    }
+
+    #[test]
+    fn only_one_variable_is_swept() {
+        let mut out = [0.0; 4];
+        assert_eq!(
+            (var("x") * var("y")).compile().eval_simd(&[1.0; 4], &mut out),
+            Err(EvaluationError::VariableNotFoundError)
+        );
+        // The index of a sum isn't a variable to sweep.
+        let expression = sum("k", constant(1.0), var("n"), var("k"));
+        assert_eq!(expression.compile().eval_simd(&[1.0, 2.0, 3.0, 4.0], &mut out), Ok(()));
+        assert_eq!(out, [1.0, 3.0, 6.0, 10.0]);
+    }
+}
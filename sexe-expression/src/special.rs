@@ -0,0 +1,57 @@
+//! Special functions that are missing from `std`.
+
+use std::f64::consts::PI;
+
+/// The error function, computed with the rational approximation 7.1.26 from Abramowitz and
+/// Stegun. The absolute error is at most 1.5e-7 across the whole real line, which is plenty for
+/// plotting.
+pub fn erf(x: f64) -> f64 {
+    const P: f64 = 0.327_591_1;
+    const A: [f64; 5] = [
+        0.254_829_592,
+        -0.284_496_736,
+        1.421_413_741,
+        -1.453_152_027,
+        1.061_405_429,
+    ];
+
+    // The approximation is only valid for x >= 0, but erf is odd.
+    let sign = x.signum();
+    let x = x.abs();
+
+    let t = 1.0 / (1.0 + P * x);
+    let polynomial = A.iter().rev().fold(0.0, |acc, a| (acc + a) * t);
+    sign * (1.0 - polynomial * (-x * x).exp())
+}
+
+/// The gamma function, computed with the Lanczos approximation (g = 7, n = 9). The relative error
+/// is around 1e-15 for positive arguments. Arguments below 1/2 go through the reflection formula,
+/// and the poles at zero and the negative integers evaluate to NaN.
+pub fn gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x <= 0.0 && x == x.floor() {
+        f64::NAN
+    } else if x < 0.5 {
+        PI / ((PI * x).sin() * gamma(1.0 - x))
+    } else {
+        let x = x - 1.0;
+        let sum = COEFFICIENTS[1..]
+            .iter()
+            .enumerate()
+            .fold(COEFFICIENTS[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.0));
+        let t = x + G + 0.5;
+        (2.0 * PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * sum
+    }
+}
@@ -0,0 +1,109 @@
+//! Variables interned as small integers, so that evaluating an expression doesn't have to hash
+//! their names.
+
+use super::*;
+
+/// A variable, identified by its position in a `SymbolTable`. A compiled expression reads the
+/// value of a variable from the slice it is evaluated against, at the index of its symbol.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct Symbol(pub u32);
+
+impl Symbol {
+    /// The position of this symbol's value in a slice of variable values.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Maps the names of variables to symbols and back. Symbols are handed out in the order the names
+/// are first interned, starting at `Symbol(0)`.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct SymbolTable {
+    names: Vec<String>,
+    symbols: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// A table of every variable name in `expression`, including the indices of sums, in the
+    /// order they first appear.
+    pub fn from_expression(expression: &ExpressionNode) -> SymbolTable {
+        let mut table = SymbolTable::new();
+        table.intern_all(expression);
+        table
+    }
+
+    fn intern_all(&mut self, node: &ExpressionNode) {
+        match node {
+            ExpressionNode::BinaryExprNode {
+                left_node,
+                right_node,
+                ..
+            } => {
+                self.intern_all(left_node);
+                self.intern_all(right_node);
+            }
+            ExpressionNode::UnaryExprNode { child_node, .. } => self.intern_all(child_node),
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => {
+                if let Some(index_key) = operator.index_key() {
+                    self.intern(index_key);
+                }
+                for node in child_nodes.iter() {
+                    self.intern_all(node);
+                }
+            }
+            ExpressionNode::FunctionCallNode { args, .. } => {
+                for node in args {
+                    self.intern_all(node);
+                }
+            }
+            ExpressionNode::VariableExprNode { variable_key } => {
+                self.intern(variable_key);
+            }
+            ExpressionNode::ConstantExprNode { .. } => (),
+        }
+    }
+
+    /// The symbol of `name`, which is added to the table if it isn't in it yet.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.symbols.insert(name.to_string(), symbol);
+        symbol
+    }
+
+    /// The symbol of `name`, if it has been interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).cloned()
+    }
+
+    /// The name `symbol` was interned for. Panics if `symbol` is from another table.
+    pub fn name(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Every symbol with its name, in the order of the symbols.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (Symbol(i as u32), name.as_str()))
+    }
+}
@@ -0,0 +1,118 @@
+//! Polynomial approximations of expressions by their Taylor series.
+
+use super::*;
+use builder::*;
+
+/// The Taylor polynomial of `func` in `var` up to the `order`th power, expanded around `around`,
+/// as an expression that can be plotted alongside `func`. Its coefficients are the successive
+/// symbolic derivatives of `func` evaluated at `around`, so e.g. the 3rd order polynomial of
+/// `sin(x)` around 0 is `x - 0.16666666666666666 * x ^ 3`. Terms with a coefficient of zero are
+/// left out.
+///
+/// If one of the derivatives is undefined or not finite at `around`, the expansion is a
+/// `DomainError`. Errors from differentiating or evaluating `func` are passed on.
+pub fn taylor(
+    func: &ExpressionNode,
+    var: &str,
+    around: f64,
+    order: usize,
+) -> Result<ExpressionNode, EvaluationError> {
+    let mut vars = HashMap::new();
+    vars.insert(var.to_string(), around);
+    let offset = if around == 0.0 {
+        builder::var(var)
+    } else {
+        builder::var(var) - constant(around)
+    };
+
+    let mut derivative = func.clone();
+    let mut factorial = 1.0;
+    let mut polynomial: Option<ExpressionNode> = None;
+    for n in 0..=order {
+        if n > 0 {
+            // Folding the constants keeps the higher derivatives from growing needlessly.
+            derivative = derivative
+                .differentiate(var)?
+                .partial_evaluate(&HashMap::new());
+            factorial *= n as f64;
+        }
+        let coefficient = match derivative.evaluate(&vars)? {
+            value if value.is_finite() => value / factorial,
+            _ => return Err(EvaluationError::DomainError),
+        };
+        if coefficient == 0.0 {
+            continue;
+        }
+        // Negative terms after the first are subtracted, e.g. `x - 0.5 * x ^ 2`.
+        let subtract = coefficient < 0.0 && polynomial.is_some();
+        let magnitude = if subtract { -coefficient } else { coefficient };
+        let term = match n {
+            0 => constant(magnitude),
+            1 if magnitude == 1.0 => offset.clone(),
+            1 => constant(magnitude) * offset.clone(),
+            _ => constant(magnitude) * offset.clone().pow(constant(n as f64)),
+        };
+        polynomial = Some(match polynomial {
+            Some(polynomial) if subtract => polynomial - term,
+            Some(polynomial) => polynomial + term,
+            None => term,
+        });
+    }
+    Ok(polynomial.unwrap_or_else(|| constant(0.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maclaurin_series_of_sine() {
+        let polynomial = taylor(&sin(var("x")), "x", 0.0, 5).unwrap();
+        assert_eq!(
+            polynomial.to_string(),
+            "x - 0.16666666666666666 * x ^ 3 + 0.008333333333333333 * x ^ 5"
+        );
+
+        let mut vars = HashMap::new();
+        vars.insert("x".to_string(), 0.5);
+        let value = polynomial.evaluate(&vars).unwrap();
+        assert!((value - 0.5f64.sin()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn taylor_series_around_a_point() {
+        // The expansion of a polynomial of the same order is exact.
+        let cubic = var("t").pow(constant(3.0)) - constant(2.0) * var("t");
+        let polynomial = taylor(&cubic, "t", 1.5, 3).unwrap();
+        for &t in &[-2.0, 0.0, 1.5, 4.0] {
+            let mut vars = HashMap::new();
+            vars.insert("t".to_string(), t);
+            let expected = cubic.evaluate(&vars).unwrap();
+            assert!((polynomial.evaluate(&vars).unwrap() - expected).abs() < 1e-9);
+        }
+
+        assert_eq!(
+            taylor(&exp(var("x")), "x", 1.0, 0),
+            Ok(constant(std::f64::consts::E))
+        );
+        assert_eq!(taylor(&constant(0.0), "x", 1.0, 3), Ok(constant(0.0)));
+    }
+
+    #[test]
+    fn undefined_derivatives_are_errors() {
+        // The derivative of sqrt(x) is infinite at 0.
+        let root = var("x").pow(constant(0.5));
+        assert_eq!(
+            taylor(&root, "x", 0.0, 2),
+            Err(EvaluationError::DomainError)
+        );
+        assert_eq!(
+            taylor(&ln(var("x")), "x", 0.0, 2),
+            Err(EvaluationError::DomainError)
+        );
+        assert_eq!(
+            taylor(&gamma(var("x")), "x", 1.0, 1),
+            Err(EvaluationError::NotDifferentiable)
+        );
+    }
+}
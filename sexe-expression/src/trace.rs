@@ -0,0 +1,261 @@
+//! Evaluation that records the value of every subtree, for finding out where an expression that
+//! plots wrong values goes astray.
+
+use super::*;
+
+/// The values of the nodes of an expression, as computed by `evaluate_traced`. Nodes are
+/// identified by their index in pre-order, like the ids of `to_dot`, so the root is node 0 and
+/// its leftmost child node 1.
+///
+/// Nodes that weren't evaluated have no value. These are the nodes after the one whose error
+/// stopped the evaluation, and the bounds and body of sums, whose body is evaluated once for every
+/// index.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct EvalTrace {
+    values: Vec<Option<f64>>,
+}
+
+impl EvalTrace {
+    /// The value of the node with the pre-order index `index`, if it was evaluated.
+    pub fn value(&self, index: usize) -> Option<f64> {
+        self.values.get(index).cloned().flatten()
+    }
+
+    /// The number of nodes that were evaluated.
+    pub fn len(&self) -> usize {
+        self.values.iter().filter(|value| value.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every evaluated node with its value, in pre-order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(index, value)| value.map(|value| (index, value)))
+    }
+
+    /// Writes `expression`, which must be the expression this trace is of, with the value of every
+    /// evaluated node after it, e.g. `(sin(x{=3}){=0.1411200080598672} + 2{=2}){=2.14...}` for
+    /// `sin(x) + 2` at `x = 3`. Binary operators and negations are always parenthesized, so that it is clear which node a
+    /// value belongs to.
+    pub fn render(&self, expression: &ExpressionNode) -> String {
+        let mut out = String::new();
+        self.render_node(expression, &mut 0, &mut out);
+        out
+    }
+
+    fn render_node(&self, node: &ExpressionNode, next_index: &mut usize, out: &mut String) {
+        let index = *next_index;
+        *next_index += 1;
+        match node {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                out.push('(');
+                self.render_node(left_node, next_index, out);
+                out.push_str(&format!(" {} ", operator.symbol()));
+                self.render_node(right_node, next_index, out);
+                out.push(')');
+            }
+            ExpressionNode::UnaryExprNode {
+                operator: UnaryOperator::Negation,
+                child_node,
+            } => {
+                out.push_str("(-");
+                self.render_node(child_node, next_index, out);
+                out.push(')');
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => {
+                out.push_str(operator.name());
+                out.push('(');
+                self.render_node(child_node, next_index, out);
+                out.push(')');
+            }
+            // Nothing inside of a sum or product is traced.
+            ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Sum { .. } | NaryOperator::Product { .. },
+                ..
+            } => {
+                out.push_str(&node.to_string());
+                *next_index += node.node_count() - 1;
+            }
+            ExpressionNode::NaryExprNode {
+                child_nodes: args, ..
+            }
+            | ExpressionNode::FunctionCallNode { args, .. } => {
+                let name = match node {
+                    ExpressionNode::FunctionCallNode { name, .. } => name.as_str(),
+                    _ => "log",
+                };
+                out.push_str(name);
+                out.push('(');
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.render_node(arg, next_index, out);
+                }
+                out.push(')');
+            }
+            ExpressionNode::VariableExprNode { .. } | ExpressionNode::ConstantExprNode { .. } => {
+                out.push_str(&node.to_string())
+            }
+        }
+        if let Some(value) = self.value(index) {
+            out.push_str(&format!("{{={}}}", value));
+        }
+    }
+
+    fn record(&mut self, index: usize, value: f64) {
+        if self.values.len() <= index {
+            self.values.resize(index + 1, None);
+        }
+        self.values[index] = Some(value);
+    }
+}
+
+impl ExpressionNode {
+    /// Evaluates the expression like `evaluate`, recording the value of every subtree on the way.
+    /// If the evaluation fails, the trace holds the values computed up to the error.
+    pub fn evaluate_traced(
+        &self,
+        vars: &HashMap<String, f64>,
+    ) -> (Result<f64, EvaluationError>, EvalTrace) {
+        let mut trace = EvalTrace::default();
+        let result = self.evaluate_traced_node(vars, &EvaluationContext::new(), &mut 0, &mut trace);
+        (result, trace)
+    }
+
+    fn evaluate_traced_node(
+        &self,
+        vars: &HashMap<String, f64>,
+        ctx: &EvaluationContext,
+        next_index: &mut usize,
+        trace: &mut EvalTrace,
+    ) -> Result<f64, EvaluationError> {
+        let index = *next_index;
+        *next_index += 1;
+        let mut traced = |node: &ExpressionNode, trace: &mut EvalTrace| {
+            node.evaluate_traced_node(vars, ctx, next_index, trace)
+        };
+        let value = match self {
+            ExpressionNode::BinaryExprNode {
+                operator,
+                left_node,
+                right_node,
+            } => {
+                let left_value = traced(left_node, trace)?;
+                let right_value = traced(right_node, trace)?;
+                operator.apply(left_value, right_value)
+            }
+            ExpressionNode::UnaryExprNode {
+                operator,
+                child_node,
+            } => operator.apply(traced(child_node, trace)?, ctx.angle_mode)?,
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } if operator.index_key().is_some() => {
+                *next_index += self.node_count() - 1;
+                evaluate_series(operator, child_nodes, vars, ctx)?
+            }
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes,
+            } => {
+                let child_values = child_nodes
+                    .iter()
+                    .map(|node| traced(node, trace))
+                    .collect::<Result<Vec<_>, _>>()?;
+                operator.apply(&child_values)?
+            }
+            ExpressionNode::FunctionCallNode { name, args } => {
+                let arg_values = args
+                    .iter()
+                    .map(|node| traced(node, trace))
+                    .collect::<Result<Vec<_>, _>>()?;
+                ctx.call_function(name, &arg_values)?
+            }
+            ExpressionNode::VariableExprNode { variable_key } => match vars.get(variable_key) {
+                Some(value) => *value,
+                None => return Err(EvaluationError::VariableNotFoundError),
+            },
+            ExpressionNode::ConstantExprNode { value } => *value,
+        };
+        trace.record(index, value);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::*;
+
+    #[test]
+    fn every_node_is_traced() {
+        let expression = sin(var("x")) + constant(2.0);
+        let mut vars = HashMap::new();
+        vars.insert("x".to_string(), 3.0);
+        let (result, trace) = expression.evaluate_traced(&vars);
+
+        assert_eq!(result, expression.evaluate(&vars));
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace.value(0), Some(3f64.sin() + 2.0));
+        assert_eq!(trace.value(1), Some(3f64.sin()));
+        assert_eq!(trace.value(2), Some(3.0));
+        assert_eq!(trace.value(3), Some(2.0));
+        assert_eq!(
+            trace.render(&expression),
+            "(sin(x{=3}){=0.1411200080598672} + 2{=2}){=2.1411200080598674}"
+        );
+    }
+
+    #[test]
+    fn errors_stop_the_trace() {
+        // x * ln(x - 5) + log(8, 2)
+        let expression =
+            var("x") * ln(var("x") - constant(5.0)) + log(constant(2.0), constant(8.0));
+        let mut vars = HashMap::new();
+        vars.insert("x".to_string(), 3.0);
+        let (result, trace) = expression.evaluate_traced(&vars);
+
+        assert_eq!(result, Err(EvaluationError::DomainError));
+        assert_eq!(
+            trace.iter().collect::<Vec<_>>(),
+            vec![(2, 3.0), (4, -2.0), (5, 3.0), (6, 5.0)]
+        );
+        assert_eq!(
+            trace.render(&expression),
+            "((x{=3} * ln((x{=3} - 5{=5}){=-2})) + log(8, 2))"
+        );
+    }
+
+    #[test]
+    fn sums_are_traced_as_a_whole() {
+        // sum(k, 1, 3, k * x) + x
+        let expression = sum("k", constant(1.0), constant(3.0), var("k") * var("x")) + var("x");
+        let mut vars = HashMap::new();
+        vars.insert("x".to_string(), 2.0);
+        let (result, trace) = expression.evaluate_traced(&vars);
+
+        assert_eq!(result, Ok(14.0));
+        assert_eq!(
+            trace.iter().collect::<Vec<_>>(),
+            vec![(0, 14.0), (1, 12.0), (7, 2.0)]
+        );
+        assert_eq!(
+            trace.render(&expression),
+            "(sum(k, 1, 3, k * x){=12} + x{=2}){=14}"
+        );
+    }
+}
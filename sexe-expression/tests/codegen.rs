@@ -0,0 +1,100 @@
+//! Checks that the Rust code generated by `to_rust_fn` compiles and computes the same values as
+//! `evaluate`. The generated functions are kept in `codegen/generated.rs`, which is compiled into
+//! this test, and compared with the output of `to_rust_fn` so that it can't go stale.
+
+extern crate sexe_expression;
+
+use std::collections::HashMap;
+use std::f64::consts::{E, PI};
+
+use sexe_expression::builder::*;
+use sexe_expression::ExpressionNode;
+
+mod generated {
+    include!("codegen/generated.rs");
+}
+
+/// The functions of `codegen/generated.rs`, with their names and parameters.
+fn functions() -> Vec<(&'static str, ExpressionNode, Vec<&'static str>)> {
+    let x = || var("x");
+    let c = constant;
+    vec![
+        (
+            "polynomial",
+            c(3.0) * x().pow(c(2.0)) - c(2.0) * x() + c(1.0) / (x() - c(0.5)),
+            vec!["x"],
+        ),
+        (
+            "trigonometry",
+            sin(x()) * cos(c(2.0) * x()) + tan(x() / c(3.0)) - ctan(x() + c(0.1))
+                + asin(x() / c(10.0))
+                + acos(-x() / c(10.0)),
+            vec!["x"],
+        ),
+        (
+            "arithmetic",
+            abs(x() - var("y")) + exp(-x()) + ceil(x()) * floor(var("y")) + fract(x() * var("y"))
+                + x() % var("y")
+                + x().pow(var("y"))
+                + x().pow(c(0.5))
+                + c(E) * c(PI) / c(-2.5),
+            vec!["x", "y"],
+        ),
+        (
+            "logarithms",
+            ln(x()) + log2(x()) + log10(x()) + log(c(3.0), x()),
+            vec!["x"],
+        ),
+        ("special", erf(x()) * gamma(x() + c(1.0)), vec!["x"]),
+        (
+            "series",
+            sum("k", c(1.0), var("n"), x().pow(var("k")) / var("k"))
+                * product("k", c(1.0), c(3.0), var("k") + x())
+                - -(-x()),
+            vec!["x", "n"],
+        ),
+    ]
+}
+
+#[test]
+fn generated_code_is_up_to_date() {
+    let code: Vec<String> = functions()
+        .iter()
+        .map(|(name, function, vars)| function.to_rust_fn(name, vars))
+        .collect();
+    let code = code.join("\n");
+    assert_eq!(include_str!("codegen/generated.rs"), code, "generated.rs should be:\n{}", code);
+}
+
+#[test]
+fn generated_code_agrees_with_evaluate() {
+    let generated: Vec<fn(&[f64]) -> f64> = vec![
+        |v| generated::polynomial(v[0]),
+        |v| generated::trigonometry(v[0]),
+        |v| generated::arithmetic(v[0], v[1]),
+        |v| generated::logarithms(v[0]),
+        |v| generated::special(v[0]),
+        |v| generated::series(v[0], v[1]),
+    ];
+    for ((name, function, vars), generated) in functions().iter().zip(generated) {
+        for i in -20..=20 {
+            // The second parameter takes a few integer values, e.g. the number of terms of a sum.
+            for j in 0..4 {
+                let values = [f64::from(i) * 0.35, f64::from(j) * 1.5];
+                let mut bound = HashMap::new();
+                for (var, value) in vars.iter().zip(&values) {
+                    bound.insert(var.to_string(), *value);
+                }
+                let expected = match function.evaluate(&bound) {
+                    Ok(expected) => expected,
+                    Err(_) => continue,
+                };
+                let actual = generated(&values);
+                let agrees = (expected.is_nan() && actual.is_nan())
+                    || expected == actual
+                    || (expected - actual).abs() <= 1e-12 * expected.abs().max(1.0);
+                assert!(agrees, "{} at {:?} is {} instead of {}", name, values, actual, expected);
+            }
+        }
+    }
+}
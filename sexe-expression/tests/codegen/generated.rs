@@ -0,0 +1,23 @@
+pub fn polynomial(x: f64) -> f64 {
+    3.0_f64 * x.powi(2) - 2.0_f64 * x + 1.0_f64 / (x - 0.5_f64)
+}
+
+pub fn trigonometry(x: f64) -> f64 {
+    x.sin() * (2.0_f64 * x).cos() + (x / 3.0_f64).tan() - 1.0_f64 / (x + 0.1_f64).tan() + (x / 10.0_f64).asin() + (-x / 10.0_f64).acos()
+}
+
+pub fn arithmetic(x: f64, y: f64) -> f64 {
+    (x - y).abs() + (-x).exp() + x.ceil() * y.floor() + (x * y).fract() + x.rem_euclid(y) + x.powf(y) + ({ let __base = x; if __base > 0.0 { __base.sqrt() } else { __base.powf(0.5) } }) + ::std::f64::consts::E * ::std::f64::consts::PI / -2.5_f64
+}
+
+pub fn logarithms(x: f64) -> f64 {
+    x.ln() + x.log2() + x.log10() + x.log(3.0_f64)
+}
+
+pub fn special(x: f64) -> f64 {
+    ::sexe_expression::special::erf(x) * ::sexe_expression::special::gamma(x + 1.0_f64)
+}
+
+pub fn series(x: f64, n: f64) -> f64 {
+    ({ let __lower = 1.0_f64.round(); let __upper = n.round(); if __lower.abs() <= 9007199254740992.0 && __upper.abs() <= 9007199254740992.0 && __upper - __lower < 1000000.0 { let mut __total = 0.0_f64; let mut k = __lower; while k <= __upper { __total += x.powf(k) / k; k += 1.0; } __total } else { f64::NAN } }) * ({ let __lower = 1.0_f64.round(); let __upper = 3.0_f64.round(); if __lower.abs() <= 9007199254740992.0 && __upper.abs() <= 9007199254740992.0 && __upper - __lower < 1000000.0 { let mut __total = 1.0_f64; let mut k = __lower; while k <= __upper { __total *= k + x; k += 1.0; } __total } else { f64::NAN } }) - -(-x)
+}
@@ -0,0 +1,132 @@
+#ifndef SEXE_H
+#define SEXE_H
+
+/* Generated with cbindgen from sexe-ffi/src/lib.rs, don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The call succeeded.
+ */
+#define SEXE_OK 0
+
+/**
+ * A variable of the expression was given no value.
+ */
+#define SEXE_VARIABLE_NOT_FOUND 1
+
+/**
+ * An operator or function was given the wrong number of arguments.
+ */
+#define SEXE_WRONG_NUMBER_OF_ARGS 2
+
+/**
+ * An operator produced NaN from operands that weren't NaN.
+ */
+#define SEXE_NOT_A_NUMBER 3
+
+/**
+ * The expression calls a function that doesn't exist.
+ */
+#define SEXE_UNKNOWN_FUNCTION 4
+
+/**
+ * An operator was applied outside of its domain, e.g. `ln(-1)`.
+ */
+#define SEXE_DOMAIN_ERROR 5
+
+/**
+ * The expression has no symbolic derivative.
+ */
+#define SEXE_NOT_DIFFERENTIABLE 6
+
+/**
+ * A pointer argument that mustn't be null was null.
+ */
+#define SEXE_NULL_ARGUMENT -1
+
+/**
+ * A string argument wasn't valid UTF-8.
+ */
+#define SEXE_INVALID_UTF8 -2
+
+/**
+ * The output buffer can't hold every point of the sweep.
+ */
+#define SEXE_BUFFER_TOO_SMALL -3
+
+/**
+ * A parsed expression, which is owned by the caller until it is passed to `sexe_free`.
+ */
+typedef struct SexeExpr SexeExpr;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Parses the null-terminated string `input`. Returns null if `input` is null, isn't valid UTF-8
+ * or doesn't parse.
+ *
+ * # Safety
+ *
+ * `input` must be null or point to a null-terminated string.
+ */
+SexeExpr *sexe_parse(const char *input);
+
+/**
+ * Evaluates `expr` with the `n` variables named by `names` set to the corresponding `values`,
+ * writing the result to `out`. Returns `SEXE_OK`, or the status of what went wrong, in which
+ * case `out` is left as it was.
+ *
+ * # Safety
+ *
+ * `expr` must be null or returned by `sexe_parse` and not yet freed. `names` and `values` must
+ * point to `n` elements each, where every name is a null-terminated string, unless `n` is 0, in
+ * which case they may be null. `out` must be null or point to a `double`.
+ */
+int32_t sexe_eval(const SexeExpr *expr,
+                  const char *const *names,
+                  const double *values,
+                  size_t n,
+                  double *out);
+
+/**
+ * Samples `expr` at `resolution` values of the variable named `var_name` from `start` to `end`,
+ * writing the points as interleaved pairs `x0, y0, x1, y1, ...` to `out`, which has room for
+ * `capacity` points. Points that fail to evaluate are left out, so the number of points written
+ * is stored in `written`. Returns `SEXE_BUFFER_TOO_SMALL` without writing anything if `capacity`
+ * is less than `resolution`.
+ *
+ * # Safety
+ *
+ * `expr` must be null or returned by `sexe_parse` and not yet freed, and `var_name` must be null
+ * or point to a null-terminated string. `out` must be null or point to `2 * capacity` doubles,
+ * and `written` must be null or point to a `size_t`.
+ */
+int32_t sexe_sample(const SexeExpr *expr,
+                    const char *var_name,
+                    double start,
+                    double end,
+                    uint32_t resolution,
+                    double *out,
+                    size_t capacity,
+                    size_t *written);
+
+/**
+ * Frees an expression returned by `sexe_parse`. Freeing null does nothing.
+ *
+ * # Safety
+ *
+ * `expr` must be null or returned by `sexe_parse` and not yet freed.
+ */
+void sexe_free(SexeExpr *expr);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* SEXE_H */
@@ -0,0 +1,196 @@
+//! A C interface for parsing and evaluating expressions, e.g. from a C or C++ frontend:
+//!
+//! ```c
+//! #include "sexe.h"
+//!
+//! SexeExpr *expr = sexe_parse("sin(x) * a");
+//! const char *names[] = {"x", "a"};
+//! const double values[] = {1.5, 2.0};
+//! double y;
+//! if (expr != NULL && sexe_eval(expr, names, values, 2, &y) == SEXE_OK) {
+//!     printf("%f\n", y);
+//! }
+//! sexe_free(expr);
+//! ```
+//!
+//! The header is `include/sexe.h`, which is regenerated with `cbindgen --config cbindgen.toml
+//! --output include/sexe.h` from this directory after the interface changes.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::slice;
+
+use sexe_expression::{EvaluationError, ExpressionNode};
+
+/// The call succeeded.
+pub const SEXE_OK: i32 = 0;
+/// A variable of the expression was given no value.
+pub const SEXE_VARIABLE_NOT_FOUND: i32 = 1;
+/// An operator or function was given the wrong number of arguments.
+pub const SEXE_WRONG_NUMBER_OF_ARGS: i32 = 2;
+/// An operator produced NaN from operands that weren't NaN.
+pub const SEXE_NOT_A_NUMBER: i32 = 3;
+/// The expression calls a function that doesn't exist.
+pub const SEXE_UNKNOWN_FUNCTION: i32 = 4;
+/// An operator was applied outside of its domain, e.g. `ln(-1)`.
+pub const SEXE_DOMAIN_ERROR: i32 = 5;
+/// The expression has no symbolic derivative.
+pub const SEXE_NOT_DIFFERENTIABLE: i32 = 6;
+/// A pointer argument that mustn't be null was null.
+pub const SEXE_NULL_ARGUMENT: i32 = -1;
+/// A string argument wasn't valid UTF-8.
+pub const SEXE_INVALID_UTF8: i32 = -2;
+/// The output buffer can't hold every point of the sweep.
+pub const SEXE_BUFFER_TOO_SMALL: i32 = -3;
+
+/// A parsed expression, which is owned by the caller until it is passed to `sexe_free`.
+pub struct SexeExpr {
+    tree: ExpressionNode,
+}
+
+/// Parses the null-terminated string `input`. Returns null if `input` is null, isn't valid UTF-8
+/// or doesn't parse.
+///
+/// # Safety
+///
+/// `input` must be null or point to a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sexe_parse(input: *const c_char) -> *mut SexeExpr {
+    let input = match to_str(input) {
+        Ok(input) => input,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match sexe_parser::parse(input) {
+        Ok(tree) => Box::into_raw(Box::new(SexeExpr { tree })),
+        Err(()) => std::ptr::null_mut(),
+    }
+}
+
+/// Evaluates `expr` with the `n` variables named by `names` set to the corresponding `values`,
+/// writing the result to `out`. Returns `SEXE_OK`, or the status of what went wrong, in which
+/// case `out` is left as it was.
+///
+/// # Safety
+///
+/// `expr` must be null or returned by `sexe_parse` and not yet freed. `names` and `values` must
+/// point to `n` elements each, where every name is a null-terminated string, unless `n` is 0, in
+/// which case they may be null. `out` must be null or point to a `double`.
+#[no_mangle]
+pub unsafe extern "C" fn sexe_eval(
+    expr: *const SexeExpr,
+    names: *const *const c_char,
+    values: *const f64,
+    n: usize,
+    out: *mut f64,
+) -> i32 {
+    if expr.is_null() || out.is_null() {
+        return SEXE_NULL_ARGUMENT;
+    }
+    let vars = match variables(names, values, n) {
+        Ok(vars) => vars,
+        Err(status) => return status,
+    };
+    match (*expr).tree.evaluate(&vars) {
+        Ok(value) => {
+            *out = value;
+            SEXE_OK
+        }
+        Err(error) => status(&error),
+    }
+}
+
+/// Samples `expr` at `resolution` values of the variable named `var_name` from `start` to `end`,
+/// writing the points as interleaved pairs `x0, y0, x1, y1, ...` to `out`, which has room for
+/// `capacity` points. Points that fail to evaluate are left out, so the number of points written
+/// is stored in `written`. Returns `SEXE_BUFFER_TOO_SMALL` without writing anything if `capacity`
+/// is less than `resolution`.
+///
+/// # Safety
+///
+/// `expr` must be null or returned by `sexe_parse` and not yet freed, and `var_name` must be null
+/// or point to a null-terminated string. `out` must be null or point to `2 * capacity` doubles,
+/// and `written` must be null or point to a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn sexe_sample(
+    expr: *const SexeExpr,
+    var_name: *const c_char,
+    start: f64,
+    end: f64,
+    resolution: u32,
+    out: *mut f64,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    if expr.is_null() || out.is_null() || written.is_null() {
+        return SEXE_NULL_ARGUMENT;
+    }
+    let var_name = match to_str(var_name) {
+        Ok(var_name) => var_name,
+        Err(status) => return status,
+    };
+    if capacity < resolution as usize {
+        return SEXE_BUFFER_TOO_SMALL;
+    }
+    let points =
+        sexe_expression::evaluate_function_over_domain_var(start, end, resolution, &(*expr).tree, var_name);
+    let out = slice::from_raw_parts_mut(out, 2 * capacity);
+    for (pair, &(x, y)) in out.chunks_exact_mut(2).zip(&points) {
+        pair[0] = x;
+        pair[1] = y;
+    }
+    *written = points.len();
+    SEXE_OK
+}
+
+/// Frees an expression returned by `sexe_parse`. Freeing null does nothing.
+///
+/// # Safety
+///
+/// `expr` must be null or returned by `sexe_parse` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn sexe_free(expr: *mut SexeExpr) {
+    if !expr.is_null() {
+        drop(Box::from_raw(expr));
+    }
+}
+
+/// Borrows a null-terminated string as a `str`.
+unsafe fn to_str<'a>(string: *const c_char) -> Result<&'a str, i32> {
+    if string.is_null() {
+        return Err(SEXE_NULL_ARGUMENT);
+    }
+    CStr::from_ptr(string).to_str().map_err(|_| SEXE_INVALID_UTF8)
+}
+
+/// Collects the variables passed to `sexe_eval`. If a name is given twice, the last value wins.
+unsafe fn variables(
+    names: *const *const c_char,
+    values: *const f64,
+    n: usize,
+) -> Result<HashMap<String, f64>, i32> {
+    let mut vars = HashMap::new();
+    if n == 0 {
+        return Ok(vars);
+    }
+    if names.is_null() || values.is_null() {
+        return Err(SEXE_NULL_ARGUMENT);
+    }
+    let names = slice::from_raw_parts(names, n);
+    let values = slice::from_raw_parts(values, n);
+    for (&name, &value) in names.iter().zip(values) {
+        vars.insert(to_str(name)?.to_string(), value);
+    }
+    Ok(vars)
+}
+
+fn status(error: &EvaluationError) -> i32 {
+    match error {
+        EvaluationError::VariableNotFoundError => SEXE_VARIABLE_NOT_FOUND,
+        EvaluationError::WrongNumberOfArgsError { .. } => SEXE_WRONG_NUMBER_OF_ARGS,
+        EvaluationError::NotANumberError => SEXE_NOT_A_NUMBER,
+        EvaluationError::UnknownFunction { .. } => SEXE_UNKNOWN_FUNCTION,
+        EvaluationError::DomainError => SEXE_DOMAIN_ERROR,
+        EvaluationError::NotDifferentiable => SEXE_NOT_DIFFERENTIABLE,
+    }
+}
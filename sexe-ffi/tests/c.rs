@@ -0,0 +1,35 @@
+//! Compiles `sexe_test.c` against the library and `include/sexe.h` with the system's C compiler,
+//! which is `cc` unless the `CC` environment variable names another one, and runs it.
+
+#![cfg(unix)]
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn c_program_uses_the_library() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // Tests run from `target/<profile>/deps`, and the library is built next to that directory.
+    let test_exe = env::current_exe().unwrap();
+    let library_dir = test_exe.parent().unwrap().parent().unwrap();
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("sexe_test");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+    let status = Command::new(compiler)
+        .arg(manifest_dir.join("tests/sexe_test.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(library_dir)
+        .arg(format!("-Wl,-rpath,{}", library_dir.display()))
+        .arg("-lsexe_ffi")
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("a C compiler is needed to run this test");
+    assert!(status.success(), "sexe_test.c didn't compile");
+
+    let status = Command::new(&program).status().unwrap();
+    assert!(status.success(), "sexe_test.c failed");
+}
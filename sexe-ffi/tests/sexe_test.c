@@ -0,0 +1,84 @@
+/* Exercises the C interface as a C program would use it. Compiled and run by `tests/c.rs`. */
+
+#include <stdio.h>
+
+#include "sexe.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                                   \
+    do {                                                                   \
+        if (!(condition)) {                                                \
+            fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #condition); \
+            failures++;                                                    \
+        }                                                                  \
+    } while (0)
+
+static void test_parse(void) {
+    CHECK(sexe_parse(NULL) == NULL);
+    CHECK(sexe_parse("sin(x) + )") == NULL);
+    CHECK(sexe_parse("\xff") == NULL);
+
+    SexeExpr *expr = sexe_parse("x^2");
+    CHECK(expr != NULL);
+    sexe_free(expr);
+    sexe_free(NULL);
+}
+
+static void test_eval(void) {
+    SexeExpr *expr = sexe_parse("x * a + 1");
+    const char *names[] = {"x", "a"};
+    const double values[] = {2.0, 3.0};
+    double out = 0.0;
+    CHECK(sexe_eval(expr, names, values, 2, &out) == SEXE_OK);
+    CHECK(out == 7.0);
+
+    out = 0.0;
+    CHECK(sexe_eval(expr, names, values, 1, &out) == SEXE_VARIABLE_NOT_FOUND);
+    CHECK(out == 0.0);
+    CHECK(sexe_eval(expr, NULL, NULL, 0, &out) == SEXE_VARIABLE_NOT_FOUND);
+    CHECK(sexe_eval(expr, NULL, values, 2, &out) == SEXE_NULL_ARGUMENT);
+    CHECK(sexe_eval(expr, names, values, 2, NULL) == SEXE_NULL_ARGUMENT);
+    CHECK(sexe_eval(NULL, names, values, 2, &out) == SEXE_NULL_ARGUMENT);
+
+    const char *invalid[] = {"x", "\xff"};
+    CHECK(sexe_eval(expr, invalid, values, 2, &out) == SEXE_INVALID_UTF8);
+    sexe_free(expr);
+
+    expr = sexe_parse("ln(x)");
+    CHECK(sexe_eval(expr, names, (const double[]){-1.0}, 1, &out) == SEXE_DOMAIN_ERROR);
+    sexe_free(expr);
+
+    expr = sexe_parse("foo(x)");
+    CHECK(sexe_eval(expr, names, values, 1, &out) == SEXE_UNKNOWN_FUNCTION);
+    sexe_free(expr);
+}
+
+static void test_sample(void) {
+    SexeExpr *expr = sexe_parse("t^2");
+    double points[8];
+    size_t written = 0;
+    CHECK(sexe_sample(expr, "t", 0.0, 2.0, 2, points, 4, &written) == SEXE_OK);
+    CHECK(written == 2);
+    CHECK(points[0] == 0.0 && points[1] == 0.0);
+    CHECK(points[2] == 1.0 && points[3] == 1.0);
+
+    CHECK(sexe_sample(expr, "t", 0.0, 2.0, 5, points, 4, &written) == SEXE_BUFFER_TOO_SMALL);
+    CHECK(sexe_sample(expr, NULL, 0.0, 2.0, 2, points, 4, &written) == SEXE_NULL_ARGUMENT);
+    CHECK(sexe_sample(expr, "t", 0.0, 2.0, 2, NULL, 4, &written) == SEXE_NULL_ARGUMENT);
+    sexe_free(expr);
+
+    /* ln(0) fails, so the first point is left out. */
+    expr = sexe_parse("ln(x)");
+    CHECK(sexe_sample(expr, "x", 0.0, 2.0, 2, points, 4, &written) == SEXE_OK);
+    CHECK(written == 1);
+    CHECK(points[0] == 1.0 && points[1] == 0.0);
+    sexe_free(expr);
+}
+
+int main(void) {
+    test_parse();
+    test_eval();
+    test_sample();
+    return failures == 0 ? 0 : 1;
+}
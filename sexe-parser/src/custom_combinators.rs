@@ -65,9 +65,9 @@ where
 /// A combinator that takes a parser `inner` and produces a parser that also consumes both leading
 /// and trailing whitespace, returning the output of `inner`.
 /// See `https://docs.rs/nom/6.0.1/nom/recipes/index.html` for details.
-pub fn ws<'a, F: 'a, O, E: ParseError<&'a str>>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
+pub fn ws<'a, F, O, E: ParseError<&'a str>>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
   where
-  F: FnMut(&'a str) -> IResult<&'a str, O, E>,
+  F: FnMut(&'a str) -> IResult<&'a str, O, E> + 'a,
 {
   nom::sequence::delimited(
     nom::character::complete::multispace0,
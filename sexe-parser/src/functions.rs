@@ -0,0 +1,101 @@
+use sexe_expression::UnaryOperator;
+
+/// What a name in a `FunctionTable` is parsed as, when it is followed by its parenthesized
+/// arguments.
+#[derive(Debug, PartialEq, Clone)]
+pub enum FunctionKind {
+    /// A built-in unary operator, e.g. `sin(x)`, which takes exactly one argument.
+    Unary(UnaryOperator),
+    /// The logarithm `log(x, base)`.
+    Log,
+    /// A call to the custom function registered in the `EvaluationContext` under the given name,
+    /// which needn't be the name it is parsed from, e.g. `sqrt` could be parsed as `root2`.
+    Call(String),
+}
+
+/// The built-in functions, with every name they can be written as.
+const BUILTIN_FUNCTIONS: &[(&str, UnaryOperator)] = &[
+    ("sin", UnaryOperator::Sin),
+    ("asin", UnaryOperator::Asin),
+    ("arcsin", UnaryOperator::Asin),
+    ("cos", UnaryOperator::Cos),
+    ("acos", UnaryOperator::Acos),
+    ("arccos", UnaryOperator::Acos),
+    ("tan", UnaryOperator::Tan),
+    ("tg", UnaryOperator::Tan),
+    ("ctan", UnaryOperator::Ctan),
+    ("ctg", UnaryOperator::Ctan),
+    ("asinh", UnaryOperator::Asinh),
+    ("arsinh", UnaryOperator::Asinh),
+    ("acosh", UnaryOperator::Acosh),
+    ("arcosh", UnaryOperator::Acosh),
+    ("atanh", UnaryOperator::Atanh),
+    ("artanh", UnaryOperator::Atanh),
+    ("abs", UnaryOperator::Abs),
+    ("log2", UnaryOperator::Log2),
+    ("log10", UnaryOperator::Log10),
+    ("ln", UnaryOperator::Ln),
+    ("exp", UnaryOperator::Exp),
+    ("ceil", UnaryOperator::Ceil),
+    ("floor", UnaryOperator::Floor),
+    ("fract", UnaryOperator::Fract),
+    ("erf", UnaryOperator::Erf),
+    ("gamma", UnaryOperator::Gamma),
+    ("isnan", UnaryOperator::IsNan),
+    ("isinf", UnaryOperator::IsInf),
+    ("isfinite", UnaryOperator::IsFinite),
+];
+
+/// The names that are parsed as functions, e.g. `sin` in `sin(x)`. The default table holds the
+/// built-in functions, and embedders can add their own names to it for `parse_with_table`.
+///
+/// When one name is a prefix of another, like `log` of `log10`, the longest one that is followed
+/// by parentheses wins, whatever order they were added in.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FunctionTable {
+    entries: Vec<(String, FunctionKind)>,
+}
+
+impl FunctionTable {
+    /// A table without any names, not even the built-in ones.
+    pub fn empty() -> FunctionTable {
+        FunctionTable { entries: Vec::new() }
+    }
+
+    /// Adds `name` to the table, replacing what it was parsed as before, if anything.
+    pub fn insert(&mut self, name: &str, kind: FunctionKind) {
+        match self.entries.iter_mut().find(|(entry, _)| entry == name) {
+            Some(entry) => entry.1 = kind,
+            None => self.entries.push((name.to_string(), kind)),
+        }
+    }
+
+    /// What `name` is parsed as, if it is in the table.
+    pub fn get(&self, name: &str) -> Option<&FunctionKind> {
+        self.entries.iter().find(|(entry, _)| entry == name).map(|(_, kind)| kind)
+    }
+
+    /// The longest name in the table that `input` starts with, followed by an opening parenthesis
+    /// after any whitespace, and what it is parsed as.
+    pub(crate) fn longest_match(&self, input: &str) -> Option<(&str, &FunctionKind)> {
+        self.entries
+            .iter()
+            .filter(|(name, _)| {
+                input.starts_with(name.as_str()) && input[name.len()..].trim_start().starts_with('(')
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(name, kind)| (name.as_str(), kind))
+    }
+}
+
+impl Default for FunctionTable {
+    /// The built-in functions, which `parse` uses.
+    fn default() -> FunctionTable {
+        let mut table = FunctionTable::empty();
+        for (name, operator) in BUILTIN_FUNCTIONS {
+            table.insert(name, FunctionKind::Unary(operator.clone()));
+        }
+        table.insert("log", FunctionKind::Log);
+        table
+    }
+}
@@ -0,0 +1,163 @@
+use std::ops::Range;
+
+use sexe_expression::ExpressionNode;
+
+use crate::parse_complete;
+
+/// How bad a `Diagnostic` is.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    /// The input was parsed, but only after fixing it up, e.g. by closing a parenthesis.
+    Warning,
+    /// Part of the input was left out of the expression.
+    Error,
+}
+
+/// A problem with the input of `parse_lenient`, and the range of bytes of the input it is about.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+/// Parses as much of `input` as possible, for input that is still being typed. Parentheses that
+/// are still open at the end are closed, with a warning for each. If the input still doesn't
+/// parse, the longest prefix of it that does is the expression, with an error about the rest,
+/// e.g. `sin(x)+` is `sin(x)` and an error about the `+`.
+///
+/// If no prefix parses at all, there is no expression, and the error spans the whole input.
+pub fn parse_lenient(input: &str) -> (Option<ExpressionNode>, Vec<Diagnostic>) {
+    let ends = input
+        .char_indices()
+        .map(|(i, _)| i)
+        .skip(1)
+        .chain(Some(input.len()))
+        .collect::<Vec<_>>();
+    for &end in ends.iter().rev() {
+        let prefix = &input[..end];
+        let unclosed = unclosed_parens(prefix);
+        let closed = format!("{}{}", prefix, ")".repeat(unclosed.len()));
+        if let Ok(func) = parse_complete(&closed) {
+            let mut diagnostics: Vec<Diagnostic> = unclosed
+                .into_iter()
+                .map(|position| Diagnostic {
+                    severity: Severity::Warning,
+                    span: position..position + 1,
+                    message: String::from("unclosed parenthesis"),
+                })
+                .collect();
+            let rest = &input[end..];
+            if !rest.trim().is_empty() {
+                let start = end + (rest.len() - rest.trim_start().len());
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    span: start..input.trim_end().len(),
+                    message: format!("could not parse `{}`", rest.trim()),
+                });
+            }
+            return (Some(func), diagnostics);
+        }
+    }
+
+    let message = if input.trim().is_empty() {
+        String::from("expected an expression")
+    } else {
+        format!("could not parse `{}`", input.trim())
+    };
+    let diagnostic = Diagnostic {
+        severity: Severity::Error,
+        span: 0..input.len(),
+        message,
+    };
+    (None, vec![diagnostic])
+}
+
+/// The positions of the opening parentheses of `input` that aren't closed, from the innermost
+/// one out, which is the order to close them in.
+fn unclosed_parens(input: &str) -> Vec<usize> {
+    let mut open = Vec::new();
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => open.push(i),
+            ')' => {
+                open.pop();
+            }
+            _ => (),
+        }
+    }
+    open.reverse();
+    open
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn error(span: Range<usize>, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            message: message.to_string(),
+        }
+    }
+
+    fn unclosed(position: usize) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            span: position..position + 1,
+            message: String::from("unclosed parenthesis"),
+        }
+    }
+
+    #[test]
+    fn complete_input_has_no_diagnostics() {
+        assert_eq!(parse_lenient("sin(x) + 1"), (parse("sin(x) + 1").ok(), vec![]));
+        assert_eq!(parse_lenient(" x "), (parse("x").ok(), vec![]));
+    }
+
+    #[test]
+    fn unparsable_tails_are_left_out() {
+        assert_eq!(
+            parse_lenient("sin(x)+"),
+            (parse("sin(x)").ok(), vec![error(6..7, "could not parse `+`")])
+        );
+        assert_eq!(
+            parse_lenient("x^2 * "),
+            (parse("x^2").ok(), vec![error(4..5, "could not parse `*`")])
+        );
+        assert_eq!(
+            parse_lenient("3 + 4 )"),
+            (parse("3 + 4").ok(), vec![error(6..7, "could not parse `)`")])
+        );
+        assert_eq!(
+            parse_lenient("log(x, 2) + |x"),
+            (parse("log(x, 2)").ok(), vec![error(10..14, "could not parse `+ |x`")])
+        );
+    }
+
+    #[test]
+    fn open_parentheses_are_closed() {
+        assert_eq!(parse_lenient("sin(x"), (parse("sin(x)").ok(), vec![unclosed(3)]));
+        assert_eq!(
+            parse_lenient("2 * (x + sin(x"),
+            (parse("2 * (x + sin(x))").ok(), vec![unclosed(12), unclosed(4)])
+        );
+        // The prefix that parses once its parenthesis is closed is `sin(x`.
+        assert_eq!(
+            parse_lenient("sin(x +"),
+            (
+                parse("sin(x)").ok(),
+                vec![unclosed(3), error(6..7, "could not parse `+`")]
+            )
+        );
+    }
+
+    #[test]
+    fn input_without_a_valid_prefix_has_no_expression() {
+        assert_eq!(parse_lenient("+"), (None, vec![error(0..1, "could not parse `+`")]));
+        assert_eq!(parse_lenient(""), (None, vec![error(0..0, "expected an expression")]));
+        assert_eq!(parse_lenient("  "), (None, vec![error(0..2, "expected an expression")]));
+    }
+}
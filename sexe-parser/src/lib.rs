@@ -1,6 +1,7 @@
 extern crate nom;
 extern crate sexe_expression;
 
+use std::cell::{Cell, RefCell};
 use std::f64::consts::{E, PI};
 
 use nom::IResult;
@@ -8,52 +9,37 @@ use nom::ParseTo;
 use nom::branch::alt;
 use nom::bytes::complete::{tag, tag_no_case};
 use nom::character::complete::{alpha1, char};
-use nom::combinator::not;
+use nom::combinator::{not, verify};
 use nom::multi::separated_list0;
-use nom::sequence::{delimited, pair};
+use nom::sequence::{delimited, pair, terminated};
 
 use sexe_expression::*;
 
 mod custom_combinators;
 use crate::custom_combinators::{recognize_float, fold_many0_once, ws};
 
+mod functions;
+pub use crate::functions::{FunctionKind, FunctionTable};
 
-/// Helper macro for defining simple unary functions to be invoked with function
-/// call like syntax (like `sin(x)`). The first argument is the name of the
-/// function, (e.g. `parse_sin`), the second argument is the UnaryOperator
-/// expression node type, (e.g. `UnaryOperator::Sin`), and then the remaining
-/// arguments are a comma separated list of different valid parse strings for
-/// this function (e.g. `"asin", "arcsin").
-macro_rules! def_unary_fn_parser {
-    // When only parse string is provided, we cannot use an alt combinator, so
-    // we parse the string directly with tag.
-    ($name:ident, $op:expr, $str:expr) => (
-        fn $name(i: &str) -> IResult<&str, ExpressionNode> {
-            let (i, _) = tag($str)(i)?;
-            let (i, res) = parse_parens(i)?;
-            Ok((i, ExpressionNode::UnaryExprNode {
-                operator: $op,
-                child_node: Box::new(res),
-            }))
-        }
-    );
-    // If multiple parse strings are provided, we wrap them in an alt
-    // combinator.
-    ($name:ident, $op:expr, $($strs:expr),+) => (
-        fn $name(i: &str) -> IResult<&str, ExpressionNode> {
-            let (i, _) = alt(($(tag($strs),)+))(i)?;
-            let (i, res) = parse_parens(i)?;
-            Ok((i, ExpressionNode::UnaryExprNode {
-                operator: $op,
-                child_node: Box::new(res),
-            }))
-        }
-    );
-}
+mod lenient;
+pub use crate::lenient::{parse_lenient, Diagnostic, Severity};
+
+mod tokenize;
+pub use crate::tokenize::{tokenize, LexError, Token, TokenKind};
 
+#[cfg(test)]
+mod round_trip;
+
+
+/// Parses a number literal. Literals too large for an `f64`, like `1e400`, are rejected rather
+/// than read as infinity, which has no syntax of its own to be printed back as.
 fn parse_double(i: &str) -> IResult<&str, f64> {
-    let (i, f) = recognize_float(i)?;
-    Ok((i, f.parse_to().unwrap()))
+    let (rest, f) = recognize_float(i)?;
+    let value: f64 = f.parse_to().unwrap();
+    if !value.is_finite() {
+        return Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Float)));
+    }
+    Ok((rest, value))
 }
 
 fn parse_constant(i: &str) -> IResult<&str, ExpressionNode> {
@@ -61,12 +47,61 @@ fn parse_constant(i: &str) -> IResult<&str, ExpressionNode> {
     Ok((i, ExpressionNode::ConstantExprNode { value, }))
 }
 
+/// Parses a number with an angle unit, e.g. `90deg`, as a constant in radians. The unit has to
+/// be the whole word following the number, so that e.g. `2degree` is still `2 * degree`.
+fn parse_angle(i: &str) -> IResult<&str, ExpressionNode> {
+    let (i, value) = parse_double(i)?;
+    let (i, unit) = terminated(alt((tag("deg"), tag("grad"), tag("rad"))), not(alpha1))(i)?;
+    let radians_per_unit = match unit {
+        "deg" => PI / 180.0,
+        "grad" => PI / 200.0,
+        _ => 1.0,
+    };
+    Ok((i, ExpressionNode::ConstantExprNode { value: value * radians_per_unit, }))
+}
+
+/// Parses a percentage, e.g. `50%`, as a constant a hundredth of the number. A `%` that is
+/// followed by an operand is the modulo operator instead, so `10%3` is `10 mod 3` while `10% + 1`
+/// is `0.1 + 1` and `10%` alone is `0.1`.
+fn parse_percentage(i: &str) -> IResult<&str, ExpressionNode> {
+    let (i, value) = parse_double(i)?;
+    let (i, _) = terminated(ws(char('%')), not(parse_priority_1))(i)?;
+    Ok((i, ExpressionNode::ConstantExprNode { value: value / 100.0, }))
+}
+
+/// Words that are operators, which can't be used as the names of variables or functions.
+const RESERVED_WORDS: &[&str] = &["mod"];
+
 fn parse_variable(i: &str) -> IResult<&str, ExpressionNode> {
-    let (i, var) = alpha1(i)?;
+    let (i, var) = verify(alpha1, |var: &str| !RESERVED_WORDS.contains(&var))(i)?;
     Ok((i, ExpressionNode::VariableExprNode { variable_key: var.to_string(), }))
 }
 
+/// Options that change which inputs `parse_with_options` accepts.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ParseOptions {
+    /// Whether juxtaposition multiplies, e.g. `3x` or `2(x+1)`. When this is off, the
+    /// multiplication has to be written out as `3*x`, and `3x` is a parse error.
+    pub implicit_multiplication: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions { implicit_multiplication: true }
+    }
+}
+
+thread_local! {
+    // The parsers are plain functions, so the options of the current parse are kept here instead
+    // of being passed down through every one of them.
+    static IMPLICIT_MULTIPLICATION: Cell<bool> = const { Cell::new(true) };
+    static FUNCTION_TABLE: RefCell<FunctionTable> = RefCell::new(FunctionTable::default());
+}
+
 fn parse_coefficient(i: &str) -> IResult<&str, ExpressionNode> {
+    if !IMPLICIT_MULTIPLICATION.with(Cell::get) {
+        return Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Verify)));
+    }
     let (i, coefficient) = parse_priority_1(i)?;
     let (i, res) = parse_priority_1(i)?;
     Ok((i, ExpressionNode::BinaryExprNode {
@@ -80,19 +115,36 @@ fn parse_parens(i: &str) -> IResult<&str, ExpressionNode> {
     ws(delimited(char('('), parse_expr, char(')')))(i)
 }
 
-def_unary_fn_parser!(parse_sin, UnaryOperator::Sin, "sin");
-def_unary_fn_parser!(parse_asin, UnaryOperator::Asin, "asin", "arcsin");
-def_unary_fn_parser!(parse_cos, UnaryOperator::Cos, "cos");
-def_unary_fn_parser!(parse_acos, UnaryOperator::Acos, "acos", "arccos");
-def_unary_fn_parser!(parse_tan, UnaryOperator::Tan, "tan", "tg");
-def_unary_fn_parser!(parse_ctan, UnaryOperator::Ctan, "ctan", "ctg");
-def_unary_fn_parser!(parse_abs, UnaryOperator::Abs, "abs");
-def_unary_fn_parser!(parse_log2, UnaryOperator::Log2, "log2");
-def_unary_fn_parser!(parse_log10, UnaryOperator::Log10, "log10");
-def_unary_fn_parser!(parse_ln, UnaryOperator::Ln, "ln");
-def_unary_fn_parser!(parse_exp, UnaryOperator::Exp, "exp");
-def_unary_fn_parser!(parse_ceil, UnaryOperator::Ceil, "ceil");
-def_unary_fn_parser!(parse_floor, UnaryOperator::Floor, "floor");
+/// Parses a call to one of the functions of the current `FunctionTable`, e.g. `sin(x)`.
+fn parse_table_function(i: &str) -> IResult<&str, ExpressionNode> {
+    let matched = FUNCTION_TABLE.with(|table| {
+        table.borrow().longest_match(i).map(|(name, kind)| (name.len(), kind.clone()))
+    });
+    let (i, kind) = match matched {
+        Some((name_len, kind)) => (&i[name_len..], kind),
+        None => return Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Tag))),
+    };
+    match kind {
+        FunctionKind::Unary(operator) => {
+            let (i, res) = parse_parens(i)?;
+            Ok((i, ExpressionNode::UnaryExprNode {
+                operator,
+                child_node: Box::new(res),
+            }))
+        }
+        FunctionKind::Log => {
+            let (i, res) = ws(parse_args)(i)?;
+            Ok((i, ExpressionNode::NaryExprNode {
+                operator: NaryOperator::Log,
+                child_nodes: res,
+            }))
+        }
+        FunctionKind::Call(name) => {
+            let (i, args) = ws(parse_args)(i)?;
+            Ok((i, ExpressionNode::FunctionCallNode { name, args }))
+        }
+    }
+}
 
 fn parse_args(i: &str) -> IResult<&str, Vec<ExpressionNode>> {
     //let (i, _) = char('(')(i)?;
@@ -102,12 +154,60 @@ fn parse_args(i: &str) -> IResult<&str, Vec<ExpressionNode>> {
     delimited(char('('), separated_list0(tag(","), parse_expr), char(')'))(i)
 }
 
-fn parse_log(i: &str) -> IResult<&str, ExpressionNode> {
-    let (i, _) = tag("log")(i)?;
-    let (i, res) = parse_args(i)?;
+/// Parses a logarithm with its base written as a subscript, e.g. `log_2(x)` or `log_(b)(x)`.
+fn parse_log_base(i: &str) -> IResult<&str, ExpressionNode> {
+    let (i, _) = tag("log_")(i)?;
+    let (i, base) = alt((parse_constant, parse_parens))(i)?;
+    let (i, x) = parse_parens(i)?;
     Ok((i, ExpressionNode::NaryExprNode {
         operator: NaryOperator::Log,
-        child_nodes: Box::new(res),
+        child_nodes: vec![x, base],
+    }))
+}
+
+/// Parses a sum over an index variable, e.g. `sum(k, 1, n, k^2)`. Unlike the arguments of other
+/// functions, the first one is the name of the index rather than an expression.
+fn parse_sum(i: &str) -> IResult<&str, ExpressionNode> {
+    let (i, _) = tag("sum")(i)?;
+    let (i, (index_key, child_nodes)) = parse_series_args(i)?;
+    Ok((i, ExpressionNode::NaryExprNode {
+        operator: NaryOperator::Sum { index_key },
+        child_nodes,
+    }))
+}
+
+/// Parses a product over an index variable, e.g. `prod(k, 1, n, k)`, the same way as a sum.
+fn parse_prod(i: &str) -> IResult<&str, ExpressionNode> {
+    let (i, _) = tag("prod")(i)?;
+    let (i, (index_key, child_nodes)) = parse_series_args(i)?;
+    Ok((i, ExpressionNode::NaryExprNode {
+        operator: NaryOperator::Product { index_key },
+        child_nodes,
+    }))
+}
+
+/// Parses the parenthesized `(k, lower, upper, body)` of a sum or product into the name of the
+/// index and the children `[lower, upper, body]`.
+fn parse_series_args(i: &str) -> IResult<&str, (String, Vec<ExpressionNode>)> {
+    let (i, index_key) = delimited(char('('), ws(alpha1), char(','))(i)?;
+    let (i, lower) = parse_expr(i)?;
+    let (i, _) = char(',')(i)?;
+    let (i, upper) = parse_expr(i)?;
+    let (i, _) = char(',')(i)?;
+    let (i, body) = parse_expr(i)?;
+    let (i, _) = char(')')(i)?;
+    Ok((i, (index_key.to_string(), vec![lower, upper, body])))
+}
+
+/// Parses a call to a function that isn't built in, e.g. `double(x)`, which is looked up when the
+/// expression is evaluated. Only names of at least two letters are calls: a single letter followed
+/// by parentheses is a variable times a parenthesized expression, as in `x(x+1)`.
+fn parse_function_call(i: &str) -> IResult<&str, ExpressionNode> {
+    let (i, name) = verify(alpha1, |name: &str| name.len() > 1 && !RESERVED_WORDS.contains(&name))(i)?;
+    let (i, args) = parse_args(i)?;
+    Ok((i, ExpressionNode::FunctionCallNode {
+        name: name.to_string(),
+        args,
     }))
 }
 
@@ -123,12 +223,54 @@ fn parse_pi(i: &str) -> IResult<&str, ExpressionNode> {
     Ok((i, ExpressionNode::ConstantExprNode { value: PI, }))
 }
 
+/// Named physical constants in SI units, e.g. `c` for the speed of light in m/s.
+#[cfg(feature = "physics-constants")]
+const PHYSICS_CONSTANTS: &[(&str, f64)] = &[
+    // The speed of light in vacuum.
+    ("c", 299_792_458.0),
+    // Standard gravity.
+    ("g", 9.806_65),
+    // The Newtonian constant of gravitation.
+    ("G", 6.674_30e-11),
+    // The Planck constant.
+    ("h", 6.626_070_15e-34),
+    // The Boltzmann constant.
+    ("kB", 1.380_649e-23),
+    // The Avogadro constant.
+    ("NA", 6.022_140_76e23),
+];
+
+/// Parses the name of one of the `PHYSICS_CONSTANTS`, which has to be a whole word like `pi`, so
+/// that e.g. `cos` and `gx` aren't mistaken for it.
+#[cfg(feature = "physics-constants")]
+fn parse_physics_constant(i: &str) -> IResult<&str, ExpressionNode> {
+    for &(name, value) in PHYSICS_CONSTANTS {
+        if let Ok((i, _)) = terminated(tag::<_, _, ()>(name), not(alpha1))(i) {
+            return Ok((i, ExpressionNode::ConstantExprNode { value }));
+        }
+    }
+    Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Tag)))
+}
+
+/// Parses `|x|` as `abs(x)`. The same character opens and closes the bars, so which `|` closes
+/// which can be ambiguous. The opening bar is closed by the first `|` after it that ends a
+/// complete expression, trying the later ones if the earlier ones don't. This makes `||x|-1|`
+/// `abs(abs(x) - 1)` and `|x| + |y|` `abs(x) + abs(y)`. The choice isn't revisited if the input
+/// after the closing bar then fails to parse, so e.g. `|x|y|` is an error rather than
+/// `abs(x * abs(y))`.
 fn parse_abs_bar_syntax(i: &str) -> IResult<&str, ExpressionNode> {
-    let (i, res) = delimited(char('|'), parse_expr, char('|'))(i)?;
-    Ok((i, ExpressionNode::UnaryExprNode {
-        operator: UnaryOperator::Abs,
-        child_node: Box::new(res),
-    }))
+    let (i, _) = char('|')(i)?;
+    for (position, _) in i.match_indices('|') {
+        if let Ok((rest, res)) = parse_expr(&i[..position]) {
+            if rest.trim().is_empty() {
+                return Ok((&i[position + 1..], ExpressionNode::UnaryExprNode {
+                    operator: UnaryOperator::Abs,
+                    child_node: Box::new(res),
+                }));
+            }
+        }
+    }
+    Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Char)))
 }
 
 fn parse_expr(i: &str) -> IResult<&str, ExpressionNode> {
@@ -136,63 +278,61 @@ fn parse_expr(i: &str) -> IResult<&str, ExpressionNode> {
 }
 
 fn parse_priority_0(i: &str) -> IResult<&str, ExpressionNode> {
-    // TODO: Figure out a way to avoid redefining these if a parser is already
-    // defined using the `def_unary_fn_parser!` macro?
+    // These would otherwise be parsed as variables.
+    #[cfg(feature = "physics-constants")]
+    {
+        if let Ok(res) = ws(parse_physics_constant)(i) {
+            return Ok(res);
+        }
+    }
     ws(alt((
+        // This must go before parse_constant, or `90deg` would be `90 * deg`.
+        parse_angle,
+        // So must this, or the `%` would be left for a modulo that has no right operand.
+        parse_percentage,
         parse_constant,
         parse_parens,
-        parse_sin,
-        parse_asin,
-        parse_cos,
-        parse_acos,
-        parse_tan,
-        parse_ctan,
-        parse_abs,
-        parse_exp,
-        parse_log2,
-        parse_log10,
-        parse_ln,
-        parse_ceil,
-        parse_floor,
+        parse_table_function,
         parse_abs_bar_syntax,
-        parse_log,
+        parse_log_base,
+        parse_sum,
+        parse_prod,
         // N.B. These must go after the other parsers, or e.g. parse_e will
         // match `exp(x)`.
         parse_e,
         parse_pi,
+        parse_function_call,
         parse_variable
     )))(i)
 }
 
 fn parse_priority_1(i: &str) -> IResult<&str, ExpressionNode> {
-    let (i, init) = parse_priority_0(i)?;
-    fold_many0_once(
-        |i: &str| { ws(pair(tag("^"), parse_priority_0))(i) },
-        init,
-        |acc, (op, val): (&str, ExpressionNode)| {
-            let operator = match op.as_bytes()[0] as char {
-                '^' => BinaryOperator::Exponentiation,
-                // For now, default to Exponentiation.
-                _ => BinaryOperator::Exponentiation,
-            };
-            ExpressionNode::BinaryExprNode {
-                operator,
-                left_node: Box::new(acc),
-                right_node: Box::new(val),
-            }
-        }
-    )(i)
+    let (i, base) = parse_priority_0(i)?;
+    // Exponentiation is right-associative, so the exponent is parsed at this same level: `2^3^2`
+    // is `2^(3^2)`. `**` is accepted as well for those used to Python. It is tried here, before
+    // the `*` of `parse_priority_2` gets a chance to see it.
+    match ws(pair(alt((tag("**"), tag("^"))), parse_priority_1))(i) {
+        Ok((i, (_, exponent))) => Ok((i, ExpressionNode::BinaryExprNode {
+            operator: BinaryOperator::Exponentiation,
+            left_node: Box::new(base),
+            right_node: Box::new(exponent),
+        })),
+        Err(nom::Err::Error(_)) => Ok((i, base)),
+        Err(e) => Err(e),
+    }
 }
 
 fn parse_priority_2(i: &str) -> IResult<&str, ExpressionNode> {
     let (i, init) = alt((parse_coefficient, parse_priority_1))(i)?;
     fold_many0_once(
-        |i: &str| { ws(pair(alt((tag("*"), tag("/"))), parse_priority_1))(i) },
+        // `mod` is only an operator as a whole word, so that e.g. `model` is still a variable.
+        |i: &str| { ws(pair(alt((tag("*"), tag("/"), tag("%"), terminated(tag("mod"), not(alpha1)))), parse_priority_1))(i) },
         init,
         |acc, (op, val): (&str, ExpressionNode)| {
-            let operator = match op.as_bytes()[0] as char {
-                '*' => BinaryOperator::Multiplication,
-                '/' => BinaryOperator::Division,
+            let operator = match op {
+                "*" => BinaryOperator::Multiplication,
+                "/" => BinaryOperator::Division,
+                "%" | "mod" => BinaryOperator::Modulo,
                 // For now, default to Multiplication.
                 _   => BinaryOperator::Multiplication,
             };
@@ -207,7 +347,8 @@ fn parse_priority_2(i: &str) -> IResult<&str, ExpressionNode> {
 
 fn parse_priority_3(i: &str) -> IResult<&str, ExpressionNode> {
     fn _parse_priority_3_internal(i: &str) -> IResult<&str, ExpressionNode> {
-        let (i, op) = tag("-")(i)?;
+        // The sign may follow whitespace, e.g. in `x + -y` or `log(x, -1)`.
+        let (i, op) = ws(tag("-"))(i)?;
         let (i, res) = parse_priority_2(i)?;
         Ok((i, ExpressionNode::UnaryExprNode {
             operator: match op.as_bytes()[0] as char {
@@ -243,10 +384,71 @@ fn parse_priority_4(i: &str) -> IResult<&str, ExpressionNode> {
     )(i)
 }
 
+#[allow(clippy::result_unit_err)]
 pub fn parse(function_string: &str) -> Result<ExpressionNode, ()> {
-    if let Ok((rem, func)) = parse_expr(function_string) {
+    parse_with_options(function_string, ParseOptions::default())
+}
+
+/// Like `parse`, but accepting only the inputs allowed by `options`.
+#[allow(clippy::result_unit_err)]
+pub fn parse_with_options(function_string: &str, options: ParseOptions) -> Result<ExpressionNode, ()> {
+    with_options(options, || parse_complete(function_string))
+}
+
+/// Parses the expression at the start of `input`, which may be followed by anything else, e.g.
+/// the rest of a command in an embedder's own syntax. Returns the expression and the byte offset
+/// of the first character after it. The expression is the longest one that `input` starts with,
+/// as greedy as `parse` would be:
+///
+/// - A word right after the expression continues it by implicit multiplication, so `sin(x) from`
+///   is all consumed. Use `parse_prefix_with_options` without implicit multiplication to stop
+///   before the word instead.
+/// - An operator is only consumed together with its operand, so `sin(x)+ ,` ends before the `+`,
+///   while `sin(x)+ from` is a sum.
+/// - Whitespace before the expression is skipped, but whitespace after it isn't consumed.
+///
+/// Fails if `input` doesn't start with an expression.
+#[allow(clippy::result_unit_err)]
+pub fn parse_prefix(input: &str) -> Result<(ExpressionNode, usize), ()> {
+    parse_prefix_with_options(input, ParseOptions::default())
+}
+
+/// Like `parse_prefix`, but accepting only the inputs allowed by `options`.
+#[allow(clippy::result_unit_err)]
+pub fn parse_prefix_with_options(input: &str, options: ParseOptions) -> Result<(ExpressionNode, usize), ()> {
+    match with_options(options, || ws(parse_expr)(input)) {
+        Ok((rest, func)) => {
+            let end = input[..input.len() - rest.len()].trim_end().len();
+            Ok((func, end))
+        }
+        Err(_) => Err(()),
+    }
+}
+
+/// Runs `parse` with the options of the current parse set to `options`.
+fn with_options<T>(options: ParseOptions, parse: impl FnOnce() -> T) -> T {
+    let previous = IMPLICIT_MULTIPLICATION.with(|cell| cell.replace(options.implicit_multiplication));
+    let result = parse();
+    IMPLICIT_MULTIPLICATION.with(|cell| cell.set(previous));
+    result
+}
+
+/// Like `parse`, but parsing the names of `table` as functions instead of the built-in ones, e.g.
+/// to add aliases for them or calls to custom functions.
+#[allow(clippy::result_unit_err)]
+pub fn parse_with_table(function_string: &str, table: &FunctionTable) -> Result<ExpressionNode, ()> {
+    let previous = FUNCTION_TABLE.with(|cell| cell.replace(table.clone()));
+    let result = parse_complete(function_string);
+    FUNCTION_TABLE.with(|cell| cell.replace(previous));
+    result
+}
+
+fn parse_complete(function_string: &str) -> Result<ExpressionNode, ()> {
+    // Whitespace around the whole expression is skipped here, rather than relying on the parsers
+    // of its first and last terms to do so.
+    if let Ok((rem, func)) = ws(parse_expr)(function_string) {
         // Make sure we consumed the entire input.
-        if rem.len() > 0 {
+        if !rem.is_empty() {
             Err(())
         }
         else {
@@ -258,6 +460,15 @@ pub fn parse(function_string: &str) -> Result<ExpressionNode, ()> {
     }
 }
 
+/// Like `parse`, but also interns the variables of the expression, e.g. to compile it or to
+/// evaluate it against a slice of values.
+#[allow(clippy::result_unit_err)]
+pub fn parse_with_symbols(function_string: &str) -> Result<(ExpressionNode, SymbolTable), ()> {
+    let func = parse(function_string)?;
+    let symbols = SymbolTable::from_expression(&func);
+    Ok((func, symbols))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -288,6 +499,21 @@ mod test {
         };
     }
 
+    macro_rules! approx_eval_test {
+        // Assume an empty variable map.
+        ($inp:expr, $out:expr, $tol:expr) => {
+            let value = parse_expr($inp)
+                .unwrap()
+                .1
+                .evaluate(&HashMap::new())
+                .unwrap();
+            assert!(
+                (value - $out).abs() <= $tol,
+                "{} evaluated to {}, expected {}", $inp, value, $out
+            );
+        };
+    }
+
     macro_rules! error_test {
         // Use the specified variable map.
         ($inp:expr, $err:expr, $vars:expr) => {
@@ -356,6 +582,12 @@ mod test {
         eval_test!("(((2(4)))))", 8.0, &vars_map);
         eval_test!("-2^4", -16.0, &vars_map);
         eval_test!("(-2)^4", 16.0, &vars_map);
+        eval_test!("2^3^2", 512.0);
+        eval_test!("2^2^3", 256.0);
+        eval_test!("(2^2)^3", 64.0);
+        eval_test!("2**3**2", 512.0);
+        eval_test!("-2^2^3", -256.0);
+        eval_test!("3 2^2^3", 768.0);
         eval_test!("exp(0)", 1.0, &vars_map);
         eval_test!("log2(2)", 1.0, &vars_map);
         eval_test!("log2(8)", 3.0, &vars_map);
@@ -365,6 +597,362 @@ mod test {
         eval_test!("sin (   0   )", 0.0, &vars_map);
         eval_test!("sin (   0 * pi  )", 0.0, &vars_map);
         eval_test!("log( 9 , 3)", 2.0, &vars_map);
+        eval_test!("2**3", 8.0, &vars_map);
+        eval_test!("2 ** 3 * 2", 16.0, &vars_map);
+        eval_test!("x**2", 100.0, &vars_map);
+        eval_test!("2*3", 6.0, &vars_map);
+        eval_test!("2 * 3", 6.0, &vars_map);
+    }
+
+    #[test]
+    fn rounding_functions() {
+        eval_test!("floor(2.75)", 2.0);
+        eval_test!("ceil(2.75)", 3.0);
+        eval_test!("fract(2.75)", 0.75);
+        eval_test!("floor(-2.75)", -3.0);
+        eval_test!("ceil(-2.75)", -2.0);
+        // The fractional part keeps the sign of its argument.
+        eval_test!("fract(-2.75)", -0.75);
+        eval_test!("ceil(-2.75) + fract(-2.75)", -2.75);
+        eval_test!("floor(2.75) + fract(2.75)", 2.75);
+        eval_test!("fract(3)", 0.0);
+    }
+
+    #[test]
+    fn inverse_hyperbolic_functions() {
+        eval_test!("asinh(0)", 0.0);
+        eval_test!("acosh(1)", 0.0);
+        eval_test!("atanh(0)", 0.0);
+        eval_test!("arsinh(1)", 1.0f64.asinh());
+        eval_test!("2arcosh(2)", 2.0 * 2.0f64.acosh());
+        // asinh isn't asin followed by a variable h.
+        eval_test!("artanh(0.5) - asinh(0.5)", 0.5f64.atanh() - 0.5f64.asinh());
+        // Outside of their domains they are NaN, which leaves a gap in the plot.
+        for input in &["acosh(0.5)", "atanh(2)"] {
+            let value = parse_expr(input).unwrap().1.evaluate(&HashMap::new()).unwrap();
+            assert!(value.is_nan(), "{} is {}", input, value);
+        }
+        eval_test!("atanh(1)", f64::INFINITY);
+    }
+
+    #[test]
+    fn predicates() {
+        // 0/0 is NaN.
+        eval_test!("isnan(0/0)", 1.0);
+        eval_test!("isnan(1/0)", 0.0);
+        eval_test!("isinf(-1/0)", 1.0);
+        eval_test!("isinf(3)", 0.0);
+        eval_test!("isfinite(1/0)", 0.0);
+        eval_test!("isfinite(0/0)", 0.0);
+        eval_test!("isfinite(3)", 1.0);
+        eval_test!("2 isfinite(3) + isnan(3)", 2.0);
+    }
+
+    #[test]
+    #[cfg(feature = "physics-constants")]
+    fn physics_constants() {
+        eval_test!("c", 299_792_458.0);
+        eval_test!("g", 9.806_65);
+        eval_test!("2g + 1", 2.0 * 9.806_65 + 1.0);
+        eval_test!("NA * kB", 6.022_140_76e23 * 1.380_649e-23);
+        // Longer names and functions are unaffected.
+        let mut vars_map = HashMap::new();
+        vars_map.insert("gx".to_string(), 2.0);
+        eval_test!("gx", 2.0, &vars_map);
+        eval_test!("cos(0)", 1.0);
+        eval_test!("ceil(0.5)", 1.0);
+    }
+
+    #[test]
+    fn logarithms_with_subscript_bases() {
+        eval_test!("log_2(8)", 3.0);
+        approx_eval_test!("log_10(1000)", 3.0, 1e-12);
+        approx_eval_test!("log_(1 + 1)(16) + 1", 5.0, 1e-12);
+        eval_test!("log_2(8) * log(8, 2)", 9.0);
+        assert!(parse("log_x(8)").is_err());
+        assert!(parse("log_2").is_err());
+    }
+
+    #[test]
+    fn percentages() {
+        let mut vars_map = HashMap::new();
+        vars_map.insert("x".to_string(), 8.0);
+
+        eval_test!("50%", 0.5);
+        eval_test!("12.5 %", 0.125);
+        eval_test!("10% + 1", 1.1);
+        eval_test!("(25%) * x", 2.0, &vars_map);
+        eval_test!("x * 50% - 1", 3.0, &vars_map);
+        eval_test!("2^50%", 2.0f64.sqrt());
+        // A `%` followed by an operand is the modulo operator.
+        eval_test!("10%3", 1.0);
+        eval_test!("10 % x", 2.0, &vars_map);
+        eval_test!("10% (4)", 2.0);
+        eval_test!("10% mod 3", 0.1);
+        assert_eq!(parse("50 %"), Ok(ExpressionNode::constant(0.5)));
+        assert!(parse("x%").is_err());
+    }
+
+    #[test]
+    fn modulo() {
+        let mut vars_map = HashMap::new();
+        vars_map.insert("model".to_string(), 10.0);
+        vars_map.insert("x".to_string(), 7.0);
+
+        eval_test!("7 % 3", 1.0);
+        eval_test!("7 mod 3", 1.0);
+        eval_test!("x mod 3", 1.0, &vars_map);
+        eval_test!("7.5 % 2", 1.5);
+        // The remainder is never negative.
+        eval_test!("-1 % 3", -1.0);
+        eval_test!("(-1) % 3", 2.0);
+        eval_test!("2 * 7 mod 4", 2.0);
+        eval_test!("1 + 7 mod 4", 4.0);
+        // `mod` is only an operator as a whole word.
+        eval_test!("model", 10.0, &vars_map);
+        eval_test!("model mod 4", 2.0, &vars_map);
+        eval_test!("2model", 20.0, &vars_map);
+        assert!(parse("mod").is_err());
+        assert!(parse("x mod").is_err());
+    }
+
+    #[test]
+    fn variables_are_interned() {
+        let (func, symbols) = parse_with_symbols("y * x + sum(k, 1, 3, k * y)").unwrap();
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols.get("y"), Some(Symbol(0)));
+        assert_eq!(symbols.get("x"), Some(Symbol(1)));
+        assert_eq!(symbols.get("k"), Some(Symbol(2)));
+        assert_eq!(func.compile().evaluate(&[2.0, 3.0, 0.0]), Ok(18.0));
+        assert!(parse_with_symbols("y *").is_err());
+    }
+
+    #[test]
+    fn constant_expressions_are_detected() {
+        let func = parse("3+4").unwrap();
+        assert!(func.is_constant());
+        assert_eq!(func.eval_const(), Some(7.0));
+
+        let func = parse("x+1").unwrap();
+        assert!(!func.is_constant());
+        assert_eq!(func.eval_const(), None);
+
+        let func = parse("sum(k, 1, 3, k) * pi").unwrap();
+        assert!(func.is_constant());
+        assert_eq!(func.eval_const(), Some(6.0 * PI));
+
+        // Constant, but it doesn't evaluate.
+        let func = parse("ln(-1)").unwrap();
+        assert!(func.is_constant());
+        assert_eq!(func.eval_const(), None);
+    }
+
+    #[test]
+    fn sums() {
+        let mut vars_map = HashMap::new();
+        vars_map.insert("n".to_string(), 4.0);
+
+        eval_test!("sum(k,1,3,k)", 6.0);
+        eval_test!("sum(k, 1, 5, k^2)", 55.0);
+        eval_test!("sum( i , 0 , n , 2 ) + 1", 11.0, &vars_map);
+        eval_test!("sum(k, 1, n, sum(j, 1, k, 1))", 10.0, &vars_map);
+        // An empty sum is zero.
+        eval_test!("sum(k, 3, 1, k)", 0.0);
+    }
+
+    #[test]
+    fn products() {
+        let mut vars_map = HashMap::new();
+        vars_map.insert("n".to_string(), 5.0);
+
+        eval_test!("prod(k,1,4,k)", 24.0);
+        eval_test!("prod(k, 1, n, 2)", 32.0, &vars_map);
+        eval_test!("prod(k, 1, 3, sum(j, 1, k, j))", 18.0);
+        // An empty product is one.
+        eval_test!("prod(k,1,0,k)", 1.0);
+        assert_eq!(parse("prod(k, 1, n, k)").unwrap().variables(), vec!["n".to_string()].into_iter().collect());
+    }
+
+    #[test]
+    fn special_functions() {
+        approx_eval_test!("erf(0)", 0.0, 1.5e-7);
+        approx_eval_test!("erf(10)", 1.0, 1.5e-7);
+        approx_eval_test!("erf(-10)", -1.0, 1.5e-7);
+        approx_eval_test!("erf(0.5)", 0.520_499_877_813_046_5, 1.5e-7);
+        approx_eval_test!("gamma(5)", 24.0, 1e-10);
+        approx_eval_test!("gamma(0.5)", std::f64::consts::PI.sqrt(), 1e-12);
+        approx_eval_test!("gamma(-0.5)", -2.0 * std::f64::consts::PI.sqrt(), 1e-12);
+        assert!(parse_expr("gamma(-2)").unwrap().1.evaluate(&HashMap::new()).unwrap().is_nan());
+    }
+
+    #[test]
+    fn custom_function_calls() {
+        let mut ctx = EvaluationContext::new();
+        ctx.register_function("double", 1, |args| Ok(2.0 * args[0]));
+        ctx.register_function("hypot", 2, |args| Ok(args[0].hypot(args[1])));
+        ctx.variables.insert("x".to_string(), 4.0);
+
+        let func = parse("double(x)+1").unwrap();
+        assert_eq!(func.evaluate_ctx(&ctx), Ok(9.0));
+        let func = parse("hypot(3, x) * 2").unwrap();
+        assert_eq!(func.evaluate_ctx(&ctx), Ok(10.0));
+        // Single letters are still implicit multiplication.
+        let func = parse("x(x+1)").unwrap();
+        assert_eq!(func.evaluate_ctx(&ctx), Ok(20.0));
+        // Built-in functions take precedence.
+        let func = parse("sin(0)").unwrap();
+        assert_eq!(func.evaluate_ctx(&ctx), Ok(0.0));
+    }
+
+    #[test]
+    fn built_trees_match_parsed_ones() {
+        use sexe_expression::builder::*;
+
+        assert_eq!(
+            parse("sin(x) * 2 + 3"),
+            Ok(sin(var("x")) * constant(2.0) + constant(3.0))
+        );
+        assert_eq!(
+            parse("-(x + 1) / log(x, 2)"),
+            // A leading `-` negates the whole quotient.
+            Ok(-((var("x") + constant(1.0)) / log(constant(2.0), var("x"))))
+        );
+        assert_eq!(
+            parse("x^2 - sum(k, 1, n, k) * abs(y)"),
+            Ok(var("x").pow(constant(2.0)) - sum("k", constant(1.0), var("n"), var("k")) * abs(var("y")))
+        );
+    }
+
+    #[test]
+    fn angle_units() {
+        approx_eval_test!("sin(90deg)", 1.0, 1e-15);
+        approx_eval_test!("180deg", PI, 1e-15);
+        approx_eval_test!("200grad", PI, 1e-15);
+        eval_test!("2rad", 2.0);
+        approx_eval_test!("cos(0.5deg) * 2", 2.0 * (PI / 360.0).cos(), 1e-15);
+        approx_eval_test!("-45deg", -PI / 4.0, 1e-15);
+        approx_eval_test!("2 * 90deg", PI, 1e-15);
+
+        let mut vars_map = HashMap::new();
+        vars_map.insert("degree".to_string(), 3.0);
+        vars_map.insert("x".to_string(), 2.0);
+        eval_test!("2degree", 6.0, &vars_map);
+        eval_test!("x(90deg)", PI, &vars_map);
+    }
+
+    #[test]
+    fn function_tables() {
+        use sexe_expression::builder::*;
+
+        let mut table = FunctionTable::default();
+        table.insert("sine", FunctionKind::Unary(UnaryOperator::Sin));
+        table.insert("f", FunctionKind::Call("double".to_string()));
+        table.insert("sinh", FunctionKind::Call("sinh".to_string()));
+        assert_eq!(table.get("f"), Some(&FunctionKind::Call("double".to_string())));
+        assert_eq!(table.get("g"), None);
+
+        assert_eq!(parse_with_table("sine(x)", &table), parse("sin(x)"));
+        // Without the table, `f(x)` is `f * x`.
+        assert_eq!(parse_with_table("f(x)", &table), Ok(call("double", vec![var("x")])));
+        assert_eq!(parse("f(x)"), Ok(var("f") * var("x")));
+        // The longest name wins, whichever prefix of it was added first.
+        assert_eq!(parse_with_table("sinh(x)", &table), Ok(call("sinh", vec![var("x")])));
+        assert_eq!(parse_with_table("sin(x) + log10(x)", &table), parse("sin(x) + log10(x)"));
+
+        let mut table = FunctionTable::empty();
+        table.insert("log", FunctionKind::Log);
+        assert_eq!(parse_with_table("log(8, 2)", &table), parse("log(8, 2)"));
+        // `sin` isn't a function anymore, so this is a custom function call.
+        assert_eq!(parse_with_table("sin(x)", &table), Ok(call("sin", vec![var("x")])));
+
+        // The table only lasts for the one parse.
+        assert_eq!(parse("sine(x)"), Ok(call("sine", vec![var("x")])));
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        assert_eq!(parse("  3 + 4  ").unwrap().evaluate(&HashMap::new()), Ok(7.0));
+        assert_eq!(parse("\tsin(0)\n"), parse("sin(0)"));
+        assert_eq!(parse(" |x| "), parse("|x|"));
+        assert_eq!(parse("3 + 4 x y"), Err(()));
+        assert_eq!(parse("3 + 4 )"), Err(()));
+        assert_eq!(parse("   "), Err(()));
+    }
+
+    #[test]
+    fn prefixes() {
+        let no_implicit_multiplication = ParseOptions { implicit_multiplication: false };
+
+        let (func, end) = parse_prefix("sin(x) from 0 to 10").unwrap();
+        assert_eq!(end, 11);
+        assert_eq!(func, parse("sin(x) from").unwrap());
+        let (func, end) = parse_prefix_with_options("sin(x) from 0 to 10", no_implicit_multiplication).unwrap();
+        assert_eq!(end, 6);
+        assert_eq!(func, parse("sin(x)").unwrap());
+
+        // Operators without an operand are left over, along with the whitespace before them.
+        assert_eq!(parse_prefix("sin(x)+ ,").unwrap().1, 6);
+        assert_eq!(parse_prefix("x * ").unwrap().1, 1);
+        assert_eq!(parse_prefix("2 ^)").unwrap().1, 1);
+        assert_eq!(parse_prefix("sin(x)+ from").unwrap().1, 12);
+        assert_eq!(parse_prefix("  (x + 1)  ;").unwrap().1, 9);
+        assert_eq!(parse_prefix("x"), Ok((parse("x").unwrap(), 1)));
+
+        assert_eq!(parse_prefix(""), Err(()));
+        assert_eq!(parse_prefix("+ x"), Err(()));
+        assert_eq!(parse_prefix(") x"), Err(()));
+    }
+
+    #[test]
+    fn negations_after_whitespace() {
+        let vars = HashMap::new();
+        assert_eq!(parse("3 + -2").unwrap().evaluate(&vars), Ok(1.0));
+        assert_eq!(parse("3 - - 2").unwrap().evaluate(&vars), Ok(5.0));
+        assert_eq!(parse("( -2)").unwrap().evaluate(&vars), Ok(-2.0));
+        assert_eq!(parse("log(8, -2)"), parse("log(8,-2)"));
+        assert_eq!(parse("sum(k, -1, 1, k)").unwrap().evaluate(&vars), Ok(0.0));
+    }
+
+    #[test]
+    fn overflowing_numbers_are_rejected() {
+        assert_eq!(parse("1e308").unwrap().evaluate(&HashMap::new()), Ok(1e308));
+        assert_eq!(parse("1e400"), Err(()));
+        assert_eq!(parse("x + 1e400"), Err(()));
+    }
+
+    #[test]
+    fn abs_bars() {
+        let mut vars_map = HashMap::new();
+        vars_map.insert("x".to_string(), -3.0);
+        vars_map.insert("y".to_string(), 5.0);
+
+        eval_test!("|x|", 3.0, &vars_map);
+        eval_test!("|3|+|-4|", 7.0);
+        eval_test!("|x| + |y|", 8.0, &vars_map);
+        eval_test!("|x||y|", 15.0, &vars_map);
+        eval_test!("2|x|", 6.0, &vars_map);
+        eval_test!("||x|-1|", 2.0, &vars_map);
+        eval_test!("|x - |y||", 8.0, &vars_map);
+        eval_test!("| |x| - |y| |", 2.0, &vars_map);
+        assert_eq!(parse("|x"), Err(()));
+        assert_eq!(parse("|x|y|"), Err(()));
+    }
+
+    #[test]
+    fn implicit_multiplication_can_be_disabled() {
+        let explicit = ParseOptions { implicit_multiplication: false };
+        let mut vars_map = HashMap::new();
+        vars_map.insert("x".to_string(), 10.0);
+
+        assert_eq!(parse("3x").unwrap().evaluate(&vars_map), Ok(30.0));
+        assert_eq!(parse_with_options("3x", ParseOptions::default()), parse("3x"));
+        assert_eq!(parse_with_options("3x", explicit), Err(()));
+        assert_eq!(parse_with_options("2(x+1)", explicit), Err(()));
+        assert_eq!(parse_with_options("3*x", explicit), parse("3x"));
+        assert_eq!(parse_with_options("ln(e)", explicit).unwrap().evaluate(&vars_map), Ok(1.0));
+
+        // The option only lasts for the one parse.
+        assert!(parse("3x").is_ok());
     }
 
     #[test]
@@ -373,8 +961,18 @@ mod test {
         vars_map.insert("x".to_string(), 10.0);
         vars_map.insert("foo".to_string(), 10.0);
 
-        error_test!("log(3,9,5)", EvaluationError::WrongNumberOfArgsError);
-        error_test!("log(3,    9   ,5)", EvaluationError::WrongNumberOfArgsError);
+        error_test!("log(3,9,5)", EvaluationError::WrongNumberOfArgsError { expected: 2, got: 3 });
+        error_test!("log(3,    9   ,5)", EvaluationError::WrongNumberOfArgsError { expected: 2, got: 3 });
+        error_test!("log(1,2,3)", EvaluationError::WrongNumberOfArgsError { expected: 2, got: 3 });
+        error_test!("log(8)", EvaluationError::WrongNumberOfArgsError { expected: 2, got: 1 });
         error_test!("y", EvaluationError::VariableNotFoundError, &vars_map);
+        error_test!("ln(-1)", EvaluationError::DomainError);
+        error_test!("log(-1, 2)", EvaluationError::DomainError);
+        error_test!("log2(0) + x", EvaluationError::DomainError, &vars_map);
+        error_test!(
+            "foo(x)",
+            EvaluationError::UnknownFunction { name: "foo".to_string() },
+            &vars_map
+        );
     }
 }
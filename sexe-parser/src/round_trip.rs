@@ -0,0 +1,147 @@
+//! Checks that printing an expression and parsing the text again gives back the same tree, for
+//! randomly generated trees of every shape the parser can produce.
+
+use std::f64::consts::{E, PI};
+
+use sexe_expression::*;
+
+use crate::parse;
+
+/// How many random trees `display_round_trips_through_parse` checks.
+const CASES: u32 = 5000;
+
+/// A xorshift generator, so that every run checks the same trees and a failure can be reproduced
+/// from its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number from `0` up to but excluding `n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+/// Names of variables, including some that start like a keyword or a function without being one.
+const VARIABLES: &[&str] = &["x", "y", "t", "theta", "k", "ex", "pie", "model", "deg", "sinh", "sum", "prod", "log"];
+
+/// Names of custom functions, which mustn't be in the default `FunctionTable` or be `sum`.
+const FUNCTIONS: &[&str] = &["double", "foo", "expo", "pix", "logb"];
+
+const UNARY_OPERATORS: &[UnaryOperator] = &[
+    UnaryOperator::Sin,
+    UnaryOperator::Asin,
+    UnaryOperator::Cos,
+    UnaryOperator::Acos,
+    UnaryOperator::Tan,
+    UnaryOperator::Ctan,
+    UnaryOperator::Asinh,
+    UnaryOperator::Acosh,
+    UnaryOperator::Atanh,
+    UnaryOperator::Abs,
+    UnaryOperator::Exp,
+    UnaryOperator::Log2,
+    UnaryOperator::Log10,
+    UnaryOperator::Ln,
+    UnaryOperator::Ceil,
+    UnaryOperator::Floor,
+    UnaryOperator::Fract,
+    UnaryOperator::Erf,
+    UnaryOperator::Gamma,
+    UnaryOperator::IsNan,
+    UnaryOperator::IsInf,
+    UnaryOperator::IsFinite,
+];
+
+const BINARY_OPERATORS: &[BinaryOperator] = &[
+    BinaryOperator::Addition,
+    BinaryOperator::Subtraction,
+    BinaryOperator::Multiplication,
+    BinaryOperator::Division,
+    BinaryOperator::Modulo,
+    BinaryOperator::Exponentiation,
+];
+
+/// A constant the parser can produce. Number literals have no sign, so the value is never
+/// negative, but it may be any finite size, `e` or `pi`, or an angle converted to radians.
+fn arbitrary_constant(rng: &mut Rng) -> f64 {
+    match rng.below(6) {
+        0 => rng.below(100) as f64,
+        1 => rng.below(100_000) as f64 / 1000.0,
+        2 => E,
+        3 => PI,
+        4 => rng.below(360) as f64 * PI / 180.0,
+        _ => loop {
+            // Any bit pattern without the sign bit, other than infinity and NaN.
+            let value = f64::from_bits(rng.next() >> 1);
+            if value.is_finite() {
+                break value;
+            }
+        },
+    }
+}
+
+/// A random tree of at most `depth` levels below its root, in the shape the parser would produce
+/// for some input.
+fn arbitrary_expression(rng: &mut Rng, depth: u32) -> ExpressionNode {
+    let leaf = depth == 0 || rng.below(4) == 0;
+    match if leaf { rng.below(2) } else { 2 + rng.below(6) } {
+        0 => ExpressionNode::ConstantExprNode { value: arbitrary_constant(rng) },
+        1 => ExpressionNode::VariableExprNode { variable_key: rng.pick(VARIABLES).to_string() },
+        2 | 3 => ExpressionNode::BinaryExprNode {
+            operator: BINARY_OPERATORS[rng.below(BINARY_OPERATORS.len())].clone(),
+            left_node: Box::new(arbitrary_expression(rng, depth - 1)),
+            right_node: Box::new(arbitrary_expression(rng, depth - 1)),
+        },
+        4 => ExpressionNode::UnaryExprNode {
+            operator: UnaryOperator::Negation,
+            child_node: Box::new(arbitrary_expression(rng, depth - 1)),
+        },
+        5 => ExpressionNode::UnaryExprNode {
+            operator: UNARY_OPERATORS[rng.below(UNARY_OPERATORS.len())].clone(),
+            child_node: Box::new(arbitrary_expression(rng, depth - 1)),
+        },
+        6 => {
+            let index_key = rng.pick(VARIABLES).to_string();
+            let operator = match rng.below(3) {
+                0 => NaryOperator::Log,
+                1 => NaryOperator::Sum { index_key },
+                _ => NaryOperator::Product { index_key },
+            };
+            // Sums and products always have their bounds and body, while `log` takes any number
+            // of arguments until it is evaluated.
+            let count = match operator {
+                NaryOperator::Sum { .. } | NaryOperator::Product { .. } => 3,
+                NaryOperator::Log => rng.below(4),
+            };
+            ExpressionNode::NaryExprNode {
+                operator,
+                child_nodes: (0..count).map(|_| arbitrary_expression(rng, depth - 1)).collect(),
+            }
+        }
+        _ => ExpressionNode::FunctionCallNode {
+            name: rng.pick(FUNCTIONS).to_string(),
+            args: (0..rng.below(3)).map(|_| arbitrary_expression(rng, depth - 1)).collect(),
+        },
+    }
+}
+
+#[test]
+fn display_round_trips_through_parse() {
+    let mut rng = Rng(0x5e8e_d15b_1a70_0001);
+    for case in 0..CASES {
+        let expression = arbitrary_expression(&mut rng, 5);
+        let text = expression.to_string();
+        assert_eq!(parse(&text), Ok(expression), "case {} was printed as `{}`", case, text);
+    }
+}
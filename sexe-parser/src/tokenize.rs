@@ -0,0 +1,198 @@
+use std::ops::Range;
+
+use crate::custom_combinators::recognize_float;
+use crate::RESERVED_WORDS;
+
+/// What a `Token` is, e.g. for highlighting it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TokenKind {
+    /// A number, as the parser reads it, e.g. `3`, `.5` or `1.5e3`. Signs are operators.
+    Number,
+    /// A run of letters, which is the name of a variable, a function or a constant, e.g. `sin`.
+    Identifier,
+    /// `+`, `-`, `*`, `**`, `/`, `%`, `^`, the word `mod`, or the `_` of `log_2`.
+    Operator,
+    LParen,
+    RParen,
+    Comma,
+    /// The `|` of `|x|`.
+    Bar,
+    Whitespace,
+}
+
+/// A piece of the input of `tokenize`, with the range of bytes it spans.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+/// A character that no token starts with, e.g. `$`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LexError {
+    /// The byte offset of the character in the input.
+    pub position: usize,
+    pub character: char,
+}
+
+/// Splits `input` into tokens, e.g. for syntax highlighting. The tokens cover the whole input
+/// without gaps, including its whitespace, so the spans of the tokens put together are `input`.
+/// This doesn't check that the tokens form an expression; only `parse` does that.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while let Some(c) = input[position..].chars().next() {
+        let rest = &input[position..];
+        let (kind, len) = if let Ok((after, _)) = recognize_float(rest) {
+            (TokenKind::Number, rest.len() - after.len())
+        } else if is_letter(c) {
+            let len = rest.find(|c| !is_letter(c)).unwrap_or(rest.len());
+            let kind = if RESERVED_WORDS.contains(&&rest[..len]) {
+                TokenKind::Operator
+            } else {
+                TokenKind::Identifier
+            };
+            (kind, len)
+        } else if c.is_whitespace() {
+            let len = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len());
+            (TokenKind::Whitespace, len)
+        } else if rest.starts_with("**") {
+            (TokenKind::Operator, 2)
+        } else {
+            let kind = match c {
+                '+' | '-' | '*' | '/' | '%' | '^' | '_' => TokenKind::Operator,
+                '(' => TokenKind::LParen,
+                ')' => TokenKind::RParen,
+                ',' => TokenKind::Comma,
+                '|' => TokenKind::Bar,
+                _ => return Err(LexError { position, character: c }),
+            };
+            (kind, c.len_utf8())
+        };
+        tokens.push(Token { kind, span: position..position + len });
+        position += len;
+    }
+    Ok(tokens)
+}
+
+/// Whether `c` can be part of a name. `π` is a name by itself, as the parser reads it as `pi`.
+fn is_letter(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == 'π'
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|token| (token.kind, &input[token.span]))
+            .collect()
+    }
+
+    #[test]
+    fn tokens_have_kinds() {
+        use self::TokenKind::*;
+
+        assert_eq!(
+            kinds("3x**2 - sin(.5e3)"),
+            vec![
+                (Number, "3"),
+                (Identifier, "x"),
+                (Operator, "**"),
+                (Number, "2"),
+                (Whitespace, " "),
+                (Operator, "-"),
+                (Whitespace, " "),
+                (Identifier, "sin"),
+                (LParen, "("),
+                (Number, ".5e3"),
+                (RParen, ")"),
+            ]
+        );
+        assert_eq!(
+            kinds("log_2(|x|, 7 mod 2)"),
+            vec![
+                (Identifier, "log"),
+                (Operator, "_"),
+                (Number, "2"),
+                (LParen, "("),
+                (Bar, "|"),
+                (Identifier, "x"),
+                (Bar, "|"),
+                (Comma, ","),
+                (Whitespace, " "),
+                (Number, "7"),
+                (Whitespace, " "),
+                (Operator, "mod"),
+                (Whitespace, " "),
+                (Number, "2"),
+                (RParen, ")"),
+            ]
+        );
+        assert_eq!(kinds("2π"), vec![(Number, "2"), (Identifier, "π")]);
+        assert_eq!(kinds(""), vec![]);
+    }
+
+    #[test]
+    fn implicit_products_and_calls_are_split() {
+        use self::TokenKind::*;
+
+        assert_eq!(
+            kinds("3x + sin(y)"),
+            vec![
+                (Number, "3"),
+                (Identifier, "x"),
+                (Whitespace, " "),
+                (Operator, "+"),
+                (Whitespace, " "),
+                (Identifier, "sin"),
+                (LParen, "("),
+                (Identifier, "y"),
+                (RParen, ")"),
+            ]
+        );
+        assert_eq!(
+            kinds("2e(x)"),
+            vec![(Number, "2"), (Identifier, "e"), (LParen, "("), (Identifier, "x"), (RParen, ")")]
+        );
+    }
+
+    #[test]
+    fn spans_cover_the_input() {
+        let inputs = [
+            "3-(2-3+1)+(4-1+4)",
+            "3 -   (2  -  3 + 1   ) + (  4 - 1    +4 )",
+            "2*2/(5-1)+3",
+            "-x*sin(0)",
+            "3^(-3)",
+            "(((2(4)))))",
+            "log(3,    9   ,5)",
+            "sin (   0   )",
+            "sum(k, 1, 5, k^2)",
+            "|x - |y||",
+            "\tln(e)\n",
+            "90deg + 1.5E3 % 2.",
+            "arcsin(x) ** π",
+        ];
+        for input in &inputs {
+            let tokens = tokenize(input).unwrap();
+            let mut end = 0;
+            for token in &tokens {
+                assert_eq!(token.span.start, end, "{:?} has a gap before {:?}", input, token);
+                end = token.span.end;
+            }
+            assert_eq!(end, input.len());
+            let rebuilt: String = tokens.iter().map(|token| &input[token.span.clone()]).collect();
+            assert_eq!(&rebuilt, input);
+        }
+    }
+
+    #[test]
+    fn unknown_characters_are_errors() {
+        assert_eq!(tokenize("3 $ 4"), Err(LexError { position: 2, character: '$' }));
+        assert_eq!(tokenize("π + é"), Err(LexError { position: 5, character: 'é' }));
+    }
+}
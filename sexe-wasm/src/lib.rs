@@ -0,0 +1,139 @@
+//! Bindings for parsing and evaluating expressions from JavaScript, e.g. in a web page:
+//!
+//! ```js
+//! import { parse } from "sexe_wasm";
+//!
+//! const expression = parse("sin(x) * a");
+//! expression.evaluate({ x: 1.5, a: 2 });
+//! // [x0, y0, x1, y1, ...]
+//! const points = expression.sample(0, 10, 100);
+//! ```
+//!
+//! Errors are thrown as `ExpressionError` objects, which have a `message` and, for parse errors,
+//! the `offset` in the input where parsing stopped.
+
+use std::collections::HashMap;
+
+use js_sys::{Array, Float64Array, Object};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use sexe_expression::{EvaluationError, ExpressionNode};
+
+/// An error thrown to JavaScript.
+#[wasm_bindgen]
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExpressionError {
+    message: String,
+    offset: Option<usize>,
+}
+
+#[wasm_bindgen]
+impl ExpressionError {
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    /// Where in the input parsing stopped, in UTF-16 code units like the indices of JavaScript
+    /// strings. `undefined` for errors that aren't about the input, e.g. evaluation errors.
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+
+impl ExpressionError {
+    fn new(message: String) -> ExpressionError {
+        ExpressionError { message, offset: None }
+    }
+}
+
+/// A parsed expression.
+#[wasm_bindgen]
+pub struct JsExpression {
+    tree: ExpressionNode,
+}
+
+/// Parses `expr`, throwing an `ExpressionError` at the end of the longest expression it starts
+/// with if it isn't a whole one.
+#[wasm_bindgen]
+pub fn parse(expr: &str) -> Result<JsExpression, JsValue> {
+    match sexe_parser::parse(expr) {
+        Ok(tree) => Ok(JsExpression { tree }),
+        Err(()) => {
+            let end = match sexe_parser::parse_prefix(expr) {
+                Ok((_, end)) => end,
+                Err(()) => expr.len() - expr.trim_start().len(),
+            };
+            let rest = expr[end..].trim();
+            let message = if rest.is_empty() {
+                String::from("expected an expression")
+            } else {
+                format!("could not parse `{}`", rest)
+            };
+            let offset = expr[..end].encode_utf16().count();
+            Err(ExpressionError { message, offset: Some(offset) }.into())
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl JsExpression {
+    /// Evaluates the expression with the variables of `vars`, an object from their names to
+    /// numbers. `vars` may be left out if the expression has no variables.
+    pub fn evaluate(&self, vars: JsValue) -> Result<f64, JsValue> {
+        let vars = variables(&vars)?;
+        self.tree
+            .evaluate(&vars)
+            .map_err(|error| ExpressionError::new(describe(&error)).into())
+    }
+
+    /// Samples the expression at `resolution` values of `x` from `start` to `end`, as the
+    /// interleaved pairs `[x0, y0, x1, y1, ...]`. Points that fail to evaluate are left out.
+    pub fn sample(&self, start: f64, end: f64, resolution: u32) -> Float64Array {
+        let points = sexe_expression::evaluate_function_over_domain(start, end, resolution, &self.tree);
+        let interleaved: Vec<f64> = points.iter().flat_map(|&(x, y)| vec![x, y]).collect();
+        Float64Array::from(&interleaved[..])
+    }
+
+    /// The expression as the parser would read it, e.g. `(x + 1) * 2`.
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        self.tree.to_string()
+    }
+}
+
+/// Reads an object of variable values, where `undefined` and `null` mean there aren't any.
+fn variables(vars: &JsValue) -> Result<HashMap<String, f64>, ExpressionError> {
+    let mut map = HashMap::new();
+    if vars.is_undefined() || vars.is_null() {
+        return Ok(map);
+    }
+    let object = vars
+        .dyn_ref::<Object>()
+        .ok_or_else(|| ExpressionError::new(String::from("the variables must be an object")))?;
+    for entry in Object::entries(object).iter() {
+        let entry = Array::from(&entry);
+        let name = entry.get(0).as_string().unwrap_or_default();
+        let value = entry
+            .get(1)
+            .as_f64()
+            .ok_or_else(|| ExpressionError::new(format!("the value of `{}` isn't a number", name)))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+fn describe(error: &EvaluationError) -> String {
+    match error {
+        EvaluationError::VariableNotFoundError => String::from("a variable has no value"),
+        EvaluationError::WrongNumberOfArgsError { expected, got } => {
+            format!("expected {} arguments, but got {}", expected, got)
+        }
+        EvaluationError::NotANumberError => String::from("the result is not a number"),
+        EvaluationError::UnknownFunction { name } => format!("unknown function `{}`", name),
+        EvaluationError::DomainError => String::from("a function was applied outside of its domain"),
+        EvaluationError::NotDifferentiable => String::from("the expression is not differentiable"),
+    }
+}
@@ -0,0 +1,59 @@
+//! Run with `wasm-pack test --node sexe-wasm`.
+
+use js_sys::{Object, Reflect};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+
+use sexe_wasm::parse;
+
+fn vars(pairs: &[(&str, f64)]) -> JsValue {
+    let object = Object::new();
+    for (name, value) in pairs {
+        Reflect::set(&object, &JsValue::from_str(name), &JsValue::from_f64(*value)).unwrap();
+    }
+    object.into()
+}
+
+fn property(error: &JsValue, name: &str) -> JsValue {
+    Reflect::get(error, &JsValue::from_str(name)).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn expressions_are_parsed() {
+    assert_eq!(parse("(x+1) * 2").unwrap().to_js_string(), "(x + 1) * 2");
+
+    let error = parse("sin(x) + )").err().unwrap();
+    assert_eq!(property(&error, "message").as_string().unwrap(), "could not parse `+ )`");
+    assert_eq!(property(&error, "offset").as_f64(), Some(6.0));
+
+    // Offsets count UTF-16 code units, like JavaScript strings do.
+    let error = parse("2π )").err().unwrap();
+    assert_eq!(property(&error, "offset").as_f64(), Some(2.0));
+
+    let error = parse("  ").err().unwrap();
+    assert_eq!(property(&error, "message").as_string().unwrap(), "expected an expression");
+}
+
+#[wasm_bindgen_test]
+fn expressions_are_evaluated() {
+    let expression = parse("x * a + 1").unwrap();
+    assert_eq!(expression.evaluate(vars(&[("x", 2.0), ("a", 3.0)])), Ok(7.0));
+    assert_eq!(parse("2 + 3").unwrap().evaluate(JsValue::UNDEFINED), Ok(5.0));
+
+    let error = expression.evaluate(vars(&[("x", 2.0)])).err().unwrap();
+    assert_eq!(property(&error, "message").as_string().unwrap(), "a variable has no value");
+    assert!(property(&error, "offset").is_undefined());
+
+    let error = expression.evaluate(JsValue::from_f64(1.0)).err().unwrap();
+    assert_eq!(property(&error, "message").as_string().unwrap(), "the variables must be an object");
+}
+
+#[wasm_bindgen_test]
+fn expressions_are_sampled() {
+    let points = parse("x^2").unwrap().sample(0.0, 2.0, 2).to_vec();
+    assert_eq!(points, vec![0.0, 0.0, 1.0, 1.0]);
+
+    // ln(0) fails, so the first point is left out.
+    let points = parse("ln(x)").unwrap().sample(0.0, 2.0, 2).to_vec();
+    assert_eq!(points, vec![1.0, 0.0]);
+}
@@ -0,0 +1,253 @@
+//! The headless mode, which prints the sampled points of a function instead of plotting them, e.g.
+//! `echo "sin(x)" | sexe --sample 0 10 100`.
+
+use io;
+use io::Read;
+use std::fmt;
+use std::process;
+
+use sexe_expression as expression;
+use sexe_parser as parser;
+
+const USAGE: &str = "usage: sexe [--config <file>]
+       sexe --sample <start> <end> <resolution> [--eval <function>] [--format json|tsv|csv]";
+
+/// The most points `--sample` samples. Every point is kept in memory before it is printed, so
+/// larger resolutions are rejected rather than exhausting it.
+pub const MAX_SAMPLE_RESOLUTION: u32 = 10_000_000;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UsageError,
+    ParseError,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UsageError => write!(f, "{}", USAGE),
+            Error::ParseError => write!(f, "the function could not be parsed"),
+        }
+    }
+}
+
+/// How the sampled points are printed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputFormat {
+    /// One line of tab-separated `x` and `y` per point.
+    Tsv,
+    /// One line of comma-separated `x` and `y` per point.
+    Csv,
+    /// A JSON array of `[x, y]` pairs.
+    Json,
+}
+
+/// What to do, as given by the command line arguments.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Display the interface, starting with the config file at the path, if any.
+    Display(Option<String>),
+    /// Sample without displaying the interface.
+    Sample(SampleArgs),
+}
+
+/// What to sample in the headless mode.
+#[derive(Debug, PartialEq)]
+pub struct SampleArgs {
+    start_x: f64,
+    end_x: f64,
+    resolution: u32,
+    /// The function given with `--eval`. Without it, the function is read from stdin.
+    function: Option<String>,
+    format: OutputFormat,
+}
+
+/// Parses the command line arguments, without the program name. The interface is displayed
+/// unless `--sample` is given, and the options of the two modes can't be mixed.
+pub fn parse_args(args: &[String]) -> Result<Command, Error> {
+    let mut sample = None;
+    let mut function = None;
+    let mut format = None;
+    let mut config = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sample" => {
+                let mut next_number = || args.next().and_then(|arg| arg.parse::<f64>().ok());
+                let start_x = next_number().ok_or(Error::UsageError)?;
+                let end_x = next_number().ok_or(Error::UsageError)?;
+                let resolution = next_number()
+                    .filter(|n| {
+                        n.fract() == 0.0 && *n >= 0.0 && *n <= f64::from(MAX_SAMPLE_RESOLUTION)
+                    })
+                    .ok_or(Error::UsageError)?;
+                sample = Some((start_x, end_x, resolution as u32));
+            }
+            "--eval" => function = Some(args.next().ok_or(Error::UsageError)?.clone()),
+            "--format" => {
+                format = match args.next().map(String::as_str) {
+                    Some("tsv") => Some(OutputFormat::Tsv),
+                    Some("csv") => Some(OutputFormat::Csv),
+                    Some("json") => Some(OutputFormat::Json),
+                    _ => return Err(Error::UsageError),
+                }
+            }
+            "--config" => config = Some(args.next().ok_or(Error::UsageError)?.clone()),
+            _ => return Err(Error::UsageError),
+        }
+    }
+
+    match sample {
+        Some((start_x, end_x, resolution)) if config.is_none() => Ok(Command::Sample(SampleArgs {
+            start_x,
+            end_x,
+            resolution,
+            function,
+            format: format.unwrap_or(OutputFormat::Tsv),
+        })),
+        None if function.is_none() && format.is_none() => Ok(Command::Display(config)),
+        _ => Err(Error::UsageError),
+    }
+}
+
+/// Samples the function of `args`, reading it from stdin if it wasn't given, and prints the
+/// points to stdout. If the function can't be parsed, the process exits with an error message.
+pub fn sample(args: SampleArgs) -> Result<(), io::Error> {
+    let function = match args.function {
+        Some(function) => function,
+        None => {
+            let mut function = String::new();
+            io::stdin().read_to_string(&mut function)?;
+            function
+        }
+    };
+
+    match run_sample(&function, args.start_x, args.end_x, args.resolution, args.format) {
+        Ok(points) => {
+            print!("{}", points);
+            Ok(())
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/// Samples `function` over the domain from `start_x` to `end_x`, formatting the points as `format`.
+/// Points that fail to evaluate are omitted.
+pub fn run_sample(
+    function: &str,
+    start_x: f64,
+    end_x: f64,
+    resolution: u32,
+    format: OutputFormat,
+) -> Result<String, Error> {
+    // A trailing newline, e.g. from `echo`, isn't part of the function.
+    let func = parser::parse(function.trim()).map_err(|_| Error::ParseError)?;
+    let points = expression::evaluate_function_over_domain(start_x, end_x, resolution, &func);
+    Ok(format_points(&points, format))
+}
+
+/// Formats `points` as `format`. Points that aren't finite are omitted, since JSON can't represent
+/// them and other tools are unlikely to parse them.
+pub fn format_points(points: &[(f64, f64)], format: OutputFormat) -> String {
+    let finite = points.iter().filter(|(x, y)| x.is_finite() && y.is_finite());
+    match format {
+        OutputFormat::Tsv => finite.map(|(x, y)| format!("{}\t{}\n", x, y)).collect(),
+        OutputFormat::Csv => finite.map(|(x, y)| format!("{},{}\n", x, y)).collect(),
+        OutputFormat::Json => {
+            let pairs = finite.map(|(x, y)| format!("[{},{}]", x, y)).collect::<Vec<_>>();
+            format!("[{}]\n", pairs.join(","))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn samples_are_printed_as_tsv() {
+        assert_eq!(
+            run_sample("x^2\n", 0.0, 2.0, 3, OutputFormat::Tsv),
+            Ok(String::from(
+                "0\t0\n0.6666666666666666\t0.4444444444444444\n1.3333333333333333\t1.7777777777777777\n"
+            ))
+        );
+        // ln(0) is a domain error, so the first point is omitted.
+        assert_eq!(run_sample("ln(x)", 0.0, 2.0, 2, OutputFormat::Tsv), Ok(String::from("1\t0\n")));
+        assert_eq!(run_sample("x +", 0.0, 2.0, 3, OutputFormat::Tsv), Err(Error::ParseError));
+    }
+
+    #[test]
+    fn arguments_are_parsed() {
+        assert_eq!(parse_args(&[]), Ok(Command::Display(None)));
+        assert_eq!(
+            parse_args(&args(&["--config", "sexe.json"])),
+            Ok(Command::Display(Some(String::from("sexe.json"))))
+        );
+        assert_eq!(
+            parse_args(&args(&["--sample", "0", "10", "100"])),
+            Ok(Command::Sample(SampleArgs {
+                start_x: 0.0,
+                end_x: 10.0,
+                resolution: 100,
+                function: None,
+                format: OutputFormat::Tsv,
+            }))
+        );
+        assert_eq!(
+            parse_args(&args(&["--eval", "sin(x)", "--sample", "-1.5", "1.5", "3"])),
+            Ok(Command::Sample(SampleArgs {
+                start_x: -1.5,
+                end_x: 1.5,
+                resolution: 3,
+                function: Some(String::from("sin(x)")),
+                format: OutputFormat::Tsv,
+            }))
+        );
+        assert_eq!(
+            parse_args(&args(&["--sample", "0", "1", "2", "--format", "json"])),
+            Ok(Command::Sample(SampleArgs {
+                start_x: 0.0,
+                end_x: 1.0,
+                resolution: 2,
+                function: None,
+                format: OutputFormat::Json,
+            }))
+        );
+        assert_eq!(parse_args(&args(&["--sample", "0", "10"])), Err(Error::UsageError));
+        assert_eq!(parse_args(&args(&["--sample", "0", "10", "2.5"])), Err(Error::UsageError));
+        assert_eq!(parse_args(&args(&["--sample", "0", "10", "4e9"])), Err(Error::UsageError));
+        assert_eq!(
+            parse_args(&args(&["--sample", "0", "10", "10000001"])),
+            Err(Error::UsageError)
+        );
+        assert!(parse_args(&args(&["--sample", "0", "10", "10000000"])).is_ok());
+        assert_eq!(parse_args(&args(&["--eval", "x"])), Err(Error::UsageError));
+        assert_eq!(parse_args(&args(&["--config"])), Err(Error::UsageError));
+        assert_eq!(
+            parse_args(&args(&["--config", "sexe.json", "--sample", "0", "1", "2"])),
+            Err(Error::UsageError)
+        );
+        assert_eq!(parse_args(&args(&["--plot"])), Err(Error::UsageError));
+        assert_eq!(
+            parse_args(&args(&["--sample", "0", "1", "2", "--format", "xml"])),
+            Err(Error::UsageError)
+        );
+    }
+
+    #[test]
+    fn points_are_formatted() {
+        let points = [(-1.0, 0.5), (0.0, f64::INFINITY), (0.5, f64::NAN), (1.0, -2.0)];
+        assert_eq!(format_points(&points, OutputFormat::Tsv), "-1\t0.5\n1\t-2\n");
+        assert_eq!(format_points(&points, OutputFormat::Csv), "-1,0.5\n1,-2\n");
+        assert_eq!(format_points(&points, OutputFormat::Json), "[[-1,0.5],[1,-2]]\n");
+        assert_eq!(format_points(&[], OutputFormat::Json), "[]\n");
+    }
+}
@@ -0,0 +1,242 @@
+//! The config file given with `--config`, which sets what the interface starts with, e.g.
+//! `{ "function": "sin(x) / x", "start_x": -10, "end_x": 10, "resolution_multiplier": 2 }`.
+
+use io;
+use std::fmt;
+use std::fs;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The most points that can be sampled per column of the terminal. Beyond this, the plot can't
+/// show more detail anyway, but sampling it gets slow.
+pub const MAX_RESOLUTION_MULTIPLIER: u32 = 64;
+
+/// What the interface starts with. Fields left out of the file are `None`, so that the defaults
+/// are used for them.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    pub function: Option<String>,
+    pub start_x: Option<f64>,
+    pub end_x: Option<f64>,
+    /// How many points are sampled per column of the terminal.
+    pub resolution_multiplier: Option<u32>,
+}
+
+#[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    /// The file couldn't be read, with the reason why.
+    ReadError(String),
+    /// The file isn't a JSON object of strings and numbers.
+    SyntaxError,
+    /// A field that isn't one of `Config`.
+    UnknownFieldError(String),
+    /// A field with a value of the wrong type, or out of range.
+    ValueError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ReadError(reason) => write!(f, "the config file could not be read: {}", reason),
+            Error::SyntaxError => write!(f, "the config file is not a JSON object"),
+            Error::UnknownFieldError(field) => {
+                write!(f, "unknown field \"{}\" in the config file", field)
+            }
+            Error::ValueError(field) => {
+                write!(f, "invalid value of \"{}\" in the config file", field)
+            }
+        }
+    }
+}
+
+/// The values of fields. Only strings and numbers are needed.
+enum Value {
+    String(String),
+    Number(f64),
+}
+
+/// Reads and parses the config file at `path`.
+pub fn load(path: &str) -> Result<Config, Error> {
+    let json = fs::read_to_string(path).map_err(|error: io::Error| {
+        Error::ReadError(format!("{}: {}", path, error))
+    })?;
+    parse_config(&json)
+}
+
+/// Parses a config from a JSON object. Every field is optional, but no others are allowed, so
+/// that misspelled ones aren't silently ignored.
+pub fn parse_config(json: &str) -> Result<Config, Error> {
+    let mut config = Config::default();
+    let mut chars = json.chars().peekable();
+    expect(&mut chars, '{')?;
+    if skip_whitespace(&mut chars) == Some('}') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            let field = parse_string(&mut chars)?;
+            expect(&mut chars, ':')?;
+            match (field.as_str(), parse_value(&mut chars)?) {
+                ("function", Value::String(function)) => config.function = Some(function),
+                ("start_x", Value::Number(start_x)) => config.start_x = Some(start_x),
+                ("end_x", Value::Number(end_x)) => config.end_x = Some(end_x),
+                ("resolution_multiplier", Value::Number(n))
+                    if n.fract() == 0.0 && n >= 1.0 && n <= f64::from(MAX_RESOLUTION_MULTIPLIER) =>
+                {
+                    config.resolution_multiplier = Some(n as u32)
+                }
+                ("function", _) | ("start_x", _) | ("end_x", _) | ("resolution_multiplier", _) => {
+                    return Err(Error::ValueError(field))
+                }
+                _ => return Err(Error::UnknownFieldError(field)),
+            }
+            match skip_whitespace(&mut chars) {
+                Some(',') => chars.next(),
+                Some('}') => break,
+                _ => return Err(Error::SyntaxError),
+            };
+        }
+        chars.next();
+    }
+    match skip_whitespace(&mut chars) {
+        None => Ok(config),
+        Some(_) => Err(Error::SyntaxError),
+    }
+}
+
+/// Skips to the next character that isn't whitespace, and returns it without consuming it.
+fn skip_whitespace(chars: &mut Peekable<Chars>) -> Option<char> {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    chars.peek().cloned()
+}
+
+/// Consumes `expected`, after any whitespace.
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), Error> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        _ => Err(Error::SyntaxError),
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, Error> {
+    match skip_whitespace(chars) {
+        Some('"') => parse_string(chars).map(Value::String),
+        Some(_) => parse_number(chars).map(Value::Number),
+        None => Err(Error::SyntaxError),
+    }
+}
+
+/// Parses a string in double quotes, with the escapes JSON allows.
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, Error> {
+    expect(chars, '"')?;
+    let mut string = String::new();
+    loop {
+        match chars.next().ok_or(Error::SyntaxError)? {
+            '"' => return Ok(string),
+            '\\' => {
+                let escaped = match chars.next().ok_or(Error::SyntaxError)? {
+                    c @ '"' | c @ '\\' | c @ '/' => c,
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == 4)
+                            .and_then(std::char::from_u32)
+                            .ok_or(Error::SyntaxError)?
+                    }
+                    _ => return Err(Error::SyntaxError),
+                };
+                string.push(escaped);
+            }
+            c => string.push(c),
+        }
+    }
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Result<f64, Error> {
+    let mut number = String::new();
+    while let Some(&c) = chars.peek() {
+        if !(c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E') {
+            break;
+        }
+        number.push(c);
+        chars.next();
+    }
+    number.parse().map_err(|_| Error::SyntaxError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configs_are_parsed() {
+        assert_eq!(
+            parse_config(
+                r#"{
+                    "function": "sin(x) / \"x\"²",
+                    "start_x": -10,
+                    "end_x": 2.5e1,
+                    "resolution_multiplier": 2
+                }"#
+            ),
+            Ok(Config {
+                function: Some(String::from("sin(x) / \"x\"²")),
+                start_x: Some(-10.0),
+                end_x: Some(25.0),
+                resolution_multiplier: Some(2),
+            })
+        );
+        assert_eq!(parse_config(" {} \n"), Ok(Config::default()));
+        assert_eq!(
+            parse_config(r#"{"end_x": 1}"#),
+            Ok(Config {
+                end_x: Some(1.0),
+                ..Config::default()
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_configs_are_rejected() {
+        assert_eq!(parse_config(""), Err(Error::SyntaxError));
+        assert_eq!(parse_config("[]"), Err(Error::SyntaxError));
+        assert_eq!(parse_config(r#"{"start_x": 1,}"#), Err(Error::SyntaxError));
+        assert_eq!(parse_config(r#"{"start_x": 1} {}"#), Err(Error::SyntaxError));
+        assert_eq!(parse_config(r#"{"function": "sin(x)"#), Err(Error::SyntaxError));
+        assert_eq!(
+            parse_config(r#"{"start": 1}"#),
+            Err(Error::UnknownFieldError(String::from("start")))
+        );
+        assert_eq!(
+            parse_config(r#"{"start_x": "1"}"#),
+            Err(Error::ValueError(String::from("start_x")))
+        );
+        assert_eq!(
+            parse_config(r#"{"resolution_multiplier": 1.5}"#),
+            Err(Error::ValueError(String::from("resolution_multiplier")))
+        );
+        assert_eq!(
+            parse_config(r#"{"resolution_multiplier": 0}"#),
+            Err(Error::ValueError(String::from("resolution_multiplier")))
+        );
+        assert_eq!(
+            parse_config(r#"{"resolution_multiplier": 65}"#),
+            Err(Error::ValueError(String::from("resolution_multiplier")))
+        );
+        assert_eq!(
+            parse_config(r#"{"resolution_multiplier": 64}"#).unwrap().resolution_multiplier,
+            Some(MAX_RESOLUTION_MULTIPLIER)
+        );
+        assert!(matches!(load("/nonexistent/sexe.json"), Err(Error::ReadError(_))));
+    }
+}
@@ -0,0 +1,97 @@
+//! Editing of a line of text at a cursor, as the input boxes do it, with the shortcuts of
+//! readline. Cursors are byte offsets at the start of a character, or at the end of the text.
+
+/// Whether `c` separates words, which is whitespace, an operator, a parenthesis or a comma, so
+/// that e.g. `sin(x)+co` has the words `sin`, `x` and `co`.
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || "+-*/^%!=<>()[]{},;".contains(c)
+}
+
+/// The start of the character before `cursor`, or `None` at the start of the text.
+pub fn previous_char(text: &str, cursor: usize) -> Option<usize> {
+    text[..cursor].char_indices().next_back().map(|(i, _)| i)
+}
+
+/// The end of the character after `cursor`, or `None` at the end of the text.
+pub fn next_char(text: &str, cursor: usize) -> Option<usize> {
+    text[cursor..].chars().next().map(|c| cursor + c.len_utf8())
+}
+
+/// The start of the word before `cursor`, skipping the separators in between.
+pub fn previous_word(text: &str, cursor: usize) -> usize {
+    let before = &text[..cursor];
+    let word_end = before.trim_end_matches(is_separator);
+    word_end.trim_end_matches(|c| !is_separator(c)).len()
+}
+
+/// The end of the word after `cursor`, skipping the separators in between.
+pub fn next_word(text: &str, cursor: usize) -> usize {
+    let after = &text[cursor..];
+    let word = after.trim_start_matches(is_separator);
+    text.len() - word.trim_start_matches(|c| !is_separator(c)).len()
+}
+
+/// Deletes the word before `cursor` and the separators after it, returning the new cursor.
+pub fn delete_previous_word(text: &mut String, cursor: usize) -> usize {
+    let start = previous_word(text, cursor);
+    text.replace_range(start..cursor, "");
+    start
+}
+
+/// Deletes everything before `cursor`, returning the new cursor.
+pub fn delete_to_start(text: &mut String, cursor: usize) -> usize {
+    text.replace_range(..cursor, "");
+    0
+}
+
+/// Deletes everything after `cursor`, returning the new cursor.
+pub fn delete_to_end(text: &mut String, cursor: usize) -> usize {
+    text.truncate(cursor);
+    cursor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_end_at_operators_parentheses_and_commas() {
+        let text = "sin(x)+co";
+        assert_eq!(previous_word(text, 9), 7);
+        assert_eq!(previous_word(text, 7), 4);
+        assert_eq!(previous_word(text, 4), 0);
+        assert_eq!(previous_word(text, 0), 0);
+        assert_eq!(next_word(text, 0), 3);
+        assert_eq!(next_word(text, 3), 5);
+        assert_eq!(next_word(text, 5), 9);
+        assert_eq!(next_word(text, 9), 9);
+
+        let text = "log(π, 2) *  ";
+        assert_eq!(previous_word(text, text.len()), 8);
+        assert_eq!(previous_word(text, 8), 4);
+        assert_eq!(next_word(text, 4), 6);
+    }
+
+    #[test]
+    fn characters_are_stepped_over_whole() {
+        assert_eq!(previous_char("2π", 3), Some(1));
+        assert_eq!(previous_char("2π", 0), None);
+        assert_eq!(next_char("2π", 1), Some(3));
+        assert_eq!(next_char("2π", 3), None);
+    }
+
+    #[test]
+    fn lines_are_deleted_around_the_cursor() {
+        let mut text = String::from("sin(x)+co");
+        assert_eq!(delete_previous_word(&mut text, 9), 7);
+        assert_eq!(text, "sin(x)+");
+        assert_eq!(delete_previous_word(&mut text, 7), 4);
+        assert_eq!(text, "sin(");
+
+        let mut text = String::from("x^2 + π");
+        assert_eq!(delete_to_end(&mut text, 3), 3);
+        assert_eq!(text, "x^2");
+        assert_eq!(delete_to_start(&mut text, 1), 0);
+        assert_eq!(text, "^2");
+    }
+}
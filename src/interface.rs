@@ -1,24 +1,70 @@
 use io;
+use io::Write;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::panic;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use termion::event;
-use termion::input::TermRead;
+use termion::input::{MouseTerminal, TermRead};
 use termion::raw::{IntoRawMode, RawTerminal};
 
 use tui::backend::TermionBackend;
 use tui::layout::*;
-use tui::style::{Color, Style};
+use tui::style::{Color, Modifier, Style};
 use tui::terminal::Frame;
 use tui::widgets::*;
 use tui::Terminal;
 
+use config::Config;
+use editing;
 use sexe_expression as expression;
 use sexe_parser as parser;
 
-#[derive(PartialEq, Eq)]
+type Backend = TermionBackend<MouseTerminal<RawTerminal<io::Stdout>>>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum SelectedBox {
     Function,
+    Variable,
+    Domain,
     StartX,
     EndX,
+    Family,
+}
+
+impl SelectedBox {
+    /// Every box, from left to right.
+    const ALL: [SelectedBox; 6] = [
+        SelectedBox::Function,
+        SelectedBox::Variable,
+        SelectedBox::Domain,
+        SelectedBox::StartX,
+        SelectedBox::EndX,
+        SelectedBox::Family,
+    ];
+
+    fn index(self) -> usize {
+        SelectedBox::ALL.iter().position(|&b| b == self).unwrap()
+    }
+}
+
+/// The box to the right of `selected`, wrapping around from the last box to the first.
+fn next_box(selected: SelectedBox) -> SelectedBox {
+    SelectedBox::ALL[(selected.index() + 1) % SelectedBox::ALL.len()]
+}
+
+/// The box to the left of `selected`, wrapping around from the first box to the last.
+fn prev_box(selected: SelectedBox) -> SelectedBox {
+    let len = SelectedBox::ALL.len();
+    SelectedBox::ALL[(selected.index() + len - 1) % len]
 }
 
 struct Application {
@@ -27,13 +73,273 @@ struct Application {
     end_y: f64,
     evaluation: Vec<(f64, f64)>,
     resolution: u32,
+    /// How many points are sampled per column of the terminal, which sets `resolution`.
+    resolution_multiplier: u32,
     function_input: TextInput,
+    /// The name of the variable that is swept across the X axis.
+    var_name_input: TextInput,
+    /// The variable the latest plot sweeps, which is the one of `var_name_input` unless the
+    /// function only references another one, see `sweep_variable`.
+    var_name: String,
+    /// The whole domain at once, as `[start, end]`. Typing a valid domain updates both bounds.
+    domain_input: TextInput,
     start_x_input: NumberInput,
     end_x_input: NumberInput,
+    /// A parameter and the values it takes in a family of curves, as `a = 0, 1, 2`, or nothing to
+    /// plot a single curve.
+    family_input: TextInput,
+    /// The sampled points of each curve of the family, or none if there is no family. The
+    /// first curve is also in `evaluation`, which the readout, roots and trace line follow.
+    family: Vec<Vec<(f64, f64)>>,
+    /// The sampled point nearest to the last mouse click on the plot, if any.
+    readout: Option<(f64, f64)>,
+    /// The X values at which the plotted function crosses zero.
+    roots: Vec<f64>,
+    /// The function that was last submitted with Enter, which `ans` refers to.
+    previous_function: Option<expression::ExpressionNode>,
+    /// A message shown below the plot, e.g. explaining why nothing is plotted.
+    status: String,
+    /// What was wrong with the function the last time it was plotted, if it only partially
+    /// parsed, e.g. while it is being typed.
+    parse_diagnostic: Option<String>,
+    /// The bytes of the function that failed to parse the last time it was plotted, which are
+    /// highlighted in the function box.
+    error_span: Option<Range<usize>>,
+    /// The previously submitted functions, which Up and Down cycle through.
+    history: History,
+    /// The index of the color theme in use in `THEMES`.
+    theme: usize,
+    /// How long sampling the function took during the last update.
+    update_duration: Duration,
+    /// Whether the Y bounds leave out the most extreme points, see `robust_bounds`, so that the
+    /// spikes near an asymptote don't flatten the rest of the plot.
+    robust_y_bounds: bool,
+    /// Whether the slope of the function is plotted along with it, see `finite_difference`.
+    show_slope: bool,
+    /// The X value of the trace line, which is moved with Alt-, and Alt-. to read off the value
+    /// of the function there.
+    cursor_x: Option<f64>,
+    /// The value of the function at `cursor_x`, or `None` if it is undefined there.
+    cursor_y: Option<f64>,
+    /// The function the latest plot is of, which the trace line reads off when it is moved.
+    plotted_function: Option<expression::ExpressionNode>,
+    /// Whether the function is being sampled, which the plot title shows while it takes long
+    /// enough that the interface would otherwise look frozen.
+    computing: bool,
+    /// Why nothing could be plotted the last time the function was sampled, if it couldn't.
+    plot_error: Option<Error>,
+    /// How long typing has to pause before the plot is updated, so that it doesn't flicker through
+    /// every incomplete function on the way, e.g. `si` and `sin(` of `sin(x)`.
+    debounce: Duration,
+    /// Counts the updates that were requested. Points sampled for any but the latest are stale
+    /// and dropped.
+    generation: u64,
+    /// Set when the latest update is superseded, which stops sampling it.
+    cancel: Arc<AtomicBool>,
+}
+
+/// The colors the interface is drawn with.
+struct Theme {
+    focused_border: Color,
+    unfocused_border: Color,
+    plot: Color,
+    /// Used for the roots of the function and the status line.
+    accent: Color,
+    /// Used for the status line when nothing can be plotted.
+    error: Color,
+    /// Used for the slope of the function, see `finite_difference`.
+    slope: Color,
+    /// The colors of the first and the last curve of a family, between which the colors of the
+    /// others are graduated.
+    family: [(u8, u8, u8); 2],
+}
+
+/// The themes that Ctrl-T cycles through, starting with the default one.
+const THEMES: [Theme; 3] = [
+    // Default
+    Theme {
+        focused_border: Color::Magenta,
+        unfocused_border: Color::Gray,
+        plot: Color::Magenta,
+        accent: Color::Yellow,
+        error: Color::Red,
+        slope: Color::Cyan,
+        family: [(255, 0, 255), (0, 191, 255)],
+    },
+    // High contrast
+    Theme {
+        focused_border: Color::LightYellow,
+        unfocused_border: Color::White,
+        plot: Color::LightCyan,
+        accent: Color::LightRed,
+        error: Color::LightMagenta,
+        slope: Color::LightGreen,
+        family: [(0, 255, 255), (255, 255, 0)],
+    },
+    // Monochrome
+    Theme {
+        focused_border: Color::White,
+        unfocused_border: Color::DarkGray,
+        plot: Color::White,
+        accent: Color::White,
+        error: Color::White,
+        slope: Color::DarkGray,
+        family: [(255, 255, 255), (96, 96, 96)],
+    },
+];
+
+fn next_theme(theme: usize) -> usize {
+    (theme + 1) % THEMES.len()
+}
+
+/// The color of the `index`th of `count` curves of a family, graduated between the colors of the
+/// first and the last curve.
+fn family_color(theme: &Theme, index: usize, count: usize) -> Color {
+    let fraction = if count > 1 { index as f64 / (count - 1) as f64 } else { 0.0 };
+    let [first, last] = theme.family;
+    let mix = |a: u8, b: u8| (f64::from(a) + fraction * (f64::from(b) - f64::from(a))).round() as u8;
+    Color::Rgb(mix(first.0, last.0), mix(first.1, last.1), mix(first.2, last.2))
 }
 
 struct TextInput {
     string: String,
+    /// Where typing inserts text, as a byte offset into `string`, or `None` at its end.
+    cursor: Option<usize>,
+}
+
+impl TextInput {
+    /// The byte offset of the cursor, moved back to the start of a character and to the end of
+    /// `string` if it was replaced by a shorter one.
+    fn cursor(&self) -> usize {
+        let mut cursor = self.cursor.unwrap_or(self.string.len()).min(self.string.len());
+        while !self.string.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+        cursor
+    }
+
+    fn move_cursor(&mut self, cursor: usize) {
+        self.cursor = if cursor < self.string.len() { Some(cursor) } else { None };
+    }
+
+    /// Replaces the whole string, putting the cursor at its end.
+    fn set(&mut self, string: String) {
+        self.string = string;
+        self.cursor = None;
+    }
+}
+
+/// The functions submitted so far, oldest first, and where we are while navigating through them.
+struct History {
+    entries: Vec<String>,
+    /// The entry currently shown, or `None` if we aren't navigating the history.
+    position: Option<usize>,
+    /// What was typed before navigation started, so that it can be restored.
+    draft: String,
+    /// The file the entries are kept in between sessions, if any.
+    path: Option<PathBuf>,
+}
+
+/// How many functions the history keeps. The oldest ones are forgotten first.
+const MAX_HISTORY: usize = 100;
+
+/// Where the history is kept between sessions, which is `$XDG_DATA_HOME/sexe/history`, or
+/// `~/.local/share/sexe/history` if `XDG_DATA_HOME` isn't set.
+fn history_path() -> Option<PathBuf> {
+    let data_home = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(data_home.join("sexe").join("history"))
+}
+
+impl History {
+    fn new() -> History {
+        History {
+            entries: Vec::new(),
+            position: None,
+            draft: String::new(),
+            path: None,
+        }
+    }
+
+    /// The history kept in the file at `path`, one function per line, which submitted functions
+    /// are saved to from now on. It starts out empty if the file can't be read.
+    fn load(path: PathBuf) -> History {
+        let mut history = History::new();
+        if let Ok(contents) = fs::read_to_string(&path) {
+            for line in contents.lines().filter(|line| !line.is_empty()) {
+                history.record(line.to_string());
+            }
+        }
+        history.path = Some(path);
+        history
+    }
+
+    /// Records a submitted function and stops navigating. A function that was submitted before is
+    /// moved to the end instead of being repeated.
+    fn push(&mut self, entry: String) {
+        self.record(entry);
+        self.position = None;
+        self.save();
+    }
+
+    fn record(&mut self, entry: String) {
+        self.entries.retain(|existing| *existing != entry);
+        self.entries.push(entry);
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.remove(0);
+        }
+    }
+
+    /// Writes the entries to the history file, if there is one. The history is only a
+    /// convenience, so failing to write it is ignored.
+    fn save(&self) {
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            let mut contents = self.entries.join("\n");
+            contents.push('\n');
+            let _ = fs::write(path, contents);
+        }
+    }
+
+    /// Stops navigating because the entry that is shown was edited, which makes it a new draft
+    /// rather than changing the entry, like in a shell.
+    fn fork(&mut self) {
+        self.position = None;
+    }
+
+    /// Steps back to the next older entry, stopping at the oldest one. `current` is what is typed
+    /// right now, which is remembered when navigation starts.
+    fn previous(&mut self, current: &str) -> Option<&str> {
+        let position = match self.position {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(position) => position.saturating_sub(1),
+        };
+        self.position = Some(position);
+        Some(&self.entries[position])
+    }
+
+    /// Steps forward to the next newer entry. Stepping past the newest one restores the draft.
+    fn next(&mut self) -> Option<&str> {
+        match self.position {
+            None => None,
+            Some(position) if position + 1 < self.entries.len() => {
+                self.position = Some(position + 1);
+                Some(&self.entries[position + 1])
+            }
+            Some(_) => {
+                self.position = None;
+                Some(&self.draft)
+            }
+        }
+    }
 }
 
 struct NumberInput {
@@ -45,323 +351,2530 @@ trait Input {
     fn process_input(&mut self, key: &event::Key);
 }
 
+impl NumberInput {
+    fn set_value(&mut self, value: f64) {
+        self.display_string = format!("{:+}", value);
+        self.number_value = value;
+    }
+
+    /// Adds `step` to the number. The sum is rounded to 12 significant digits, so that e.g. three
+    /// steps of 0.1 show `+0.3` rather than `+0.30000000000000004`, but never to fewer decimals
+    /// than the number or the step has, so that e.g. a step of 0.001 isn't rounded away from 1e9.
+    fn step(&mut self, step: f64) {
+        let sum = self.number_value + step;
+        if !sum.is_finite() {
+            return;
+        }
+        let significant_decimals = if sum == 0.0 {
+            0
+        } else {
+            (11.0 - sum.abs().log10().floor()).max(0.0) as usize
+        };
+        let decimals = significant_decimals
+            .max(decimals(self.number_value))
+            .max(decimals(step));
+        self.set_value(format!("{:.*}", decimals, sum).parse().unwrap());
+    }
+}
+
+/// How many decimals `value` is written with, e.g. 3 for `0.001` and none for `1e9`.
+fn decimals(value: f64) -> usize {
+    value.to_string().split('.').nth(1).map_or(0, str::len)
+}
+
+/// How far Up and Down step a number. Shift-Up and Shift-Down take finer steps, and Ctrl-Up and
+/// Ctrl-Down or Page Up and Page Down coarser ones.
+const NUMBER_STEP: f64 = 1.0;
+const FINE_NUMBER_STEP: f64 = 0.1;
+const COARSE_NUMBER_STEP: f64 = 10.0;
+
+/// The step of Up or Down with Shift or Ctrl held, which termion doesn't parse, from its escape
+/// sequence, e.g. `ESC [ 1 ; 2 A` for Shift-Up. Other modifiers and keys are `None`.
+fn modified_arrow_step(sequence: &[u8]) -> Option<f64> {
+    let step = match sequence.get(..5)? {
+        b"\x1b[1;2" => FINE_NUMBER_STEP,
+        b"\x1b[1;5" => COARSE_NUMBER_STEP,
+        _ => return None,
+    };
+    match &sequence[5..] {
+        b"A" => Some(step),
+        b"B" => Some(-step),
+        _ => None,
+    }
+}
+
 impl Input for NumberInput {
     fn process_input(&mut self, key: &event::Key) {
+        let previous = self.display_string.clone();
         match key {
-            event::Key::Up => {
-                self.display_string = format!("{:+}", self.number_value + 1.0).to_string();
-            }
-            event::Key::Down => {
-                self.display_string = format!("{:+}", self.number_value - 1.0).to_string();
-            }
+            event::Key::Up => self.step(NUMBER_STEP),
+            event::Key::Down => self.step(-NUMBER_STEP),
+            event::Key::PageUp => self.step(COARSE_NUMBER_STEP),
+            event::Key::PageDown => self.step(-COARSE_NUMBER_STEP),
             event::Key::Backspace => {
                 // Reset to placeholder if our string is too short.
                 if self.display_string.len() <= 2 {
                     self.display_string = String::from("+0");
                 } else {
                     self.display_string.pop();
+                    // An exponent without digits doesn't parse, so it goes with its last digit.
+                    if self.display_string.ends_with("e-") {
+                        self.display_string.pop();
+                    }
+                    if self.display_string.ends_with('e') {
+                        self.display_string.pop();
+                    }
                 }
             }
             event::Key::Char(digit) if digit.is_ascii_digit() => {
-                if &self.display_string == "+0" || &self.display_string == "-0" {
+                if &self.display_string == "+0"
+                    || &self.display_string == "-0"
+                    || self.display_string.ends_with("e0")
+                    || self.display_string.ends_with("e-0")
+                {
                     self.display_string.pop();
                 }
                 self.display_string.push(*digit);
             }
-            event::Key::Char('+') => {
-                self.display_string.replace_range(..1, "+");
-            }
-            event::Key::Char('-') => {
-                self.display_string.replace_range(..1, "-");
+            // Scientific notation, e.g. `1e3`. The exponent starts out as a placeholder `0`, like
+            // the whole number does, so that the string always parses.
+            event::Key::Char('e') | event::Key::Char('E')
+                if !self.display_string.contains('e')
+                    && self.display_string.ends_with(|c: char| c.is_ascii_digit()) =>
+            {
+                self.display_string.push_str("e0");
             }
-            event::Key::Char('.') => {
-                if !self.display_string.contains(".") {
-                    self.display_string.push('.');
+            // Once there is an exponent, the signs are its own, e.g. `1e-3`.
+            event::Key::Char(sign @ '+') | event::Key::Char(sign @ '-') => {
+                match self.display_string.find('e') {
+                    Some(e) => {
+                        let exponent = &self.display_string[e + 1..];
+                        let digits = exponent.trim_start_matches('-').to_string();
+                        let sign = if *sign == '-' { "-" } else { "" };
+                        self.display_string.replace_range(e + 1.., &(sign.to_string() + &digits));
+                    }
+                    None => self.display_string.replace_range(..1, &sign.to_string()),
                 }
             }
+            event::Key::Char('.')
+                if !self.display_string.contains('.') && !self.display_string.contains('e') =>
+            {
+                self.display_string.push('.');
+            }
             _ => (),
         };
-        self.number_value = self.display_string.parse().unwrap();
+        // Keys that would make the number overflow, e.g. a big exponent, are ignored.
+        match self.display_string.parse::<f64>() {
+            Ok(value) if value.is_finite() => self.number_value = value,
+            _ => self.display_string = previous,
+        }
+    }
+}
+
+impl TextInput {
+    /// Like `process_input`, but also moves the cursor and edits around it, with the shortcuts of
+    /// readline. Only the function box shows its cursor, so the other boxes don't take these keys.
+    fn process_editing_input(&mut self, key: &event::Key) {
+        let cursor = self.cursor();
+        let before = editing::previous_char(&self.string, cursor);
+        let after = editing::next_char(&self.string, cursor);
+        match key {
+            event::Key::Delete if after.is_some() => {
+                self.string.remove(cursor);
+                self.move_cursor(cursor);
+            }
+            event::Key::Left => self.move_cursor(before.unwrap_or(cursor)),
+            event::Key::Right => self.move_cursor(after.unwrap_or(cursor)),
+            event::Key::Home | event::Key::Ctrl('a') => self.move_cursor(0),
+            event::Key::End | event::Key::Ctrl('e') => self.cursor = None,
+            // Alt-B and Alt-F jump back and forward by a word, like in readline.
+            event::Key::Alt('b') => self.move_cursor(editing::previous_word(&self.string, cursor)),
+            event::Key::Alt('f') => self.move_cursor(editing::next_word(&self.string, cursor)),
+            event::Key::Ctrl('w') => {
+                let cursor = editing::delete_previous_word(&mut self.string, cursor);
+                self.move_cursor(cursor);
+            }
+            event::Key::Ctrl('u') => {
+                let cursor = editing::delete_to_start(&mut self.string, cursor);
+                self.move_cursor(cursor);
+            }
+            event::Key::Ctrl('k') => {
+                let cursor = editing::delete_to_end(&mut self.string, cursor);
+                self.move_cursor(cursor);
+            }
+            _ => self.process_input(key),
+        };
     }
 }
 
 impl Input for TextInput {
     fn process_input(&mut self, key: &event::Key) {
+        let cursor = self.cursor();
         match key {
             event::Key::Backspace => {
-                self.string.pop();
+                if let Some(before) = editing::previous_char(&self.string, cursor) {
+                    self.string.remove(before);
+                    self.move_cursor(before);
+                }
             }
+            // Enter only submits the function, the boxes are a single line each.
+            event::Key::Char('\n') => (),
             event::Key::Char(c) => {
-                self.string.push(*c);
+                self.string.insert(cursor, *c);
+                self.move_cursor(cursor + c.len_utf8());
             }
             _ => (),
         };
     }
 }
 
+/// Parses a domain written as `[start, end]`. Anything else is rejected.
+fn parse_domain(input: &str) -> Option<(f64, f64)> {
+    let input = input.trim();
+    if !input.starts_with('[') || !input.ends_with(']') {
+        return None;
+    }
+    let mut bounds = input[1..input.len() - 1].split(',');
+    match (bounds.next(), bounds.next(), bounds.next()) {
+        (Some(start), Some(end), None) => {
+            let start: f64 = start.trim().parse().ok()?;
+            let end: f64 = end.trim().parse().ok()?;
+            // The bounds are shown in the number boxes, which only deal in finite numbers.
+            if start.is_finite() && end.is_finite() {
+                Some((start, end))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The most curves a family may have, since each of them is sampled on every update.
+const MAX_FAMILY_SIZE: usize = 8;
+
+/// Parses a family of curves written as `a = 0, 1, 2`, the name of the parameter and the values
+/// it takes, of which there may be up to `MAX_FAMILY_SIZE`. Anything else is rejected.
+fn parse_family(input: &str) -> Option<(String, Vec<f64>)> {
+    let mut parts = input.splitn(2, '=');
+    let (name, values) = (parts.next()?.trim(), parts.next()?);
+    if name.is_empty() || !name.chars().all(char::is_alphabetic) {
+        return None;
+    }
+    let values = values
+        .split(',')
+        .map(|value| value.trim().parse::<f64>().ok().filter(|value| value.is_finite()))
+        .collect::<Option<Vec<f64>>>()?;
+    if values.len() > MAX_FAMILY_SIZE {
+        return None;
+    }
+    Some((name.to_string(), values))
+}
+
 enum ApplicationOperation {
     Exit,
-    Noop,
+    /// What the function is sampled from changed, so it has to be plotted again.
+    Replot,
+    /// Only what is shown changed, e.g. the theme, so drawing the interface again is enough.
+    Redraw,
+}
+
+/// Everything the plot is sampled from, except for the resolution, which follows the size of the
+/// terminal. Events that leave these alone don't need the plot to be sampled again.
+#[derive(PartialEq)]
+struct SampledInputs {
+    function: String,
+    var_name: String,
+    start_x: f64,
+    end_x: f64,
+    family: String,
+    robust_y_bounds: bool,
+    previous_function: Option<expression::ExpressionNode>,
 }
 
-fn determine_y_bounds(vec: &Vec<(f64, f64)>) -> Option<(f64, f64)> {
-    vec.into_iter().fold(None, |acc, &(_, y)| {
+fn determine_y_bounds(vec: &[(f64, f64)]) -> Option<(f64, f64)> {
+    vec.iter().fold(None, |acc, &(_, y)| {
         Some(acc.map_or((y, y), |(acc_min, acc_max)| {
             (y.min(acc_min), y.max(acc_max))
         }))
     })
 }
 
-enum Error {
-    ParseError,
-    RangeError,
+/// The percentiles of the Y values that bound the plot when outliers are left out.
+const ROBUST_BOUNDS_PERCENTILES: (f64, f64) = (2.0, 98.0);
+
+/// The `lo_pct`th and `hi_pct`th percentiles of the finite values of `ys`, which bound all but the
+/// most extreme values. Each percentile is the nearest of the sorted values, so the bounds are
+/// always values of `ys`. Without any finite values, the bounds are `(0, 0)`.
+fn robust_bounds(ys: &[f64], lo_pct: f64, hi_pct: f64) -> (f64, f64) {
+    let mut ys: Vec<f64> = ys.iter().cloned().filter(|y| y.is_finite()).collect();
+    if ys.is_empty() {
+        return (0.0, 0.0);
+    }
+    ys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let percentile = |pct: f64| {
+        let rank = (pct / 100.0 * (ys.len() - 1) as f64).round();
+        ys[(rank.max(0.0) as usize).min(ys.len() - 1)]
+    };
+    (percentile(lo_pct), percentile(hi_pct))
 }
 
-impl Application {
-    fn process_input(&mut self, key: &event::Key) -> ApplicationOperation {
-        match key {
-            // A Ctrl-C produces an exit command for the application.
-            event::Key::Ctrl('c') => return ApplicationOperation::Exit,
-            // Left and right change the focused box.
-            event::Key::Left => {
-                self.selected_box = match self.selected_box {
-                    SelectedBox::EndX => SelectedBox::StartX,
-                    _ => SelectedBox::Function,
-                };
-            }
-            event::Key::Right => {
-                self.selected_box = match self.selected_box {
-                    SelectedBox::Function => SelectedBox::StartX,
-                    _ => SelectedBox::EndX,
-                };
-            }
-            // Otherwise we hand off input to the children.
-            _ => match self.selected_box {
-                SelectedBox::Function => self.function_input.process_input(&key),
-                SelectedBox::StartX => self.start_x_input.process_input(&key),
-                SelectedBox::EndX => self.end_x_input.process_input(&key),
-            },
-        };
-        ApplicationOperation::Noop
+/// Picks the variable to sweep across the domain. If the function doesn't reference `var_name` but
+/// exactly one other variable, we plot over that one, so that e.g. `sin(t)` works without
+/// complaint. Otherwise we stick to `var_name`. The parameter of a family of curves isn't counted,
+/// since it is never swept.
+fn sweep_variable(
+    func: &expression::ExpressionNode,
+    var_name: &str,
+    parameter: Option<&str>,
+) -> String {
+    let mut variables = func.variables();
+    if let Some(parameter) = parameter {
+        variables.remove(parameter);
+    }
+    if variables.len() == 1 && !variables.contains(var_name) {
+        variables.into_iter().next().unwrap()
+    } else {
+        var_name.to_string()
     }
+}
 
-    fn draw(
-        &self,
-        t: &mut Terminal<TermionBackend<RawTerminal<io::Stdout>>>,
-    ) -> Result<(), io::Error> {
-        let f = |mut f: Frame<TermionBackend<RawTerminal<io::Stdout>>>| {
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(1)
-                .constraints(vec![Constraint::Min(3), Constraint::Percentage(100)])
-                .split(f.size());
+/// Explains why a function that references variables other than the swept one can't be plotted.
+fn unbound_variable_message(func: &expression::ExpressionNode, var_name: &str) -> Option<String> {
+    func.variables()
+        .into_iter()
+        .find(|var| var != var_name)
+        .map(|var| format!("Unknown variable `{}`; the plot sweeps `{}`", var, var_name))
+}
 
-            let input_section = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints(vec![
-                    Constraint::Percentage(60),
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(20),
-                ])
-                .direction(Direction::Horizontal)
-                .split(chunks[0]);
+/// Counts how many of the samples of a sweep fell outside of the domain of the function, e.g.
+/// `ln(x)` for `x <= 0`.
+fn count_domain_errors(samples: &[expression::Sample]) -> usize {
+    samples
+        .iter()
+        .filter(|(_, y)| *y == Err(expression::EvaluationError::DomainError))
+        .count()
+}
 
-            Paragraph::new([Text::raw(&self.function_input.string)].iter())
-                .block(
-                    Block::default()
-                        .title("Function")
-                        .borders(Borders::ALL)
-                        .border_style(self.get_box_style(SelectedBox::Function)),
-                )
-                .style(self.get_input_style(SelectedBox::Function))
-                .wrap(false)
-                .render(&mut f, input_section[0]);
+/// Reports how many of the `resolution` samples of a sweep fell outside of the domain of the
+/// function, since those points are silently left out of the plot.
+fn domain_error_message(errors: usize, resolution: u32) -> Option<String> {
+    if errors == 0 {
+        None
+    } else {
+        Some(format!("Domain error at {} of {} points", errors, resolution))
+    }
+}
 
-            Paragraph::new([Text::raw(&self.start_x_input.display_string)].iter())
-                .block(
-                    Block::default()
-                        .title("Start X")
-                        .borders(Borders::ALL)
-                        .border_style(self.get_box_style(SelectedBox::StartX)),
-                )
-                .style(self.get_input_style(SelectedBox::StartX))
-                .wrap(false)
-                .render(&mut f, input_section[1]);
+/// Puts the terminal back into a usable state: the cursor is shown again, styling is reset, and the
+/// screen is cleared so that anything printed afterwards (e.g. a panic message) is readable.
+fn restore_terminal<W: Write>(out: &mut W) -> Result<(), io::Error> {
+    write!(
+        out,
+        "{}{}{}{}",
+        termion::style::Reset,
+        termion::clear::All,
+        termion::cursor::Goto(1, 1),
+        termion::cursor::Show
+    )?;
+    out.flush()
+}
 
-            Paragraph::new([Text::raw(&self.end_x_input.display_string)].iter())
-                .block(
-                    Block::default()
-                        .title("End X")
-                        .borders(Borders::ALL)
-                        .border_style(self.get_box_style(SelectedBox::EndX)),
-                )
-                .style(self.get_input_style(SelectedBox::EndX))
-                .wrap(false)
-                .render(&mut f, input_section[2]);
+/// Installs a panic hook that restores the terminal before chaining to the previously installed
+/// hook, so a panic doesn't leave the terminal garbled. Raw mode itself is left when the
+/// `RawTerminal` is dropped during unwinding.
+fn install_panic_hook() {
+    let original_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal(&mut io::stdout());
+        original_hook(info);
+    }));
+}
 
-            Chart::default()
-                .block(Block::default().title("Plot").borders(Borders::ALL))
-                .x_axis(
-                    Axis::default()
-                        .title("X")
-                        .bounds([
-                            self.start_x_input.number_value,
-                            self.end_x_input.number_value,
-                        ])
-                        .labels(&[
-                            format!("{:.2}", self.start_x_input.number_value).as_str(),
-                            "0",
-                            format!("{:.2}", self.end_x_input.number_value).as_str(),
-                        ]),
-                )
-                .y_axis(
-                    Axis::default()
-                        .title("Y")
-                        .bounds([self.start_y, self.end_y])
-                        .labels(&[
-                            format!("{:.2}", self.start_y).as_str(),
-                            "0",
-                            format!("{:.2}", self.end_y).as_str(),
-                        ]),
-                )
-                .datasets(&[Dataset::default()
-                    .marker(Marker::Braille)
-                    .style(Style::default().fg(Color::Magenta))
-                    .data(&self.evaluation)])
-                .render(&mut f, chunks[1]);
-        };
+/// The labels of an axis from `start` to `end`: its bounds and the value halfway between them,
+/// with `precision` decimals.
+fn axis_ticks(start: f64, end: f64, precision: usize) -> [String; 3] {
+    [
+        format!("{:.*}", precision, start),
+        format!("{:.*}", precision, (start + end) / 2.0),
+        format!("{:.*}", precision, end),
+    ]
+}
 
-        t.draw(f)
+/// The number of decimals of the labels of an axis whose bounds are equal or not finite.
+const DEFAULT_LABEL_PRECISION: usize = 2;
+
+/// No more decimals than this are shown, however narrow the axis.
+const MAX_LABEL_PRECISION: usize = 12;
+
+/// How many decimals the labels of an axis from `min` to `max` need for the two significant
+/// digits of the distance between them, e.g. 5 for `[0, 0.001]` and none for `[0, 1000]`.
+fn auto_precision(min: f64, max: f64) -> usize {
+    let step = (max - min).abs() / 2.0;
+    if step == 0.0 || !step.is_finite() {
+        return DEFAULT_LABEL_PRECISION;
     }
+    let precision = 1.0 - step.log10().floor();
+    precision.max(0.0).min(MAX_LABEL_PRECISION as f64) as usize
+}
 
-    fn start(&mut self) -> Result<(), io::Error> {
-        let stdout = io::stdout().into_raw_mode()?;
-        let backend = TermionBackend::new(stdout);
-        let mut terminal = Terminal::new(backend)?;
+/// Describes how many points were plotted and how long sampling them took, e.g.
+/// `300 points in 1.25 ms`.
+fn format_timing(points: usize, duration: Duration) -> String {
+    format!("{} points in {:.2} ms", points, duration.as_secs_f64() * 1e3)
+}
 
-        let stdin = io::stdin();
-        terminal.clear().unwrap();
-        terminal.hide_cursor().unwrap();
+/// Splits the terminal into the input row, the plot area, the readout, and the status line.
+fn main_layout(size: Rect) -> Vec<Rect> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(vec![
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .split(size)
+}
 
-        let mut term_size = terminal.size().unwrap();
-        self.resolution = (term_size.width * 3).into();
-
-        match self.plot_function() {
-            Ok(vec) => {
-                // Filters all instances of f64::NAN from the vector
-                self.evaluation = vec.into_iter().filter(|&(_, a)| a.is_normal()).collect();
-                let (start_y, end_y) = determine_y_bounds(&self.evaluation).unwrap_or((0.0, 0.0));
-                if start_y == end_y {
-                    let end_y_abs = end_y.abs();
-                    self.start_y = -end_y_abs;
-                    self.end_y = end_y_abs;
-                } else {
-                    self.start_y = start_y;
-                    self.end_y = end_y;
-                }
-            }
-            Err(_) => {
-                self.evaluation = Vec::new();
-                self.start_y = 0.0;
-                self.end_y = 0.0;
-            }
-        }
+/// The area that a `Chart` with a border, drawn in `area`, plots the points in, when its axes are
+/// labeled with `x_labels` and `y_labels`. Like the chart lays it out, it is right of the widest
+/// label of the Y axis (or the first of the X axis, if that is wider) and the column of the Y
+/// axis, and above the row of the X labels and the row of the X axis.
+fn chart_graph_area(area: Rect, x_labels: &[String], y_labels: &[String]) -> Rect {
+    let area = area.inner(1);
+    if area.width == 0 || area.height == 0 {
+        return Rect::default();
+    }
+    let label_width = y_labels
+        .iter()
+        .chain(x_labels.first())
+        .map(|label| label.chars().count() as u16)
+        .max()
+        .unwrap_or(0);
+    let (mut x, mut y) = (area.left(), area.bottom() - 1);
+    if y > area.top() {
+        y -= 1;
+    }
+    if x + label_width < area.right() {
+        x += label_width;
+    }
+    if y > area.top() {
+        y -= 1;
+    }
+    if x + 1 < area.right() {
+        x += 1;
+    }
+    if x < area.right() && y > 1 {
+        Rect::new(x, area.top(), area.right() - x, y - area.top() + 1)
+    } else {
+        Rect::default()
+    }
+}
 
-        self.draw(&mut terminal)?;
+/// Maps a terminal column to an X value, assuming the columns of `area` span the domain
+/// `start_x..end_x` linearly. Returns `None` for columns outside of `area`.
+fn column_to_x(column: u16, area: Rect, start_x: f64, end_x: f64) -> Option<f64> {
+    if area.width == 0 || column < area.left() || column >= area.right() {
+        return None;
+    }
+    if area.width == 1 {
+        return Some(start_x);
+    }
+    let fraction = f64::from(column - area.left()) / f64::from(area.width - 1);
+    Some(start_x + fraction * (end_x - start_x))
+}
 
-        for c in stdin.keys() {
-            let size = terminal.size().unwrap();
-            if term_size != size {
-                terminal.resize(size).unwrap();
-                term_size = size;
-                self.resolution = (term_size.width * 3).into();
-            }
-            let evt = c.unwrap();
+/// Finds the point in `points` whose X value is closest to `x`.
+fn nearest_point(points: &[(f64, f64)], x: f64) -> Option<(f64, f64)> {
+    points.iter().cloned().fold(None, |acc: Option<(f64, f64)>, point| match acc {
+        Some(best) if (best.0 - x).abs() <= (point.0 - x).abs() => Some(best),
+        _ => Some(point),
+    })
+}
 
-            match self.process_input(&evt) {
-                ApplicationOperation::Exit => break,
-                ApplicationOperation::Noop => (),
-            };
+/// Consecutive points further apart than this many times the closest ones have a part of the
+/// domain between them where the function is undefined.
+const GAP_SPACING: f64 = 1.5;
 
-            // TODO: Handle plotting errors and display error messages.
-            match self.plot_function() {
-                Ok(vec) => {
-                    // Filters all instances of f64::NAN from the vector
-                    self.evaluation = vec.into_iter().filter(|&(_, a)| a.is_normal()).collect();
-                    let (start_y, end_y) =
-                        determine_y_bounds(&self.evaluation).unwrap_or((0.0, 0.0));
-                    if start_y == end_y {
-                        let end_y_abs = end_y.abs();
-                        self.start_y = -end_y_abs;
-                        self.end_y = end_y_abs;
-                    } else {
-                        self.start_y = start_y;
-                        self.end_y = end_y;
-                    }
-                }
-                Err(_) => {
-                    self.evaluation = Vec::new();
-                    self.start_y = 0.0;
-                    self.end_y = 0.0;
-                }
-            };
+/// The slope between each pair of consecutive points, `(y[i+1] - y[i]) / (x[i+1] - x[i])`, placed
+/// halfway between them. There is no slope across a gap where the function is undefined, see
+/// `GAP_SPACING`, since the points on either side needn't be related at all.
+fn finite_difference(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let spacing = points
+        .windows(2)
+        .map(|pair| pair[1].0 - pair[0].0)
+        .fold(f64::INFINITY, f64::min);
+    points
+        .windows(2)
+        .filter(|pair| pair[1].0 - pair[0].0 <= GAP_SPACING * spacing)
+        .map(|pair| {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            ((x0 + x1) / 2.0, (y1 - y0) / (x1 - x0))
+        })
+        .filter(|(_, slope)| slope.is_finite())
+        .collect()
+}
 
-            self.draw(&mut terminal)?;
-        }
-        terminal.clear().unwrap();
-        terminal.show_cursor().unwrap();
-        Ok(())
-    }
+/// If an update took at least this long, the plot title says that the next one is being computed
+/// until it arrives, see `Application::begin_update`.
+const SLOW_UPDATE: Duration = Duration::from_millis(100);
 
-    fn plot_function(&mut self) -> Result<Vec<(f64, f64)>, Error> {
-        if self.start_x_input.number_value >= self.end_x_input.number_value {
-            Err(Error::RangeError)
-        } else {
-            if let Ok(func) = parser::parse(&self.function_input.string) {
-                Ok(expression::evaluate_function_over_domain(
-                    self.start_x_input.number_value,
-                    self.end_x_input.number_value,
-                    self.resolution,
-                    &func,
-                ))
-            } else {
-                Err(Error::ParseError)
-            }
-        }
+/// How often the size of the terminal is checked while no event arrives, so that the interface is
+/// redrawn soon after it is resized.
+const RESIZE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many points are sampled per column of the terminal, unless configured otherwise.
+const DEFAULT_RESOLUTION_MULTIPLIER: u32 = 3;
+
+/// The most points that are sampled for a plot, however wide the terminal.
+const MAX_RESOLUTION: u32 = 1 << 16;
+
+/// How many points are sampled for a plot `width` columns wide, with `multiplier` points per
+/// column, up to `MAX_RESOLUTION`.
+fn plot_resolution(width: u16, multiplier: u32) -> u32 {
+    u32::from(width).saturating_mul(multiplier).min(MAX_RESOLUTION)
+}
+
+/// How long typing has to pause before the plot is updated, unless configured otherwise.
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Whether `key` edits the text of the focused box, which updates the plot once typing pauses
+/// rather than right away. Other keys, e.g. Up and Down stepping a bound, take effect at once.
+fn is_typing(key: &event::Key) -> bool {
+    match key {
+        event::Key::Char('\t') | event::Key::Char('\n') => false,
+        event::Key::Char(_) | event::Key::Backspace | event::Key::Delete => true,
+        event::Key::Ctrl('w') | event::Key::Ctrl('u') | event::Key::Ctrl('k') => true,
+        _ => false,
     }
+}
 
-    fn get_input_style(&self, _selected: SelectedBox) -> Style {
-        // leaving this method as a reference how to change the text of focused input
-        Style::default()
+/// How many steps of the trace line it takes to cross the whole domain.
+const CURSOR_STEPS: f64 = 100.0;
+
+/// Moves the trace line one step to the left (for a negative `direction`) or to the right, staying
+/// within `start_x..=end_x`. A trace line that isn't shown yet starts in the middle of the domain.
+fn step_cursor(cursor_x: Option<f64>, direction: f64, start_x: f64, end_x: f64) -> f64 {
+    let x = match cursor_x {
+        Some(x) => x + direction.signum() * (end_x - start_x) / CURSOR_STEPS,
+        None => (start_x + end_x) / 2.0,
+    };
+    x.max(start_x).min(end_x)
+}
+
+/// The value of `func` with `var_name` set to `x`, or `None` if it is undefined there.
+fn evaluate_at(func: &expression::ExpressionNode, var_name: &str, x: f64) -> Option<f64> {
+    let mut vars = HashMap::new();
+    vars.insert(var_name.to_string(), x);
+    func.evaluate(&vars).ok().filter(|y| y.is_finite())
+}
+
+/// Describes the value read off at the trace line, e.g. `x = 1.00, y = 0.84`.
+fn cursor_label(var_name: &str, x: f64, y: Option<f64>) -> String {
+    match y {
+        Some(y) => format!("{} = {:.2}, y = {:.2}", var_name, x, y),
+        None => format!("{} = {:.2}, y = undefined", var_name, x),
     }
+}
 
-    fn get_box_style(&self, selected: SelectedBox) -> Style {
-        if selected == self.selected_box {
-            Style::default().fg(Color::Magenta)
-        } else {
-            Style::default().fg(Color::Gray)
-        }
+/// Splits `input` into the text before `span`, the text in it, and the text after it, where
+/// `span` is a range of bytes, e.g. of a parse error. The span is widened to whole characters and
+/// cut off at the end of `input`, since the function may have been edited since it was parsed.
+fn split_at_span<'a>(input: &'a str, span: &Range<usize>) -> (&'a str, &'a str, &'a str) {
+    let mut start = span.start.min(input.len());
+    while !input.is_char_boundary(start) {
+        start -= 1;
     }
+    let mut end = span.end.max(start).min(input.len());
+    while !input.is_char_boundary(end) {
+        end += 1;
+    }
+    (&input[..start], &input[start..end], &input[end..])
 }
 
-pub fn display() -> Result<(), io::Error> {
-    let mut application = Application {
-        selected_box: SelectedBox::Function,
-        start_y: 0.0,
-        end_y: 0.0,
-        evaluation: Vec::new(),
-        function_input: TextInput {
-            string: String::from("sin(x)"),
-        },
-        start_x_input: NumberInput {
-            display_string: String::from("+0"),
-            number_value: 0.0,
-        },
-        end_x_input: NumberInput {
-            display_string: String::from("+10"),
-            number_value: 10.0,
-        },
-        resolution: 100,
+/// The text of the function box, split into runs of the same style: the part in `error_span`
+/// underlined in `error_color`, the text after it dimmed, and the character at `cursor` reversed,
+/// or a reversed space if the cursor is at the end.
+fn function_segments(
+    input: &str,
+    cursor: Option<usize>,
+    error_span: Option<&Range<usize>>,
+    error_color: Color,
+) -> Vec<(String, Style)> {
+    let error = error_span.map(|span| {
+        let (before, error, _) = split_at_span(input, span);
+        before.len()..before.len() + error.len()
+    });
+    let end = cursor.filter(|&cursor| cursor == input.len()).map(|cursor| (cursor, ' '));
+    let mut segments: Vec<(String, Style)> = Vec::new();
+    for (i, c) in input.char_indices().chain(end) {
+        let mut style = match &error {
+            Some(error) if error.contains(&i) => {
+                Style::default().fg(error_color).modifier(Modifier::UNDERLINED)
+            }
+            Some(error) if i >= error.end => Style::default().modifier(Modifier::DIM),
+            _ => Style::default(),
+        };
+        if cursor == Some(i) {
+            style.modifier |= Modifier::REVERSED;
+        }
+        match segments.last_mut() {
+            Some((text, last)) if *last == style => text.push(c),
+            _ => segments.push((c.to_string(), style)),
+        }
+    }
+    segments
+}
+
+/// Shown in the readout while there is no trace line or nothing is plotted.
+const READOUT_PLACEHOLDER: &str = "f(x) = —";
+
+/// The value of the function at the trace line, as shown in the readout below the plot, e.g.
+/// `f(1.5) = 0.997495`. Values are shown to 6 significant digits.
+fn format_readout(x: f64, y: Option<f64>) -> String {
+    match y {
+        Some(y) => format!("f({}) = {}", significant(x), significant(y)),
+        None => format!("f({}) = undefined", significant(x)),
+    }
+}
+
+/// `value` rounded to 6 significant digits, without trailing zeros, or in scientific notation if
+/// it is very large or small.
+fn significant(value: f64) -> String {
+    const DIGITS: i32 = 6;
+    if value == 0.0 {
+        return String::from("0");
+    }
+    let magnitude = value.abs().log10().floor() as i32;
+    if !(-4..DIGITS).contains(&magnitude) {
+        return format!("{:.*e}", (DIGITS - 1) as usize, value);
+    }
+    let decimals = (DIGITS - 1 - magnitude).max(0) as usize;
+    let fixed = format!("{:.*}", decimals, value);
+    if fixed.contains('.') {
+        fixed.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        fixed
+    }
+}
+
+/// Above this many samples the plot is evaluated in parallel, if the `rayon` feature is enabled.
+#[cfg(feature = "rayon")]
+const PARALLEL_RESOLUTION: u32 = 2048;
+
+/// Samples `func`, keeping the samples that failed to evaluate, and gives up once `cancel` is set.
+fn sample_function(
+    start_x: f64,
+    end_x: f64,
+    resolution: u32,
+    func: &expression::ExpressionNode,
+    var_name: &str,
+    ctx: &expression::EvaluationContext,
+    cancel: &AtomicBool,
+) -> Result<Vec<expression::Sample>, expression::Cancelled> {
+    #[cfg(feature = "rayon")]
+    {
+        if resolution > PARALLEL_RESOLUTION {
+            return expression::evaluate_samples_over_domain_par(
+                start_x, end_x, resolution, func, var_name, ctx, cancel,
+            );
+        }
+    }
+    expression::evaluate_samples_over_domain_cancellable(
+        start_x, end_x, resolution, func, var_name, ctx, cancel,
+    )
+}
+
+/// A snapshot of everything the function is sampled from for one update, which is sent to the
+/// evaluation worker, see `Application::start`.
+struct PlotRequest {
+    /// Which update this is, see `Application::generation`.
+    generation: u64,
+    func: expression::ExpressionNode,
+    var_name: String,
+    start_x: f64,
+    end_x: f64,
+    resolution: u32,
+    /// The parameter of the family of curves and the values it takes, if there is a family.
+    family: Option<(String, Vec<f64>)>,
+    robust_y_bounds: bool,
+    /// Where the trace line is, if it is shown.
+    cursor_x: Option<f64>,
+    /// Set once a newer update is requested, which stops sampling this one.
+    cancel: Arc<AtomicBool>,
+}
+
+/// What was computed for a `PlotRequest`: all of an update that takes long.
+struct Plot {
+    generation: u64,
+    /// The function the points are of, which is the first curve of a family.
+    func: expression::ExpressionNode,
+    /// The variable that was swept.
+    var_name: String,
+    /// The points at which the function is defined.
+    evaluation: Vec<(f64, f64)>,
+    family: Vec<Vec<(f64, f64)>>,
+    y_bounds: (f64, f64),
+    roots: Vec<f64>,
+    /// How many of the `resolution` sampled points were outside of the domain of the function.
+    domain_errors: usize,
+    resolution: u32,
+    /// The value of the function if it doesn't depend on any variable.
+    constant: Option<f64>,
+    /// The value of the function at the trace line, or `None` if it is undefined there.
+    cursor_y: Option<f64>,
+    /// How long computing all this took.
+    duration: Duration,
+}
+
+/// Sent to the main loop by the threads that read events and sample the function.
+enum Message {
+    Event(Result<event::Event, io::Error>),
+    Plotted(Box<Plot>),
+}
+
+/// Samples the function of `request` and computes the Y bounds and roots of the plot, and what the
+/// status line and the trace line show, unless the request is superseded in the meantime.
+fn sample_plot(request: &PlotRequest) -> Result<Plot, expression::Cancelled> {
+    let timer = Instant::now();
+    let sample = |ctx: &expression::EvaluationContext| {
+        sample_function(
+            request.start_x,
+            request.end_x,
+            request.resolution,
+            &request.func,
+            &request.var_name,
+            ctx,
+            &request.cancel,
+        )
     };
+    let (func, samples, mut family) = match &request.family {
+        Some((parameter, values)) => {
+            let mut curves = values
+                .iter()
+                .map(|&value| {
+                    let mut fixed = HashMap::new();
+                    fixed.insert(parameter.clone(), value);
+                    sample(&expression::EvaluationContext::with_variables(fixed))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            // The first curve stands in for the family everywhere else, e.g. its roots are marked
+            // and the status line is about it.
+            let first = expression::ExpressionNode::constant(values[0]);
+            let func = expression::substitute_var(&request.func, parameter, &first);
+            let samples = curves.remove(0);
+            let mut family: Vec<_> = curves.into_iter().map(expression::evaluated_points).collect();
+            family.insert(0, expression::evaluated_points(samples.clone()));
+            (func, samples, family)
+        }
+        None => {
+            let samples = sample(&expression::EvaluationContext::new())?;
+            (request.func.clone(), samples, Vec::new())
+        }
+    };
+
+    let domain_errors = count_domain_errors(&samples);
+    let vec = expression::evaluated_points(samples);
+    // Filters all instances of f64::NAN from the vector
+    let evaluation: Vec<(f64, f64)> = vec.into_iter().filter(|&(_, a)| a.is_normal()).collect();
+    for curve in &mut family {
+        curve.retain(|&(_, a)| a.is_normal());
+    }
+    // The Y bounds fit every curve of a family.
+    let plotted = if family.is_empty() {
+        evaluation.clone()
+    } else {
+        family.concat()
+    };
+    let (start_y, end_y) = if request.robust_y_bounds {
+        let ys: Vec<f64> = plotted.iter().map(|&(_, y)| y).collect();
+        robust_bounds(&ys, ROBUST_BOUNDS_PERCENTILES.0, ROBUST_BOUNDS_PERCENTILES.1)
+    } else {
+        determine_y_bounds(&plotted).unwrap_or((0.0, 0.0))
+    };
+    let y_bounds = if start_y == end_y {
+        (-end_y.abs(), end_y.abs())
+    } else {
+        (start_y, end_y)
+    };
+    let roots = expression::roots_between_samples(
+        &evaluation,
+        &func,
+        &request.var_name,
+        expression::DEFAULT_ROOT_TOLERANCE,
+    );
+    let constant = func.eval_const();
+    let cursor_y = request.cursor_x.and_then(|x| evaluate_at(&func, &request.var_name, x));
+    Ok(Plot {
+        generation: request.generation,
+        func,
+        var_name: request.var_name.clone(),
+        evaluation,
+        family,
+        y_bounds,
+        roots,
+        domain_errors,
+        resolution: request.resolution,
+        constant,
+        cursor_y,
+        duration: timer.elapsed(),
+    })
+}
+
+/// The name that refers to the previously submitted function.
+const PREVIOUS_FUNCTION_NAME: &str = "ans";
+
+/// Splices the previously submitted function into `func` wherever `ans` is referenced.
+fn resolve_previous_function(
+    func: expression::ExpressionNode,
+    previous: Option<&expression::ExpressionNode>,
+) -> Result<expression::ExpressionNode, Error> {
+    if !func.variables().contains(PREVIOUS_FUNCTION_NAME) {
+        return Ok(func);
+    }
+    match previous {
+        Some(previous) => Ok(expression::substitute_var(
+            &func,
+            PREVIOUS_FUNCTION_NAME,
+            previous,
+        )),
+        None => Err(Error::NoPreviousFunctionError),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+enum Error {
+    ParseError,
+    RangeError,
+    NoPreviousFunctionError,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ParseError => write!(f, "Parse error"),
+            Error::RangeError => write!(f, "Empty domain: the start must be less than the end"),
+            Error::NoPreviousFunctionError => write!(
+                f,
+                "`{}` refers to the previous function, but none was submitted with Enter",
+                PREVIOUS_FUNCTION_NAME
+            ),
+        }
+    }
+}
+
+impl Application {
+    fn new() -> Application {
+        Application {
+            selected_box: SelectedBox::Function,
+            start_y: 0.0,
+            end_y: 0.0,
+            evaluation: Vec::new(),
+            function_input: TextInput {
+                string: String::from("sin(x)"),
+                cursor: None,
+            },
+            var_name_input: TextInput {
+                string: String::from("x"),
+                cursor: None,
+            },
+            var_name: String::from("x"),
+            domain_input: TextInput {
+                string: String::from("[0, 10]"),
+                cursor: None,
+            },
+            start_x_input: NumberInput {
+                display_string: String::from("+0"),
+                number_value: 0.0,
+            },
+            end_x_input: NumberInput {
+                display_string: String::from("+10"),
+                number_value: 10.0,
+            },
+            family_input: TextInput {
+                string: String::new(),
+                cursor: None,
+            },
+            family: Vec::new(),
+            resolution: 100,
+            resolution_multiplier: DEFAULT_RESOLUTION_MULTIPLIER,
+            readout: None,
+            roots: Vec::new(),
+            previous_function: None,
+            status: String::new(),
+            parse_diagnostic: None,
+            error_span: None,
+            history: History::new(),
+            theme: 0,
+            update_duration: Duration::default(),
+            robust_y_bounds: false,
+            show_slope: false,
+            cursor_x: None,
+            cursor_y: None,
+            plotted_function: None,
+            computing: false,
+            plot_error: None,
+            debounce: DEFAULT_DEBOUNCE,
+            generation: 0,
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// The application as it starts with `config`, with the defaults of `new` where it doesn't
+    /// say otherwise. Bounds that don't make a valid domain are shown as an error once plotted.
+    fn from_config(config: &Config) -> Application {
+        let mut application = Application::new();
+        if let Some(function) = &config.function {
+            application.function_input.set(function.clone());
+        }
+        if let Some(start_x) = config.start_x {
+            application.start_x_input.set_value(start_x);
+        }
+        if let Some(end_x) = config.end_x {
+            application.end_x_input.set_value(end_x);
+        }
+        application.show_domain();
+        if let Some(multiplier) = config.resolution_multiplier {
+            application.resolution_multiplier = multiplier;
+        }
+        application
+    }
+
+    fn process_input(&mut self, key: &event::Key) -> ApplicationOperation {
+        match key {
+            // A Ctrl-C produces an exit command for the application.
+            event::Key::Ctrl('c') => return ApplicationOperation::Exit,
+            // Ctrl-T switches to the next color theme.
+            event::Key::Ctrl('t') => self.theme = next_theme(self.theme),
+            // Ctrl-R toggles whether outliers are left out of the Y bounds.
+            event::Key::Ctrl('r') => self.robust_y_bounds = !self.robust_y_bounds,
+            // Ctrl-D toggles whether the slope of the function is plotted too.
+            event::Key::Ctrl('d') => self.show_slope = !self.show_slope,
+            // Alt-, and Alt-. move the trace line left and right.
+            event::Key::Alt(',') | event::Key::Alt('.') => {
+                let direction = if *key == event::Key::Alt(',') { -1.0 } else { 1.0 };
+                let x = step_cursor(
+                    self.cursor_x,
+                    direction,
+                    self.start_x_input.number_value,
+                    self.end_x_input.number_value,
+                );
+                self.cursor_x = Some(x);
+                // The plot doesn't change, so the function is read off right away.
+                self.cursor_y = self
+                    .plotted_function
+                    .as_ref()
+                    .and_then(|func| evaluate_at(func, &self.var_name, x));
+            }
+            // In the function box, left and right move the cursor instead.
+            event::Key::Left | event::Key::Right if self.selected_box == SelectedBox::Function => {
+                self.function_input.process_editing_input(key);
+            }
+            // Left and right change the focused box, stopping at the first and the last one.
+            event::Key::Left if self.selected_box.index() > 0 => {
+                self.selected_box = prev_box(self.selected_box);
+            }
+            event::Key::Right if self.selected_box.index() + 1 < SelectedBox::ALL.len() => {
+                self.selected_box = next_box(self.selected_box);
+            }
+            event::Key::Left | event::Key::Right => (),
+            // Tab and Shift-Tab do the same, but wrap around.
+            event::Key::Char('\t') => self.selected_box = next_box(self.selected_box),
+            event::Key::BackTab => self.selected_box = prev_box(self.selected_box),
+            // Enter submits the function, so that `ans` refers to it from now on.
+            event::Key::Char('\n') if self.selected_box == SelectedBox::Function => {
+                if let Ok(func) = self.parse_function() {
+                    self.previous_function = Some(func);
+                    self.history.push(self.function_input.string.clone());
+                }
+            }
+            // Up and down cycle through the previously submitted functions.
+            event::Key::Up if self.selected_box == SelectedBox::Function => {
+                if let Some(entry) = self.history.previous(&self.function_input.string) {
+                    self.function_input.set(entry.to_string());
+                }
+            }
+            event::Key::Down if self.selected_box == SelectedBox::Function => {
+                if let Some(entry) = self.history.next() {
+                    self.function_input.set(entry.to_string());
+                }
+            }
+            // Otherwise we hand off input to the children.
+            _ => match self.selected_box {
+                SelectedBox::Function => {
+                    let previous = self.function_input.string.clone();
+                    self.function_input.process_editing_input(key);
+                    if self.function_input.string != previous {
+                        self.history.fork();
+                    }
+                }
+                SelectedBox::Variable => self.var_name_input.process_input(key),
+                SelectedBox::Domain => {
+                    self.domain_input.process_input(key);
+                    if let Some((start_x, end_x)) = parse_domain(&self.domain_input.string) {
+                        self.start_x_input.set_value(start_x);
+                        self.end_x_input.set_value(end_x);
+                    }
+                }
+                SelectedBox::StartX => {
+                    self.start_x_input.process_input(key);
+                    self.show_domain();
+                }
+                SelectedBox::EndX => {
+                    self.end_x_input.process_input(key);
+                    self.show_domain();
+                }
+                SelectedBox::Family => self.family_input.process_input(key),
+            },
+        };
+        ApplicationOperation::Redraw
+    }
+
+    /// Writes the bounds into the Domain box, so that it follows them when they are edited.
+    fn show_domain(&mut self) {
+        let domain = format!(
+            "[{}, {}]",
+            self.start_x_input.number_value, self.end_x_input.number_value
+        );
+        self.domain_input.set(domain);
+    }
+
+    /// Clicking (or dragging) on the plot shows the sampled point nearest to that column. Clicks
+    /// anywhere else clear the readout.
+    fn process_mouse(&mut self, mouse: &event::MouseEvent, size: Rect) {
+        let column = match mouse {
+            event::MouseEvent::Press(event::MouseButton::Left, column, _)
+            | event::MouseEvent::Hold(column, _) => *column,
+            _ => return,
+        };
+        // Termion reports 1-based coordinates.
+        let (x_ticks, y_ticks) = self.axis_ticks();
+        let plot_area = chart_graph_area(main_layout(size)[1], &x_ticks, &y_ticks);
+        self.readout = column_to_x(
+            column.saturating_sub(1),
+            plot_area,
+            self.start_x_input.number_value,
+            self.end_x_input.number_value,
+        )
+        .and_then(|x| nearest_point(&self.evaluation, x));
+    }
+
+    /// The labels of the X and the Y axis of the plot.
+    fn axis_ticks(&self) -> ([String; 3], [String; 3]) {
+        let (start_x, end_x) = (self.start_x_input.number_value, self.end_x_input.number_value);
+        (
+            axis_ticks(start_x, end_x, auto_precision(start_x, end_x)),
+            axis_ticks(self.start_y, self.end_y, auto_precision(self.start_y, self.end_y)),
+        )
+    }
+
+    fn draw(&self, t: &mut Terminal<Backend>) -> Result<(), io::Error> {
+        let f = |mut f: Frame<Backend>| {
+            let chunks = main_layout(f.size());
+
+            let input_section = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![
+                    Constraint::Percentage(30),
+                    Constraint::Percentage(10),
+                    Constraint::Percentage(15),
+                    Constraint::Percentage(15),
+                    Constraint::Percentage(15),
+                    Constraint::Percentage(15),
+                ])
+                .direction(Direction::Horizontal)
+                .split(chunks[0]);
+
+            // The cursor is only shown while the function is being edited.
+            let cursor = if self.selected_box == SelectedBox::Function {
+                Some(self.function_input.cursor())
+            } else {
+                None
+            };
+            let segments = function_segments(
+                &self.function_input.string,
+                cursor,
+                self.error_span.as_ref(),
+                THEMES[self.theme].error,
+            );
+            let function_text: Vec<Text> = segments
+                .iter()
+                .map(|(text, style)| Text::styled(text.as_str(), *style))
+                .collect();
+            Paragraph::new(function_text.iter())
+                .block(
+                    Block::default()
+                        .title("Function")
+                        .borders(Borders::ALL)
+                        .border_style(self.get_box_style(SelectedBox::Function)),
+                )
+                .style(self.get_input_style(SelectedBox::Function))
+                .wrap(false)
+                .render(&mut f, input_section[0]);
+
+            Paragraph::new([Text::raw(&self.var_name_input.string)].iter())
+                .block(
+                    Block::default()
+                        .title("Var")
+                        .borders(Borders::ALL)
+                        .border_style(self.get_box_style(SelectedBox::Variable)),
+                )
+                .style(self.get_input_style(SelectedBox::Variable))
+                .wrap(false)
+                .render(&mut f, input_section[1]);
+
+            Paragraph::new([Text::raw(&self.domain_input.string)].iter())
+                .block(
+                    Block::default()
+                        .title("Domain")
+                        .borders(Borders::ALL)
+                        .border_style(self.get_box_style(SelectedBox::Domain)),
+                )
+                .style(self.get_input_style(SelectedBox::Domain))
+                .wrap(false)
+                .render(&mut f, input_section[2]);
+
+            Paragraph::new([Text::raw(&self.start_x_input.display_string)].iter())
+                .block(
+                    Block::default()
+                        .title("Start X")
+                        .borders(Borders::ALL)
+                        .border_style(self.get_box_style(SelectedBox::StartX)),
+                )
+                .style(self.get_input_style(SelectedBox::StartX))
+                .wrap(false)
+                .render(&mut f, input_section[3]);
+
+            Paragraph::new([Text::raw(&self.end_x_input.display_string)].iter())
+                .block(
+                    Block::default()
+                        .title("End X")
+                        .borders(Borders::ALL)
+                        .border_style(self.get_box_style(SelectedBox::EndX)),
+                )
+                .style(self.get_input_style(SelectedBox::EndX))
+                .wrap(false)
+                .render(&mut f, input_section[4]);
+
+            Paragraph::new([Text::raw(&self.family_input.string)].iter())
+                .block(
+                    Block::default()
+                        .title("Family")
+                        .borders(Borders::ALL)
+                        .border_style(self.get_box_style(SelectedBox::Family)),
+                )
+                .style(self.get_input_style(SelectedBox::Family))
+                .wrap(false)
+                .render(&mut f, input_section[5]);
+
+            let theme = &THEMES[self.theme];
+            let roots: Vec<(f64, f64)> = self.roots.iter().map(|&x| (x, 0.0)).collect();
+
+            let var_name = &self.var_name;
+            let title = self.plot_title();
+            // Without a function the axes are dimmed, so that the empty chart doesn't look like a
+            // plot of a function that is undefined everywhere.
+            let axis_style = match self.plot_error {
+                Some(Error::ParseError) => Style::default().modifier(Modifier::DIM),
+                _ => Style::default(),
+            };
+            // The trace line is drawn as a column of points spanning the Y bounds.
+            let cursor: Vec<(f64, f64)> = match self.cursor_x {
+                Some(x) => (0..=CURSOR_STEPS as u32)
+                    .map(|i| {
+                        let fraction = f64::from(i) / CURSOR_STEPS;
+                        (x, self.start_y + fraction * (self.end_y - self.start_y))
+                    })
+                    .collect(),
+                None => Vec::new(),
+            };
+
+            let mut datasets: Vec<Dataset> = if self.family.is_empty() {
+                vec![Dataset::default()
+                    .marker(Marker::Braille)
+                    .style(Style::default().fg(theme.plot))
+                    .data(&self.evaluation)]
+            } else {
+                self.family
+                    .iter()
+                    .enumerate()
+                    .map(|(i, curve)| {
+                        Dataset::default()
+                            .marker(Marker::Braille)
+                            .style(Style::default().fg(family_color(theme, i, self.family.len())))
+                            .data(curve)
+                    })
+                    .collect()
+            };
+            // The slope is drawn against the same Y axis, which is fitted to the function alone.
+            let slope = if self.show_slope {
+                finite_difference(&self.evaluation)
+            } else {
+                Vec::new()
+            };
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .style(Style::default().fg(theme.slope))
+                    .data(&slope),
+            );
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Dot)
+                    .style(Style::default().fg(theme.accent))
+                    .data(&roots),
+            );
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .style(Style::default().fg(theme.accent))
+                    .data(&cursor),
+            );
+
+            let (x_ticks, y_ticks) = self.axis_ticks();
+            Chart::default()
+                .block(Block::default().title(&title).borders(Borders::ALL))
+                .x_axis(
+                    Axis::default()
+                        .title(var_name)
+                        .style(axis_style)
+                        .labels_style(axis_style)
+                        .bounds([
+                            self.start_x_input.number_value,
+                            self.end_x_input.number_value,
+                        ])
+                        .labels(&x_ticks),
+                )
+                .y_axis(
+                    Axis::default()
+                        .title("Y")
+                        .style(axis_style)
+                        .labels_style(axis_style)
+                        .bounds([self.start_y, self.end_y])
+                        .labels(&y_ticks),
+                )
+                .datasets(&datasets)
+                .render(&mut f, chunks[1]);
+
+            Paragraph::new([Text::raw(self.readout_text())].iter())
+                .block(Block::default().title("Readout").borders(Borders::ALL))
+                .wrap(false)
+                .render(&mut f, chunks[2]);
+
+            let status_style = match self.plot_error {
+                Some(_) => Style::default().fg(theme.error).modifier(Modifier::BOLD),
+                None => Style::default().fg(theme.accent),
+            };
+            Paragraph::new([Text::raw(&self.status)].iter())
+                .style(status_style)
+                .wrap(false)
+                .render(&mut f, chunks[3]);
+
+            let timing = format_timing(self.evaluation.len(), self.update_duration);
+            Paragraph::new([Text::raw(&timing)].iter())
+                .alignment(Alignment::Right)
+                .wrap(false)
+                .render(&mut f, chunks[3]);
+        };
+
+        t.draw(f)
+    }
+
+    fn start(&mut self) -> Result<(), io::Error> {
+        install_panic_hook();
+
+        let stdout = MouseTerminal::from(io::stdout().into_raw_mode()?);
+        let backend = TermionBackend::new(stdout);
+        let mut terminal = Terminal::new(backend)?;
+
+        terminal.clear().unwrap();
+        terminal.hide_cursor().unwrap();
+
+        let mut term_size = terminal.size().unwrap();
+        self.resolution = plot_resolution(term_size.width, self.resolution_multiplier);
+
+        // Nothing can be typed yet, so the first plot is sampled right away.
+        self.update();
+
+        self.draw(&mut terminal)?;
+
+        // Events are read on a thread of their own, and the function is sampled on another, so
+        // that typing never waits for an update to finish. Both send their results to this one.
+        let (sender, messages) = mpsc::channel();
+        let events = sender.clone();
+        thread::spawn(move || {
+            for c in io::stdin().events() {
+                if events.send(Message::Event(c)).is_err() {
+                    break;
+                }
+            }
+        });
+        let (requests, worker_requests) = mpsc::channel::<PlotRequest>();
+        thread::spawn(move || {
+            for request in worker_requests.iter() {
+                // Requests that were superseded while waiting are skipped.
+                let request = worker_requests.try_iter().last().unwrap_or(request);
+                if let Ok(plot) = sample_plot(&request) {
+                    if sender.send(Message::Plotted(Box::new(plot))).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        // The interface is only drawn when something changed, but the terminal isn't notified of
+        // being resized, so its size is polled while nothing happens.
+        // When the plot is to be updated after typing, see `debounce`.
+        let mut replot_at: Option<Instant> = None;
+        loop {
+            let timeout = replot_at.map_or(RESIZE_POLL_INTERVAL, |at| {
+                at.saturating_duration_since(Instant::now()).min(RESIZE_POLL_INTERVAL)
+            });
+            let message = match messages.recv_timeout(timeout) {
+                Ok(message) => Some(message),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            let size = terminal.size().unwrap();
+            let resized = term_size != size;
+            if resized {
+                terminal.resize(size).unwrap();
+                term_size = size;
+                self.resolution = plot_resolution(term_size.width, self.resolution_multiplier);
+            }
+            let (mut replot, changed) = match message {
+                Some(Message::Event(c)) => {
+                    let c = match c {
+                        Ok(c) => c,
+                        // The terminal is left usable, as when panicking.
+                        Err(error) => {
+                            let _ = restore_terminal(&mut io::stdout());
+                            return Err(error);
+                        }
+                    };
+                    let typing = matches!(c, event::Event::Key(ref key) if is_typing(key));
+                    match self.process_event(c, size) {
+                        ApplicationOperation::Exit => break,
+                        ApplicationOperation::Replot if typing => {
+                            replot_at = Some(Instant::now() + self.debounce);
+                            (resized, true)
+                        }
+                        ApplicationOperation::Replot => (true, true),
+                        ApplicationOperation::Redraw => (resized, true),
+                    }
+                }
+                Some(Message::Plotted(plot)) => (resized, self.receive_plot(*plot)),
+                None => (resized, false),
+            };
+            if replot_at.is_some_and(|at| at <= Instant::now()) {
+                replot = true;
+            }
+            if replot {
+                replot_at = None;
+                self.begin_update();
+                if let Some(request) = self.request_plot() {
+                    requests.send(request).expect("the evaluation worker stopped");
+                }
+            }
+
+            if replot || changed {
+                self.draw(&mut terminal)?;
+            }
+        }
+        terminal.clear().unwrap();
+        terminal.show_cursor().unwrap();
+        Ok(())
+    }
+
+    /// Handles `event`, and tells whether the plot has to be sampled again for it, or only drawn.
+    fn process_event(&mut self, event: event::Event, size: Rect) -> ApplicationOperation {
+        let inputs = self.sampled_inputs();
+        match event {
+            event::Event::Key(key) => {
+                // Any edit may change the plot, so the readout would be stale.
+                self.readout = None;
+                if let ApplicationOperation::Exit = self.process_input(&key) {
+                    return ApplicationOperation::Exit;
+                }
+            }
+            event::Event::Mouse(mouse) => self.process_mouse(&mouse, size),
+            event::Event::Unsupported(sequence) => {
+                if let Some(step) = modified_arrow_step(&sequence) {
+                    self.readout = None;
+                    self.step_bound(step);
+                }
+            }
+        };
+        if self.sampled_inputs() == inputs {
+            ApplicationOperation::Redraw
+        } else {
+            ApplicationOperation::Replot
+        }
+    }
+
+    fn sampled_inputs(&self) -> SampledInputs {
+        SampledInputs {
+            function: self.function_input.string.clone(),
+            var_name: self.var_name_input.string.clone(),
+            start_x: self.start_x_input.number_value,
+            end_x: self.end_x_input.number_value,
+            family: self.family_input.string.clone(),
+            robust_y_bounds: self.robust_y_bounds,
+            previous_function: self.previous_function.clone(),
+        }
+    }
+
+    /// Steps the focused bound of the domain by `step`, if a bound is focused.
+    fn step_bound(&mut self, step: f64) {
+        match self.selected_box {
+            SelectedBox::StartX => self.start_x_input.step(step),
+            SelectedBox::EndX => self.end_x_input.step(step),
+            _ => return,
+        }
+        self.show_domain();
+    }
+
+    /// Sets `computing` if the last update was slow, so that the title says the plot is being
+    /// computed until the next one arrives.
+    fn begin_update(&mut self) {
+        self.computing = self.update_duration >= SLOW_UPDATE;
+    }
+
+    /// Re-plots the function right away, updating the sampled points, the Y bounds, and the roots.
+    fn update(&mut self) {
+        if let Some(request) = self.request_plot() {
+            if let Ok(plot) = sample_plot(&request) {
+                self.receive_plot(plot);
+            }
+        }
+    }
+
+    /// Starts an update, superseding the one in progress if there is one, and returns what is to
+    /// be sampled for it. If the function can't be plotted, the plot is cleared right away and
+    /// there is nothing to sample.
+    fn request_plot(&mut self) -> Option<PlotRequest> {
+        self.generation += 1;
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::new(AtomicBool::new(false));
+        match self.plot_request() {
+            Ok(request) => Some(request),
+            Err(error) => {
+                self.show_error(error);
+                None
+            }
+        }
+    }
+
+    /// Shows the points sampled for the latest update, or drops them if a newer one was
+    /// requested since they were. Returns whether they were shown.
+    fn receive_plot(&mut self, plot: Plot) -> bool {
+        if plot.generation != self.generation {
+            return false;
+        }
+        self.update_duration = plot.duration;
+        self.computing = false;
+        self.plot_error = None;
+        self.evaluation = plot.evaluation;
+        self.family = plot.family;
+        self.start_y = plot.y_bounds.0;
+        self.end_y = plot.y_bounds.1;
+        self.roots = plot.roots;
+        self.cursor_y = plot.cursor_y;
+        self.plotted_function = Some(plot.func.clone());
+        self.var_name = plot.var_name;
+        let var_name = &self.var_name;
+        let (func, constant) = (&plot.func, plot.constant);
+        let (domain_errors, resolution) = (plot.domain_errors, plot.resolution);
+        self.status = self
+            .parse_diagnostic
+            .clone()
+            .or_else(|| unbound_variable_message(func, var_name))
+            .or_else(|| constant.map(|value| format!("Constant: {}", value)))
+            .or_else(|| domain_error_message(domain_errors, resolution))
+            .unwrap_or_default();
+        true
+    }
+
+    /// Clears the plot because of `error`, which the status line explains.
+    fn show_error(&mut self, error: Error) {
+        self.computing = false;
+        self.status = self
+            .parse_diagnostic
+            .clone()
+            .unwrap_or_else(|| error.to_string());
+        self.plot_error = Some(error);
+        self.evaluation = Vec::new();
+        self.family = Vec::new();
+        self.start_y = 0.0;
+        self.end_y = 0.0;
+        self.roots = Vec::new();
+        self.cursor_y = None;
+        self.plotted_function = None;
+    }
+
+    /// The title of the plot, which shows the value read off at the trace line or the last mouse
+    /// click, and why nothing is plotted if the function doesn't parse.
+    fn plot_title(&self) -> String {
+        let var_name = &self.var_name;
+        let title = match (&self.plot_error, self.readout, self.cursor_x) {
+            (Some(Error::ParseError), _, _) => String::from("Plot (parse error)"),
+            (_, Some((x, y)), _) => format!("Plot ({})", cursor_label(var_name, x, Some(y))),
+            (_, None, Some(x)) => format!("Plot ({})", cursor_label(var_name, x, self.cursor_y)),
+            (_, None, None) => String::from("Plot"),
+        };
+        if self.computing {
+            title + " [computing…]"
+        } else {
+            title
+        }
+    }
+
+    /// What the readout shows: the value of the function at the trace line, if there is one and
+    /// the function is plotted.
+    fn readout_text(&self) -> String {
+        match (&self.plot_error, self.cursor_x) {
+            (None, Some(x)) => format_readout(x, self.cursor_y),
+            _ => String::from(READOUT_PLACEHOLDER),
+        }
+    }
+
+    /// Snapshots the inputs that the function is sampled from, parsing the function.
+    fn plot_request(&mut self) -> Result<PlotRequest, Error> {
+        self.parse_diagnostic = None;
+        self.error_span = None;
+        if self.start_x_input.number_value >= self.end_x_input.number_value {
+            return Err(Error::RangeError);
+        }
+        let func = self.parse_partial_function()?;
+        let family = parse_family(&self.family_input.string);
+        let parameter = family.as_ref().map(|(parameter, _)| parameter.as_str());
+        let var_name = sweep_variable(&func, &self.var_name_input.string, parameter);
+        Ok(PlotRequest {
+            generation: self.generation,
+            func,
+            var_name,
+            start_x: self.start_x_input.number_value,
+            end_x: self.end_x_input.number_value,
+            resolution: self.resolution,
+            family,
+            robust_y_bounds: self.robust_y_bounds,
+            cursor_x: self.cursor_x,
+            cancel: Arc::clone(&self.cancel),
+        })
+    }
+
+    fn parse_function(&self) -> Result<expression::ExpressionNode, Error> {
+        match parser::parse(&self.function_input.string) {
+            Ok(func) => resolve_previous_function(func, self.previous_function.as_ref()),
+            Err(_) => Err(Error::ParseError),
+        }
+    }
+
+    /// Like `parse_function`, but plots as much of the function as parses while it is being typed,
+    /// e.g. `sin(x)` of `sin(x)+`, and keeps what was wrong with the rest in `parse_diagnostic`.
+    fn parse_partial_function(&mut self) -> Result<expression::ExpressionNode, Error> {
+        let (func, diagnostics) = parser::parse_lenient(&self.function_input.string);
+        let diagnostic = diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.severity == parser::Severity::Error)
+            .or_else(|| diagnostics.first());
+        self.parse_diagnostic = diagnostic.map(|diagnostic| match diagnostic.severity {
+            parser::Severity::Error => format!("Parse error: {}", diagnostic.message),
+            parser::Severity::Warning => format!("Warning: {}", diagnostic.message),
+        });
+        self.error_span = diagnostic
+            .filter(|diagnostic| diagnostic.severity == parser::Severity::Error)
+            .map(|diagnostic| diagnostic.span.clone());
+        match func {
+            Some(func) => resolve_previous_function(func, self.previous_function.as_ref()),
+            None => Err(Error::ParseError),
+        }
+    }
+
+    fn get_input_style(&self, _selected: SelectedBox) -> Style {
+        // leaving this method as a reference how to change the text of focused input
+        Style::default()
+    }
+
+    fn get_box_style(&self, selected: SelectedBox) -> Style {
+        if selected == self.selected_box {
+            Style::default().fg(THEMES[self.theme].focused_border)
+        } else {
+            Style::default().fg(THEMES[self.theme].unfocused_border)
+        }
+    }
+}
+
+pub fn display(config: &Config) -> Result<(), io::Error> {
+    let mut application = Application::from_config(config);
+    if let Some(path) = history_path() {
+        application.history = History::load(path);
+    }
     application.start()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(keys: &str) -> NumberInput {
+        let mut input = NumberInput {
+            display_string: String::from("+0"),
+            number_value: 0.0,
+        };
+        for key in keys.chars() {
+            match key {
+                '<' => input.process_input(&event::Key::Backspace),
+                _ => input.process_input(&event::Key::Char(key)),
+            }
+        }
+        input
+    }
+
+    #[test]
+    fn fine_steps_are_kept_on_large_bounds() {
+        let mut input = typed("1e9");
+        input.step(0.001);
+        assert_eq!(input.number_value, 1_000_000_000.001);
+        assert_eq!(input.display_string, "+1000000000.001");
+        input.step(FINE_NUMBER_STEP);
+        assert_eq!(input.display_string, "+1000000000.101");
+
+        let mut input = typed("-1e12");
+        input.step(FINE_NUMBER_STEP);
+        assert_eq!(input.display_string, "-999999999999.9");
+    }
+
+    #[test]
+    fn bounds_are_stepped_finely_and_coarsely() {
+        assert_eq!(modified_arrow_step(b"\x1b[1;2A"), Some(0.1));
+        assert_eq!(modified_arrow_step(b"\x1b[1;2B"), Some(-0.1));
+        assert_eq!(modified_arrow_step(b"\x1b[1;5A"), Some(10.0));
+        assert_eq!(modified_arrow_step(b"\x1b[1;5B"), Some(-10.0));
+        assert_eq!(modified_arrow_step(b"\x1b[1;3A"), None);
+        assert_eq!(modified_arrow_step(b"\x1b[1;2C"), None);
+        assert_eq!(modified_arrow_step(b"\x1b[1;2"), None);
+
+        let shift_up = event::Event::Unsupported(b"\x1b[1;2A".to_vec());
+        let ctrl_down = event::Event::Unsupported(b"\x1b[1;5B".to_vec());
+        let size = Rect::new(0, 0, 80, 24);
+        let mut application = Application::new();
+        application.selected_box = SelectedBox::StartX;
+        for _ in 0..3 {
+            application.process_event(shift_up.clone(), size);
+        }
+        assert_eq!(application.start_x_input.number_value, 0.3);
+        assert_eq!(application.start_x_input.display_string, "+0.3");
+        application.process_event(ctrl_down.clone(), size);
+        assert_eq!(application.start_x_input.number_value, -9.7);
+        application.process_input(&event::Key::PageUp);
+        application.process_input(&event::Key::Up);
+        assert_eq!(application.start_x_input.number_value, 1.3);
+        assert_eq!(application.start_x_input.display_string, "+1.3");
+
+        // Only the bounds are stepped.
+        application.selected_box = SelectedBox::Function;
+        application.process_event(ctrl_down, size);
+        assert_eq!(application.function_input.string, "sin(x)");
+        assert_eq!(application.end_x_input.number_value, 10.0);
+    }
+
+    #[test]
+    fn numbers_are_typed_in_scientific_notation() {
+        let input = typed("1e3");
+        assert_eq!(input.display_string, "+1e3");
+        assert_eq!(input.number_value, 1000.0);
+
+        let input = typed("-2.5E1");
+        assert_eq!(input.display_string, "-2.5e1");
+        assert_eq!(input.number_value, -25.0);
+
+        // Only one exponent, which has to follow a digit and can't have a fraction.
+        assert_eq!(typed("1e3e").display_string, "+1e3");
+        assert_eq!(typed("1.e").display_string, "+1.");
+        assert_eq!(typed("1e3.").display_string, "+1e3");
+
+        // The exponent is never left without digits.
+        let input = typed("1e");
+        assert_eq!(input.display_string, "+1e0");
+        assert_eq!(input.number_value, 1.0);
+        let input = typed("1e3<");
+        assert_eq!(input.display_string, "+1");
+        assert_eq!(input.number_value, 1.0);
+
+        // Signs after the `e` are the exponent's.
+        let input = typed("1e-3");
+        assert_eq!(input.display_string, "+1e-3");
+        assert_eq!(input.number_value, 0.001);
+        assert_eq!(typed("-1e-").display_string, "-1e-0");
+        assert_eq!(typed("1e-3+").display_string, "+1e3");
+        assert_eq!(typed("1e-3<").display_string, "+1");
+
+        // Digits that would overflow are ignored.
+        let input = typed("1e3999");
+        assert_eq!(input.display_string, "+1e39");
+        assert!(input.number_value.is_finite());
+    }
+
+    #[test]
+    fn renamed_variable_is_swept() {
+        let mut application = Application::new();
+        application.function_input.string = String::from("t^2");
+        application.var_name_input.string = String::from("t");
+        application.resolution = 10;
+        application.update();
+
+        assert_eq!(application.evaluation.len(), 9);
+        for &(t, y) in &application.evaluation {
+            assert_eq!(y, t * t);
+        }
+        assert_eq!(application.status, "");
+    }
+
+    #[test]
+    fn single_variable_is_swept() {
+        let func = parser::parse("sin(t)").unwrap();
+        assert_eq!(sweep_variable(&func, "x", None), "t");
+        let func = parser::parse("a * t").unwrap();
+        assert_eq!(sweep_variable(&func, "x", Some("a")), "t");
+
+        let mut application = Application::new();
+        application.function_input.string = String::from("sin(t)");
+        application.update();
+        assert!(!application.evaluation.is_empty());
+        assert_eq!(application.status, "");
+        assert_eq!(application.var_name, "t");
+    }
+
+    #[test]
+    fn zero_variables_fall_back_to_x() {
+        let func = parser::parse("3+4").unwrap();
+        assert_eq!(sweep_variable(&func, "x", None), "x");
+    }
+
+    #[test]
+    fn two_variables_fall_back_to_x() {
+        let func = parser::parse("t*y").unwrap();
+        assert_eq!(sweep_variable(&func, "x", None), "x");
+        let func = parser::parse("t*x").unwrap();
+        assert_eq!(sweep_variable(&func, "x", None), "x");
+    }
+
+    #[test]
+    fn unknown_variable_is_reported() {
+        let mut application = Application::new();
+        application.function_input.string = String::from("sin(t) * y");
+        application.update();
+
+        assert!(application.evaluation.is_empty());
+        assert_eq!(application.status, "Unknown variable `t`; the plot sweeps `x`");
+    }
+
+    #[test]
+    fn enter_is_not_typed_into_the_boxes() {
+        let mut application = Application::new();
+        for selected_box in [SelectedBox::Variable, SelectedBox::Domain, SelectedBox::Family] {
+            application.selected_box = selected_box;
+            application.process_input(&event::Key::Char('\n'));
+        }
+        assert_eq!(application.var_name_input.string, "x");
+        assert_eq!(application.domain_input.string, "[0, 10]");
+        assert_eq!(application.family_input.string, "");
+    }
+
+    #[test]
+    fn constant_functions_show_their_value() {
+        let mut application = Application::new();
+        application.function_input.string = String::from("3 + 4");
+        application.update();
+        assert_eq!(application.status, "Constant: 7");
+
+        application.function_input.string = String::from("x + 4");
+        application.update();
+        assert_eq!(application.status, "");
+    }
+
+    #[test]
+    fn domain_errors_are_reported() {
+        let mut application = Application::new();
+        application.start_x_input.set_value(-10.5);
+        application.end_x_input.set_value(9.5);
+        application.resolution = 20;
+        application.function_input.string = String::from("ln(x)");
+        application.update();
+
+        assert_eq!(application.evaluation.len(), 9);
+        assert_eq!(application.status, "Domain error at 11 of 20 points");
+
+        application.function_input.string = String::from("ln(x^2 + 1)");
+        application.update();
+        assert_eq!(application.status, "");
+
+        // The first curve of a family is counted, at the bounds the plot was requested with.
+        application.function_input.string = String::from("ln(a * x)");
+        application.family_input.string = String::from("a = -1, 1");
+        let request = application.request_plot().unwrap();
+        application.resolution = 40;
+        application.end_x_input.set_value(100.0);
+        application.receive_plot(sample_plot(&request).unwrap());
+        assert_eq!(application.status, "Domain error at 9 of 20 points");
+    }
+
+    #[test]
+    fn parse_errors_are_shown_in_the_title() {
+        let mut application = Application::new();
+        application.update();
+        assert_eq!(application.plot_title(), "Plot");
+
+        application.function_input.string = String::from(")");
+        application.update();
+        assert_eq!(application.plot_error, Some(Error::ParseError));
+        assert_eq!(application.plot_title(), "Plot (parse error)");
+        application.computing = true;
+        assert_eq!(application.plot_title(), "Plot (parse error) [computing…]");
+
+        application.function_input.string = String::from("sin(x)");
+        application.update();
+        assert_eq!(application.plot_error, None);
+        application.cursor_x = Some(0.0);
+        assert_eq!(application.plot_title(), "Plot (x = 0.00, y = undefined)");
+    }
+
+    #[test]
+    fn superseded_plots_are_dropped() {
+        let mut application = Application::new();
+        application.update();
+        let evaluation = application.evaluation.clone();
+
+        application.function_input.string = String::from("x^2");
+        let stale = application.request_plot().unwrap();
+        application.function_input.string = String::from("x^3");
+        let latest = application.request_plot().unwrap();
+        // Sampling stops as soon as a newer update is requested.
+        assert!(stale.cancel.load(Ordering::Relaxed));
+        assert!(sample_plot(&stale).is_err());
+
+        let mut plot = sample_plot(&latest).unwrap();
+        plot.generation = stale.generation;
+        assert!(!application.receive_plot(plot));
+        assert_eq!(application.evaluation, evaluation);
+
+        assert!(application.receive_plot(sample_plot(&latest).unwrap()));
+        assert_ne!(application.evaluation, evaluation);
+        assert_eq!(application.evaluation.last().unwrap().1, 9.9f64.powi(3));
+    }
+
+    #[test]
+    fn families_are_parsed() {
+        assert_eq!(parse_family("a = 0, 1, 2"), Some((String::from("a"), vec![0.0, 1.0, 2.0])));
+        assert_eq!(parse_family("k=-0.5"), Some((String::from("k"), vec![-0.5])));
+        assert_eq!(parse_family(""), None);
+        assert_eq!(parse_family("a = 0,"), None);
+        assert_eq!(parse_family("a b = 1"), None);
+        assert_eq!(parse_family("= 1"), None);
+        assert_eq!(parse_family("a = 1, inf"), None);
+        assert_eq!(parse_family("a = 1, 2, 3, 4, 5, 6, 7, 8, 9"), None);
+    }
+
+    #[test]
+    fn families_are_plotted_as_one_curve_per_value() {
+        let mut application = Application::new();
+        application.function_input.string = String::from("x + a");
+        application.family_input.string = String::from("a = 1, 2, 3");
+        application.resolution = 10;
+        application.update();
+
+        assert_eq!(application.family.len(), 3);
+        for (curve, a) in application.family.iter().zip(&[1.0, 2.0, 3.0]) {
+            assert_eq!(curve.len(), 10);
+            for &(x, y) in curve {
+                assert_eq!(y, x + a);
+            }
+        }
+        assert_eq!(application.evaluation, application.family[0]);
+        assert_eq!((application.start_y, application.end_y), (1.0, 12.0));
+        assert_eq!(application.status, "");
+
+        application.family_input.string.clear();
+        application.update();
+        assert!(application.family.is_empty());
+        assert_eq!(application.status, "Unknown variable `a`; the plot sweeps `x`");
+    }
+
+    #[test]
+    fn boxes_are_cycled_with_wrap_around() {
+        assert_eq!(next_box(SelectedBox::Function), SelectedBox::Variable);
+        assert_eq!(next_box(SelectedBox::Family), SelectedBox::Function);
+        assert_eq!(prev_box(SelectedBox::Function), SelectedBox::Family);
+        assert_eq!(prev_box(SelectedBox::StartX), SelectedBox::Domain);
+        for &selected in &SelectedBox::ALL {
+            assert_eq!(prev_box(next_box(selected)), selected);
+        }
+
+        // Tab wraps around, but the arrow keys stop at the ends.
+        let mut application = Application::new();
+        application.process_input(&event::Key::Left);
+        assert_eq!(application.selected_box, SelectedBox::Function);
+        application.process_input(&event::Key::BackTab);
+        assert_eq!(application.selected_box, SelectedBox::Family);
+        application.process_input(&event::Key::Right);
+        assert_eq!(application.selected_box, SelectedBox::Family);
+        application.process_input(&event::Key::Char('\t'));
+        assert_eq!(application.selected_box, SelectedBox::Function);
+        assert_eq!(application.function_input.string, "sin(x)");
+    }
+
+    #[test]
+    fn only_typing_is_debounced() {
+        assert!(is_typing(&event::Key::Char('s')));
+        assert!(is_typing(&event::Key::Char('(')));
+        assert!(is_typing(&event::Key::Backspace));
+        assert!(!is_typing(&event::Key::Char('\n')));
+        assert!(!is_typing(&event::Key::Char('\t')));
+        assert!(!is_typing(&event::Key::Up));
+        assert!(is_typing(&event::Key::Ctrl('w')));
+        assert!(!is_typing(&event::Key::Ctrl('r')));
+        assert!(!is_typing(&event::Key::Ctrl('a')));
+    }
+
+    #[test]
+    fn axis_ticks_are_centered_on_the_domain() {
+        assert_eq!(axis_ticks(1.0, 5.0, 2), ["1.00", "3.00", "5.00"]);
+        assert_eq!(axis_ticks(-10.0, 10.0, 2), ["-10.00", "0.00", "10.00"]);
+        assert_eq!(axis_ticks(-0.5, 0.25, 2), ["-0.50", "-0.12", "0.25"]);
+    }
+
+    #[test]
+    fn precision_follows_the_span_of_the_axis() {
+        assert_eq!(auto_precision(0.0, 0.001), 5);
+        assert_eq!(axis_ticks(0.0, 0.001, 5), ["0.00000", "0.00050", "0.00100"]);
+        assert_eq!(auto_precision(0.0, 10.0), 1);
+        assert_eq!(auto_precision(-0.5, 0.25), 2);
+        assert_eq!(auto_precision(0.0, 1000.0), 0);
+        assert_eq!(axis_ticks(0.0, 1000.0, 0), ["0", "500", "1000"]);
+        assert_eq!(auto_precision(1e6, 1e9), 0);
+        assert_eq!(auto_precision(0.0, 1e-20), MAX_LABEL_PRECISION);
+        assert_eq!(auto_precision(0.0, 0.0), DEFAULT_LABEL_PRECISION);
+        assert_eq!(auto_precision(0.0, f64::INFINITY), DEFAULT_LABEL_PRECISION);
+    }
+
+    #[test]
+    fn timing_is_formatted_in_milliseconds() {
+        assert_eq!(format_timing(300, Duration::from_micros(1250)), "300 points in 1.25 ms");
+        assert_eq!(format_timing(0, Duration::default()), "0 points in 0.00 ms");
+        assert_eq!(format_timing(1, Duration::from_secs(2)), "1 points in 2000.00 ms");
+    }
+
+    #[test]
+    fn layout_has_a_status_line() {
+        let chunks = main_layout(Rect::new(0, 0, 80, 24));
+        assert_eq!(chunks[0].height, 3);
+        assert_eq!(chunks[2].height, 3);
+        assert_eq!(chunks[3].height, 1);
+        assert_eq!(chunks[3].y, 22);
+    }
+
+    #[test]
+    fn history_is_navigated_with_up_and_down() {
+        let mut application = Application::new();
+        for func in &["sin(x)", "x^2", "x^2", "ans + 1"] {
+            application.function_input.string = func.to_string();
+            application.process_input(&event::Key::Char('\n'));
+        }
+        // Failing to parse doesn't record anything.
+        application.function_input.string = String::from("(");
+        application.process_input(&event::Key::Char('\n'));
+        assert_eq!(application.history.entries, vec!["sin(x)", "x^2", "ans + 1"]);
+
+        application.function_input.string = String::from("draft");
+        let mut press = |key| {
+            application.process_input(&key);
+            application.function_input.string.clone()
+        };
+        assert_eq!(press(event::Key::Up), "ans + 1");
+        assert_eq!(press(event::Key::Up), "x^2");
+        assert_eq!(press(event::Key::Up), "sin(x)");
+        assert_eq!(press(event::Key::Up), "sin(x)");
+        assert_eq!(press(event::Key::Down), "x^2");
+        assert_eq!(press(event::Key::Down), "ans + 1");
+        assert_eq!(press(event::Key::Down), "draft");
+        assert_eq!(press(event::Key::Down), "draft");
+    }
+
+    #[test]
+    fn empty_history_leaves_the_input_alone() {
+        let mut history = History::new();
+        assert_eq!(history.previous("sin(x)"), None);
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn history_keeps_the_latest_distinct_functions() {
+        let mut history = History::new();
+        for i in 0..MAX_HISTORY + 5 {
+            history.push(format!("x + {}", i));
+        }
+        history.push(String::from("x + 50"));
+        assert_eq!(history.entries.len(), MAX_HISTORY);
+        assert_eq!(history.entries[0], "x + 5");
+        assert_eq!(history.entries[MAX_HISTORY - 2], format!("x + {}", MAX_HISTORY + 4));
+        assert_eq!(history.entries[MAX_HISTORY - 1], "x + 50");
+        assert_eq!(history.entries.iter().filter(|entry| *entry == "x + 50").count(), 1);
+    }
+
+    #[test]
+    fn editing_an_entry_forks_it() {
+        let mut application = Application::new();
+        for func in &["sin(x)", "x^2"] {
+            application.function_input.string = func.to_string();
+            application.process_input(&event::Key::Char('\n'));
+        }
+        application.function_input.string = String::from("draft");
+        application.process_input(&event::Key::Up);
+        application.process_input(&event::Key::Up);
+        application.process_input(&event::Key::Char('2'));
+        assert_eq!(application.function_input.string, "sin(x)2");
+
+        // The edited entry is what Down comes back to, and the entry itself is unchanged.
+        application.process_input(&event::Key::Up);
+        assert_eq!(application.function_input.string, "x^2");
+        application.process_input(&event::Key::Down);
+        assert_eq!(application.function_input.string, "sin(x)2");
+        assert_eq!(application.history.entries, vec!["sin(x)", "x^2"]);
+
+        // Moving the cursor isn't an edit.
+        application.process_input(&event::Key::Up);
+        application.process_input(&event::Key::Up);
+        application.process_input(&event::Key::Left);
+        application.process_input(&event::Key::Down);
+        assert_eq!(application.function_input.string, "x^2");
+    }
+
+    #[test]
+    fn history_is_kept_between_sessions() {
+        let dir = env::temp_dir().join(format!("sexe-history-{}", std::process::id()));
+        let path = dir.join("sexe").join("history");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut history = History::load(path.clone());
+        assert!(history.entries.is_empty());
+        history.push(String::from("sin(x)"));
+        history.push(String::from("x^2"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "sin(x)\nx^2\n");
+        assert_eq!(History::load(path.clone()).entries, vec!["sin(x)", "x^2"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn domains_are_parsed() {
+        assert_eq!(parse_domain("[-5, 5]"), Some((-5.0, 5.0)));
+        assert_eq!(parse_domain("[0,10]"), Some((0.0, 10.0)));
+        assert_eq!(parse_domain(" [ 0.5 , 1e3 ] "), Some((0.5, 1000.0)));
+        assert_eq!(parse_domain("[5]"), None);
+        assert_eq!(parse_domain("[1, 2, 3]"), None);
+        assert_eq!(parse_domain("[1, 2"), None);
+        assert_eq!(parse_domain("[a, 2]"), None);
+        assert_eq!(parse_domain("[-inf, 2]"), None);
+        assert_eq!(parse_domain("]"), None);
+    }
+
+    #[test]
+    fn typing_a_domain_updates_both_bounds() {
+        let mut application = Application::new();
+        application.selected_box = SelectedBox::Domain;
+        application.domain_input.string = String::from("[-5, 5");
+        application.process_input(&event::Key::Char(']'));
+
+        assert_eq!(application.start_x_input.number_value, -5.0);
+        assert_eq!(application.start_x_input.display_string, "-5");
+        assert_eq!(application.end_x_input.number_value, 5.0);
+        assert_eq!(application.end_x_input.display_string, "+5");
+
+        // Incomplete input leaves the bounds alone.
+        application.process_input(&event::Key::Backspace);
+        assert_eq!(application.start_x_input.number_value, -5.0);
+        assert_eq!(application.end_x_input.number_value, 5.0);
+    }
+
+    #[test]
+    fn editing_a_bound_updates_the_domain() {
+        let mut application = Application::new();
+        application.selected_box = SelectedBox::StartX;
+        application.process_input(&event::Key::Char('-'));
+        application.process_input(&event::Key::Char('2'));
+        assert_eq!(application.domain_input.string, "[-2, 10]");
+        application.process_input(&event::Key::Up);
+        assert_eq!(application.domain_input.string, "[-1, 10]");
+
+        application.selected_box = SelectedBox::EndX;
+        application.process_input(&event::Key::Backspace);
+        assert_eq!(application.domain_input.string, "[-1, 1]");
+        let shift_up = event::Event::Unsupported(b"\x1b[1;2A".to_vec());
+        application.process_event(shift_up, Rect::new(0, 0, 80, 24));
+        assert_eq!(application.domain_input.string, "[-1, 1.1]");
+    }
+
+    #[test]
+    fn plot_resolution_is_bounded() {
+        assert_eq!(plot_resolution(80, 3), 240);
+        assert_eq!(plot_resolution(u16::MAX, 64), MAX_RESOLUTION);
+        assert_eq!(plot_resolution(u16::MAX, u32::MAX), MAX_RESOLUTION);
+    }
+
+    #[test]
+    fn applications_start_with_the_config() {
+        let application = Application::from_config(&Config {
+            function: Some(String::from("x^2")),
+            start_x: Some(-2.5),
+            end_x: None,
+            resolution_multiplier: Some(5),
+        });
+        assert_eq!(application.function_input.string, "x^2");
+        assert_eq!(application.start_x_input.number_value, -2.5);
+        assert_eq!(application.start_x_input.display_string, "-2.5");
+        assert_eq!(application.end_x_input.number_value, 10.0);
+        assert_eq!(application.domain_input.string, "[-2.5, 10]");
+        assert_eq!(application.resolution_multiplier, 5);
+
+        let application = Application::from_config(&Config::default());
+        assert_eq!(application.function_input.string, "sin(x)");
+        assert_eq!(application.domain_input.string, "[0, 10]");
+        assert_eq!(application.resolution_multiplier, DEFAULT_RESOLUTION_MULTIPLIER);
+    }
+
+    #[test]
+    fn robust_bounds_leave_out_outliers() {
+        // 0, 1, ..., 99, with a few huge spikes in between.
+        let mut ys: Vec<f64> = (0..100).map(f64::from).collect();
+        ys.extend(&[1e12, -1e12, 5e9, f64::INFINITY, f64::NAN]);
+        assert_eq!(robust_bounds(&ys, 2.0, 98.0), (1.0, 99.0));
+        assert_eq!(robust_bounds(&ys, 0.0, 100.0), (-1e12, 1e12));
+
+        assert_eq!(robust_bounds(&[], 2.0, 98.0), (0.0, 0.0));
+        assert_eq!(robust_bounds(&[f64::NAN, 3.0], 2.0, 98.0), (3.0, 3.0));
+    }
+
+    #[test]
+    fn robust_bounds_are_toggled() {
+        let mut application = Application::new();
+        application.function_input.string = String::from("tan(x)");
+        application.update();
+        let spread = application.end_y - application.start_y;
+
+        application.process_input(&event::Key::Ctrl('r'));
+        application.update();
+        assert!(application.robust_y_bounds);
+        assert!(application.end_y - application.start_y < spread);
+    }
+
+    #[test]
+    fn slopes_are_the_differences_between_points() {
+        let points: Vec<(f64, f64)> = (0..=20)
+            .map(|i| f64::from(i) * 0.25 - 2.5)
+            .map(|x| (x, x * x))
+            .collect();
+        let slope = finite_difference(&points);
+        assert_eq!(slope.len(), 20);
+        for &(x, dy) in &slope {
+            assert!((dy - 2.0 * x).abs() < 1e-12, "{} at {}", dy, x);
+        }
+
+        // There is no slope across the gap where ln is undefined, and none at all without pairs.
+        let points = vec![(-2.0, 1.0), (-1.0, 2.0), (2.0, 2.0f64.ln()), (3.0, 3.0f64.ln())];
+        let slope = finite_difference(&points);
+        assert_eq!(slope.len(), 2);
+        assert_eq!(slope[0], (-1.5, 1.0));
+        assert_eq!(slope[1].0, 2.5);
+        assert!(finite_difference(&points[..1]).is_empty());
+        assert!(finite_difference(&[]).is_empty());
+
+        let mut application = Application::new();
+        application.process_input(&event::Key::Ctrl('d'));
+        assert!(application.show_slope);
+    }
+
+    #[test]
+    fn themes_cycle_back_to_the_default() {
+        let mut theme = 0;
+        for expected in &[1, 2, 0, 1] {
+            theme = next_theme(theme);
+            assert_eq!(theme, *expected);
+        }
+
+        let mut application = Application::new();
+        application.process_input(&event::Key::Ctrl('t'));
+        assert_eq!(application.theme, 1);
+    }
+
+    #[test]
+    fn ans_is_replaced_by_the_previous_function() {
+        let previous = parser::parse("sin(x)").unwrap();
+        let func = parser::parse("2*ans").unwrap();
+        assert_eq!(
+            resolve_previous_function(func, Some(&previous)).unwrap(),
+            parser::parse("2*sin(x)").unwrap()
+        );
+    }
+
+    #[test]
+    fn ans_without_a_previous_function_is_an_error() {
+        let func = parser::parse("ans+1").unwrap();
+        assert!(matches!(
+            resolve_previous_function(func, None),
+            Err(Error::NoPreviousFunctionError)
+        ));
+
+        // Functions that don't reference `ans` don't need a previous function.
+        let func = parser::parse("x+1").unwrap();
+        assert!(resolve_previous_function(func, None).is_ok());
+    }
+
+    #[test]
+    fn errors_are_shown_until_the_function_is_plotted() {
+        let mut application = Application::new();
+        application.start_x_input.set_value(5.0);
+        application.end_x_input.set_value(-5.0);
+        application.update();
+        assert_eq!(application.plot_error, Some(Error::RangeError));
+        assert_eq!(application.status, "Empty domain: the start must be less than the end");
+
+        application.end_x_input.set_value(10.0);
+        application.function_input.string = String::from("ans^2");
+        application.update();
+        assert_eq!(application.plot_error, Some(Error::NoPreviousFunctionError));
+        assert_eq!(
+            application.status,
+            "`ans` refers to the previous function, but none was submitted with Enter"
+        );
+
+        application.function_input.string = String::from("x^2");
+        application.update();
+        assert_eq!(application.plot_error, None);
+        assert_eq!(application.status, "");
+    }
+
+    #[test]
+    fn restoring_the_terminal_shows_the_cursor() {
+        let mut out = Vec::new();
+        restore_terminal(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with(&termion::cursor::Show.to_string()));
+        assert!(out.contains(&termion::style::Reset.to_string()));
+    }
+
+    #[test]
+    fn columns_map_linearly_onto_the_domain() {
+        let area = Rect::new(10, 0, 11, 5);
+        assert_eq!(column_to_x(10, area, -5.0, 5.0), Some(-5.0));
+        assert_eq!(column_to_x(15, area, -5.0, 5.0), Some(0.0));
+        assert_eq!(column_to_x(20, area, -5.0, 5.0), Some(5.0));
+        assert_eq!(column_to_x(9, area, -5.0, 5.0), None);
+        assert_eq!(column_to_x(21, area, -5.0, 5.0), None);
+    }
+
+    #[test]
+    fn columns_of_the_chart_map_onto_the_domain() {
+        let area = Rect::new(0, 0, 40, 12);
+        let x_ticks = axis_ticks(-5.0, 5.0, 1);
+        let y_ticks = axis_ticks(-1000.0, 1000.0, 1);
+        let points = [(-5.0, 0.0), (5.0, 0.0)];
+        let datasets = [Dataset::default().marker(Marker::Dot).data(&points)];
+        let mut buffer = tui::buffer::Buffer::empty(area);
+        Chart::default()
+            .block(Block::default().borders(Borders::ALL))
+            .x_axis(Axis::default().bounds([-5.0, 5.0]).labels(&x_ticks))
+            .y_axis(Axis::default().bounds([-1000.0, 1000.0]).labels(&y_ticks))
+            .datasets(&datasets)
+            .draw(area, &mut buffer);
+
+        let graph_area = chart_graph_area(area, &x_ticks, &y_ticks);
+        // The Y axis is drawn right of the widest label, `-1000.0`, and left of the graph.
+        assert_eq!(graph_area.left(), 1 + 7 + 1);
+        let axis = buffer.get(graph_area.left() - 1, graph_area.top());
+        assert_eq!(axis.symbol, tui::symbols::line::VERTICAL);
+        let mut columns = Vec::new();
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                if buffer.get(x, y).symbol == tui::symbols::DOT {
+                    columns.push(x);
+                }
+            }
+        }
+        assert_eq!(columns.len(), 2);
+        assert_eq!(column_to_x(columns[0], graph_area, -5.0, 5.0), Some(-5.0));
+        assert_eq!(column_to_x(columns[1], graph_area, -5.0, 5.0), Some(5.0));
+    }
+
+    #[test]
+    fn nearest_point_is_found() {
+        let points = vec![(0.0, 1.0), (1.0, 2.0), (2.0, 3.0)];
+        assert_eq!(nearest_point(&points, 1.4), Some((1.0, 2.0)));
+        assert_eq!(nearest_point(&points, 1.6), Some((2.0, 3.0)));
+        assert_eq!(nearest_point(&[], 1.0), None);
+    }
+
+    #[test]
+    fn cursor_steps_are_clamped_to_the_domain() {
+        assert_eq!(step_cursor(None, 1.0, 0.0, 10.0), 5.0);
+        assert_eq!(step_cursor(Some(5.0), 1.0, 0.0, 10.0), 5.1);
+        assert_eq!(step_cursor(Some(5.0), -1.0, 0.0, 10.0), 4.9);
+        assert_eq!(step_cursor(Some(9.95), 1.0, 0.0, 10.0), 10.0);
+        assert_eq!(step_cursor(Some(0.05), -1.0, 0.0, 10.0), 0.0);
+        // A cursor left outside of a narrowed domain jumps back into it.
+        assert_eq!(step_cursor(Some(20.0), -1.0, 0.0, 10.0), 10.0);
+    }
+
+    #[test]
+    fn spans_are_split_at_character_boundaries() {
+        assert_eq!(split_at_span("sin(x) + )", &(9..10)), ("sin(x) + ", ")", ""));
+        // `π` takes two bytes, so the `)` after it is at byte 7 rather than 6.
+        assert_eq!(split_at_span("2*π + ) - 1", &(7..8)), ("2*π + ", ")", " - 1"));
+        // Spans that start or end inside `π` take all of it.
+        assert_eq!(split_at_span("2*π + 1", &(3..4)), ("2*", "π", " + 1"));
+        assert_eq!(split_at_span("2*π + 1", &(1..3)), ("2", "*π", " + 1"));
+        // Stale spans past the end of the input are cut off.
+        assert_eq!(split_at_span("ππ", &(3..9)), ("π", "π", ""));
+        assert_eq!(split_at_span("x", &(5..9)), ("x", "", ""));
+    }
+
+    #[test]
+    fn parse_errors_are_highlighted() {
+        let mut application = Application::new();
+        // `sin(x)` is plotted, and everything after it is highlighted.
+        application.function_input.string = String::from("sin(x) + )");
+        application.update();
+        assert_eq!(application.error_span, Some(7..10));
+
+        // Unclosed parentheses are only a warning.
+        application.function_input.string = String::from("sin(x");
+        application.update();
+        assert_eq!(application.error_span, None);
+    }
+
+    #[test]
+    fn functions_are_edited_at_the_cursor() {
+        let mut application = Application::new();
+        let mut press = |key| {
+            application.process_input(&key);
+            (application.function_input.string.clone(), application.function_input.cursor())
+        };
+        assert_eq!(press(event::Key::Left), (String::from("sin(x)"), 5));
+        assert_eq!(press(event::Key::Char('π')), (String::from("sin(xπ)"), 7));
+        assert_eq!(press(event::Key::Left), (String::from("sin(xπ)"), 5));
+        assert_eq!(press(event::Key::Left), (String::from("sin(xπ)"), 4));
+        assert_eq!(press(event::Key::Delete), (String::from("sin(π)"), 4));
+        assert_eq!(press(event::Key::Right), (String::from("sin(π)"), 6));
+        assert_eq!(press(event::Key::Backspace), (String::from("sin()"), 4));
+        assert_eq!(press(event::Key::Home), (String::from("sin()"), 0));
+        assert_eq!(press(event::Key::Left), (String::from("sin()"), 0));
+        assert_eq!(press(event::Key::Backspace), (String::from("sin()"), 0));
+        assert_eq!(press(event::Key::Char('2')), (String::from("2sin()"), 1));
+        assert_eq!(press(event::Key::End), (String::from("2sin()"), 6));
+        assert_eq!(press(event::Key::Delete), (String::from("2sin()"), 6));
+        assert_eq!(press(event::Key::Right), (String::from("2sin()"), 6));
+        assert_eq!(application.selected_box, SelectedBox::Function);
+
+        // Replacing the string moves the cursor back to its end.
+        application.function_input.move_cursor(1);
+        application.function_input.string = String::from("π");
+        assert_eq!(application.function_input.cursor(), 0);
+        application.process_input(&event::Key::Char('x'));
+        assert_eq!(application.function_input.string, "xπ");
+    }
+
+    #[test]
+    fn readline_shortcuts_edit_by_word() {
+        let mut input = TextInput {
+            string: String::from("sin(x)+co"),
+            cursor: None,
+        };
+        let mut press = |key| {
+            input.process_editing_input(&key);
+            (input.string.clone(), input.cursor())
+        };
+        assert_eq!(press(event::Key::Ctrl('w')), (String::from("sin(x)+"), 7));
+        assert_eq!(press(event::Key::Alt('b')), (String::from("sin(x)+"), 4));
+        assert_eq!(press(event::Key::Alt('b')), (String::from("sin(x)+"), 0));
+        assert_eq!(press(event::Key::Alt('f')), (String::from("sin(x)+"), 3));
+        assert_eq!(press(event::Key::Ctrl('e')), (String::from("sin(x)+"), 7));
+        assert_eq!(press(event::Key::Ctrl('a')), (String::from("sin(x)+"), 0));
+        assert_eq!(press(event::Key::Right), (String::from("sin(x)+"), 1));
+        assert_eq!(press(event::Key::Ctrl('k')), (String::from("s"), 1));
+        assert_eq!(press(event::Key::Ctrl('u')), (String::new(), 0));
+    }
+
+    #[test]
+    fn only_the_function_box_moves_its_cursor() {
+        let mut application = Application::new();
+        application.selected_box = SelectedBox::Variable;
+        for key in [
+            event::Key::Home,
+            event::Key::Ctrl('a'),
+            event::Key::Alt('b'),
+            event::Key::Ctrl('u'),
+            event::Key::Char('y'),
+        ] {
+            application.process_input(&key);
+        }
+        assert_eq!(application.var_name_input.string, "xy");
+        assert_eq!(application.var_name_input.cursor, None);
+    }
+
+    #[test]
+    fn cursor_and_parse_errors_are_styled() {
+        let plain = Style::default();
+        let cursor = plain.modifier(Modifier::REVERSED);
+        assert_eq!(
+            function_segments("sin(x)", Some(3), None, Color::Red),
+            vec![
+                (String::from("sin"), plain),
+                (String::from("("), cursor),
+                (String::from("x)"), plain)
+            ]
+        );
+        assert_eq!(
+            function_segments("2π", Some(3), None, Color::Red),
+            vec![(String::from("2π"), plain), (String::from(" "), cursor)]
+        );
+
+        let error = plain.fg(Color::Red).modifier(Modifier::UNDERLINED);
+        let dimmed = plain.modifier(Modifier::DIM);
+        assert_eq!(
+            function_segments("π + ) x", Some(5), Some(&(5..6)), Color::Red),
+            vec![
+                (String::from("π + "), plain),
+                (String::from(")"), error.modifier(Modifier::UNDERLINED | Modifier::REVERSED)),
+                (String::from(" x"), dimmed)
+            ]
+        );
+        assert_eq!(
+            function_segments("π + ) x", None, Some(&(5..6)), Color::Red),
+            vec![
+                (String::from("π + "), plain),
+                (String::from(")"), error),
+                (String::from(" x"), dimmed)
+            ]
+        );
+    }
+
+    #[test]
+    fn readouts_are_formatted_to_six_significant_digits() {
+        assert_eq!(format_readout(1.5, Some(1.5f64.sin())), "f(1.5) = 0.997495");
+        assert_eq!(format_readout(-2.0, Some(4.0)), "f(-2) = 4");
+        assert_eq!(format_readout(0.0, Some(1234.5678)), "f(0) = 1234.57");
+        assert_eq!(format_readout(5.0, None), "f(5) = undefined");
+        assert_eq!(format_readout(1e-7, Some(-2.5e9)), "f(1.00000e-7) = -2.50000e9");
+    }
+
+    #[test]
+    fn readout_follows_the_trace_line() {
+        let mut application = Application::new();
+        application.update();
+        assert_eq!(application.readout_text(), READOUT_PLACEHOLDER);
+
+        application.process_input(&event::Key::Alt('.'));
+        application.update();
+        assert_eq!(application.readout_text(), format_readout(5.0, Some(5f64.sin())));
+
+        application.function_input.string = String::from("ln(x - 5)");
+        application.update();
+        assert_eq!(application.readout_text(), "f(5) = undefined");
+
+        application.function_input.string = String::from(")");
+        application.update();
+        assert_eq!(application.readout_text(), READOUT_PLACEHOLDER);
+    }
+
+    #[test]
+    fn only_changes_to_what_is_sampled_replot() {
+        let size = Rect::new(0, 0, 80, 24);
+        let mut application = Application::new();
+        application.update();
+        let mut replots = |event: event::Event| {
+            match application.process_event(event, size) {
+                ApplicationOperation::Replot => true,
+                ApplicationOperation::Redraw => false,
+                ApplicationOperation::Exit => panic!("the application exited"),
+            }
+        };
+        assert!(replots(event::Event::Key(event::Key::Char('+'))));
+        assert!(replots(event::Event::Key(event::Key::Ctrl('r'))));
+        assert!(!replots(event::Event::Key(event::Key::Ctrl('t'))));
+        assert!(!replots(event::Event::Key(event::Key::Ctrl('d'))));
+        assert!(!replots(event::Event::Key(event::Key::Left)));
+        assert!(!replots(event::Event::Key(event::Key::End)));
+        assert!(!replots(event::Event::Key(event::Key::Alt('.'))));
+        let click = event::MouseEvent::Press(event::MouseButton::Left, 40, 12);
+        assert!(!replots(event::Event::Mouse(click)));
+    }
+
+    #[test]
+    fn trace_line_reads_off_the_plotted_function_right_away() {
+        let mut application = Application::new();
+        application.update();
+        application.process_input(&event::Key::Alt('.'));
+        assert_eq!(application.cursor_x, Some(5.0));
+        assert_eq!(application.cursor_y, Some(5f64.sin()));
+    }
+
+    #[test]
+    fn cursor_reads_off_the_function() {
+        let mut application = Application::new();
+        application.function_input.string = String::from("ln(x - 5)");
+        application.process_input(&event::Key::Alt('.'));
+        application.update();
+        assert_eq!(application.cursor_x, Some(5.0));
+        assert_eq!(application.cursor_y, None);
+        assert_eq!(cursor_label("x", 5.0, application.cursor_y), "x = 5.00, y = undefined");
+
+        application.process_input(&event::Key::Alt('.'));
+        application.update();
+        assert!((application.cursor_y.unwrap() - 0.1f64.ln()).abs() < 1e-9);
+        assert_eq!(cursor_label("x", 5.1, application.cursor_y), "x = 5.10, y = -2.30");
+    }
+
+    #[test]
+    fn slow_updates_are_announced() {
+        let mut application = Application::new();
+        application.update();
+        application.begin_update();
+        assert!(!application.computing);
+
+        application.update_duration = SLOW_UPDATE;
+        application.begin_update();
+        assert!(application.computing);
+        application.update();
+        assert!(!application.computing);
+    }
+
+    #[test]
+    fn partially_typed_functions_are_plotted() {
+        let mut application = Application::new();
+        application.resolution = 10;
+        application.function_input.string = String::from("x^2");
+        application.update();
+        let complete = application.evaluation.clone();
+
+        application.function_input.string = String::from("x^2 +");
+        application.update();
+        assert_eq!(application.evaluation, complete);
+        assert_eq!(application.status, "Parse error: could not parse `+`");
+
+        application.function_input.string = String::from("(x^2");
+        application.update();
+        assert_eq!(application.evaluation, complete);
+        assert_eq!(application.status, "Warning: unclosed parenthesis");
+
+        application.function_input.string = String::from("*");
+        application.update();
+        assert!(application.evaluation.is_empty());
+        assert_eq!(application.status, "Parse error: could not parse `*`");
+
+        application.function_input.string = String::from("x^2");
+        application.update();
+        assert_eq!(application.status, "");
+    }
+}
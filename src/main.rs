@@ -3,17 +3,35 @@ extern crate sexe_expression;
 extern crate termion;
 extern crate tui;
 
+use std::env;
 use std::io;
+use std::process;
 
+mod cli;
+mod config;
+mod editing;
 mod interface;
 
 fn main() -> Result<(), io::Error> {
-    let should_display_interface = true;
-    if should_display_interface {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match cli::parse_args(&args) {
+        // Sample without a terminal interface, e.g. to pipe the points somewhere.
+        Ok(cli::Command::Sample(sample)) => cli::sample(sample),
         // Display the interface and hand control over to `display` module.
-        interface::display()
-    } else {
-        // Do nothing, eventually other options will be added.
-        Ok(())
+        Ok(cli::Command::Display(path)) => {
+            let config = match path.map(|path| config::load(&path)) {
+                Some(Ok(config)) => config,
+                Some(Err(error)) => {
+                    eprintln!("{}", error);
+                    process::exit(1);
+                }
+                None => config::Config::default(),
+            };
+            interface::display(&config)
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(2);
+        }
     }
 }